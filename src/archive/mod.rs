@@ -0,0 +1,326 @@
+//! Streaming ZIP writer (store mode)
+//!
+//! Entries are copied straight from their source into any `AsyncWrite`; the
+//! CRC is computed on the fly and written in a data descriptor after each
+//! entry, so nothing is buffered in memory or spooled to temp files. ZIP64
+//! fields are only emitted for entries, offsets and entry counts that need
+//! them, which keeps small archives readable by old tools.
+
+use chrono::{DateTime, Datelike, Local, Timelike};
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const DATA_DESCRIPTOR: u32 = 0x0807_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const ZIP64_END: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR: u32 = 0x0706_4b50;
+const END: u32 = 0x0605_4b50;
+
+/// Data descriptor follows the data (bit 3); names are UTF-8 (bit 11)
+const FLAGS: u16 = 0x0808;
+const VERSION_DEFAULT: u16 = 20;
+const VERSION_ZIP64: u16 = 45;
+/// Unix host, so external attributes carry permissions
+const VERSION_MADE_BY: u16 = (3 << 8) | VERSION_ZIP64;
+const ZIP64_EXTRA: u16 = 0x0001;
+
+/// Largest value of a classic 32-bit size or offset field
+const LIMIT_32: u64 = 0xFFFF_FFFF;
+const LIMIT_16: usize = 0xFFFF;
+
+struct CentralEntry {
+    name: Vec<u8>,
+    crc: u32,
+    size: u64,
+    offset: u64,
+    zip64: bool,
+    time: u16,
+    date: u16,
+}
+
+pub struct ZipWriter<W> {
+    out: W,
+    /// Bytes written so far
+    offset: u64,
+    entries: Vec<CentralEntry>,
+}
+
+/// MS-DOS time and date fields; dates before 1980 are clamped
+fn dos_datetime(time: DateTime<Local>) -> (u16, u16) {
+    if time.year() < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let dos_time = (time.hour() << 11) | (time.minute() << 5) | (time.second() / 2);
+    let dos_date = (((time.year() - 1980) as u32) << 9) | (time.month() << 5) | time.day();
+    (dos_time as u16, dos_date as u16)
+}
+
+/// Little-endian field writer
+#[derive(Default)]
+struct Record(Vec<u8>);
+
+impl Record {
+    fn u16(mut self, v: u16) -> Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn u32(mut self, v: u32) -> Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn u64(mut self, v: u64) -> Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn bytes(mut self, v: &[u8]) -> Self {
+        self.0.extend_from_slice(v);
+        self
+    }
+}
+
+/// 32-bit field value, or the ZIP64 marker when it does not fit
+fn field_32(v: u64) -> u32 {
+    v.min(LIMIT_32) as u32
+}
+
+impl<W: AsyncWrite + Unpin> ZipWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            offset: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    async fn write(&mut self, record: Record) -> io::Result<()> {
+        self.out.write_all(&record.0).await?;
+        self.offset += record.0.len() as u64;
+        Ok(())
+    }
+
+    /// Append an entry, copying `reader` to the end
+    ///
+    /// `expected_size` decides whether the local header uses ZIP64; a
+    /// source that grows past 4GB without having announced it is an error.
+    pub async fn add<R: AsyncRead + Unpin>(
+        &mut self,
+        name: &str,
+        mut reader: R,
+        expected_size: u64,
+        modified: DateTime<Local>,
+    ) -> io::Result<()> {
+        let name = name.as_bytes().to_vec();
+        if name.len() > LIMIT_16 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "ZIP entry name too long"));
+        }
+        let zip64 = expected_size >= LIMIT_32;
+        let (time, date) = dos_datetime(modified);
+        let offset = self.offset;
+
+        let mut header = Record::default()
+            .u32(LOCAL_HEADER)
+            .u16(if zip64 { VERSION_ZIP64 } else { VERSION_DEFAULT })
+            .u16(FLAGS)
+            .u16(0) // stored
+            .u16(time)
+            .u16(date)
+            .u32(0) // CRC and sizes follow in the data descriptor
+            .u32(if zip64 { u32::MAX } else { 0 })
+            .u32(if zip64 { u32::MAX } else { 0 })
+            .u16(name.len() as u16)
+            .u16(if zip64 { 20 } else { 0 })
+            .bytes(&name);
+        if zip64 {
+            header = header.u16(ZIP64_EXTRA).u16(16).u64(0).u64(0);
+        }
+        self.write(header).await?;
+
+        let mut hasher = crc32fast::Hasher::new();
+        let mut size = 0u64;
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = reader.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            self.out.write_all(&buf[..n]).await?;
+            size += n as u64;
+        }
+        self.offset += size;
+        if size >= LIMIT_32 && !zip64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "ZIP entry grew past 4GB while archiving"));
+        }
+
+        let crc = hasher.finalize();
+        let descriptor = Record::default().u32(DATA_DESCRIPTOR).u32(crc);
+        let descriptor = if zip64 {
+            descriptor.u64(size).u64(size)
+        } else {
+            descriptor.u32(size as u32).u32(size as u32)
+        };
+        self.write(descriptor).await?;
+
+        self.entries.push(CentralEntry {
+            name,
+            crc,
+            size,
+            offset,
+            zip64,
+            time,
+            date,
+        });
+        Ok(())
+    }
+
+    /// Write the central directory and return the underlying writer
+    pub async fn finish(mut self) -> io::Result<W> {
+        let central_start = self.offset;
+        let entries = std::mem::take(&mut self.entries);
+
+        for entry in &entries {
+            let mut extra = Record::default();
+            if entry.zip64 {
+                extra = extra.u64(entry.size).u64(entry.size);
+            }
+            if entry.offset >= LIMIT_32 {
+                extra = extra.u64(entry.offset);
+            }
+            let extra = if extra.0.is_empty() {
+                extra
+            } else {
+                Record::default().u16(ZIP64_EXTRA).u16(extra.0.len() as u16).bytes(&extra.0)
+            };
+            let sizes = if entry.zip64 { u32::MAX } else { entry.size as u32 };
+
+            let header = Record::default()
+                .u32(CENTRAL_HEADER)
+                .u16(VERSION_MADE_BY)
+                .u16(if extra.0.is_empty() { VERSION_DEFAULT } else { VERSION_ZIP64 })
+                .u16(FLAGS)
+                .u16(0)
+                .u16(entry.time)
+                .u16(entry.date)
+                .u32(entry.crc)
+                .u32(sizes)
+                .u32(sizes)
+                .u16(entry.name.len() as u16)
+                .u16(extra.0.len() as u16)
+                .u16(0) // comment
+                .u16(0) // disk number
+                .u16(0) // internal attributes
+                .u32(0o100644 << 16)
+                .u32(field_32(entry.offset))
+                .bytes(&entry.name)
+                .bytes(&extra.0);
+            self.write(header).await?;
+        }
+
+        let central_size = self.offset - central_start;
+        let count = entries.len() as u64;
+        if count >= LIMIT_16 as u64 || central_start >= LIMIT_32 || central_size >= LIMIT_32 {
+            let zip64_end = self.offset;
+            let record = Record::default()
+                .u32(ZIP64_END)
+                .u64(44)
+                .u16(VERSION_MADE_BY)
+                .u16(VERSION_ZIP64)
+                .u32(0)
+                .u32(0)
+                .u64(count)
+                .u64(count)
+                .u64(central_size)
+                .u64(central_start);
+            self.write(record).await?;
+            let locator = Record::default().u32(ZIP64_LOCATOR).u32(0).u64(zip64_end).u32(1);
+            self.write(locator).await?;
+        }
+
+        let count_16 = count.min(LIMIT_16 as u64) as u16;
+        let end = Record::default()
+            .u32(END)
+            .u16(0)
+            .u16(0)
+            .u16(count_16)
+            .u16(count_16)
+            .u32(field_32(central_size))
+            .u32(field_32(central_start))
+            .u16(0);
+        self.write(end).await?;
+        self.out.flush().await?;
+        Ok(self.out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(data: &[u8], pos: usize) -> usize {
+        u16::from_le_bytes([data[pos], data[pos + 1]]) as usize
+    }
+
+    fn u32_at(data: &[u8], pos: usize) -> u32 {
+        u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_zip_round_trip() {
+        let photo: Vec<u8> = (0..100_000u32).map(|i| (i % 241) as u8).collect();
+        let modified = Local::now();
+
+        let mut zip = ZipWriter::new(Vec::new());
+        zip.add("Summer/IMG_0001.jpg", &photo[..], photo.len() as u64, modified).await.unwrap();
+        zip.add("manifest.json", &b"[]"[..], 2, modified).await.unwrap();
+        let data = zip.finish().await.unwrap();
+
+        // End of central directory -> central directory -> local headers
+        let end = data.len() - 22;
+        assert_eq!(u32_at(&data, end), END);
+        assert_eq!(u16_at(&data, end + 10), 2);
+        let mut pos = u32_at(&data, end + 16) as usize;
+
+        let mut found = Vec::new();
+        for _ in 0..2 {
+            assert_eq!(u32_at(&data, pos), CENTRAL_HEADER);
+            let crc = u32_at(&data, pos + 16);
+            let size = u32_at(&data, pos + 24) as usize;
+            let name_len = u16_at(&data, pos + 28);
+            let extra_len = u16_at(&data, pos + 30);
+            let local = u32_at(&data, pos + 42) as usize;
+            let name = String::from_utf8(data[pos + 46..pos + 46 + name_len].to_vec()).unwrap();
+
+            assert_eq!(u32_at(&data, local), LOCAL_HEADER);
+            let start = local + 30 + u16_at(&data, local + 26) + u16_at(&data, local + 28);
+            let content = &data[start..start + size];
+            assert_eq!(crc32fast::hash(content), crc);
+            // Data descriptor right after the data
+            assert_eq!(u32_at(&data, start + size), DATA_DESCRIPTOR);
+            assert_eq!(u32_at(&data, start + size + 4), crc);
+
+            found.push((name, content.to_vec()));
+            pos += 46 + name_len + extra_len;
+        }
+        assert_eq!(found[0], ("Summer/IMG_0001.jpg".to_string(), photo));
+        assert_eq!(found[1], ("manifest.json".to_string(), b"[]".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_zip64_entry() {
+        // Announcing a huge entry switches its headers to ZIP64
+        let mut zip = ZipWriter::new(Vec::new());
+        zip.add("big.mov", &b"data"[..], LIMIT_32, Local::now()).await.unwrap();
+        let data = zip.finish().await.unwrap();
+
+        assert_eq!(u16_at(&data, 4), VERSION_ZIP64 as usize);
+        assert_eq!(u32_at(&data, 22), u32::MAX);
+        assert_eq!(u16_at(&data, 30 + 7), ZIP64_EXTRA as usize);
+        let descriptor = 30 + 7 + 20 + 4;
+        assert_eq!(u32_at(&data, descriptor), DATA_DESCRIPTOR);
+        assert_eq!(u64::from_le_bytes(data[descriptor + 8..descriptor + 16].try_into().unwrap()), 4);
+    }
+}
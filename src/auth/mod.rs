@@ -50,6 +50,69 @@ pub fn verify_token(token: &str, secret: &str) -> anyhow::Result<Claims> {
     Ok(token_data.claims)
 }
 
+/// 分享链接 token 的 claims
+///
+/// `sub` 为 `share` 时是分享链接本身；为 `share-access` 时是输入密码后
+/// 签发的访问凭证（存放在 Cookie 中）；为 `guest-upload` 时是访客上传链接。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShareClaims {
+    pub sub: String,
+    /// 分享 ID
+    pub sid: String,
+    pub iat: i64,
+    /// 不过期的分享没有 exp
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+}
+
+/// 分享访问凭证的有效期
+const SHARE_ACCESS_HOURS: i64 = 12;
+
+/// 创建分享链接 token；同一分享每次生成的 token 相同
+pub fn create_share_token(secret: &str, share: &crate::models::Share) -> anyhow::Result<String> {
+    let claims = ShareClaims {
+        sub: "share".to_string(),
+        sid: share.id.clone(),
+        iat: share.created_at.timestamp(),
+        exp: share.expires_at.map(|t| t.timestamp()),
+    };
+    Ok(encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes()))?)
+}
+
+/// 创建访客上传链接 token
+pub fn create_guest_token(secret: &str, link: &crate::models::GuestLink) -> anyhow::Result<String> {
+    let claims = ShareClaims {
+        sub: "guest-upload".to_string(),
+        sid: link.id.clone(),
+        iat: link.created_at.timestamp(),
+        exp: link.expires_at.map(|t| t.timestamp()),
+    };
+    Ok(encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes()))?)
+}
+
+/// 创建输入密码后的访问凭证，返回 token 和有效秒数
+pub fn create_share_access_token(secret: &str, share_id: &str) -> anyhow::Result<(String, i64)> {
+    let now = Utc::now();
+    let claims = ShareClaims {
+        sub: "share-access".to_string(),
+        sid: share_id.to_string(),
+        iat: now.timestamp(),
+        exp: Some((now + Duration::hours(SHARE_ACCESS_HOURS)).timestamp()),
+    };
+    let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes()))?;
+    Ok((token, SHARE_ACCESS_HOURS * 3600))
+}
+
+/// 验证分享相关 token，`sub` 必须与预期一致
+pub fn verify_share_token(token: &str, secret: &str, sub: &str) -> anyhow::Result<ShareClaims> {
+    let mut validation = Validation::default();
+    // exp 可选，存在时仍会校验
+    validation.required_spec_claims.clear();
+    let claims = decode::<ShareClaims>(token, &DecodingKey::from_secret(secret.as_bytes()), &validation)?.claims;
+    anyhow::ensure!(claims.sub == sub, "unexpected token subject");
+    Ok(claims)
+}
+
 /// JWT 认证中间件
 pub async fn require_admin_auth(
     State(state): State<AppState>,
@@ -70,3 +133,30 @@ pub async fn require_admin_auth(
         _ => Err(StatusCode::UNAUTHORIZED),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_share_tokens() {
+        let mut share = crate::models::Share {
+            id: "abc".to_string(),
+            photo_id: Some(1),
+            album: None,
+            password_hash: None,
+            disable_download: false,
+            expires_at: None,
+            created_at: Utc::now(),
+        };
+        let token = create_share_token("secret", &share).unwrap();
+        assert_eq!(verify_share_token(&token, "secret", "share").unwrap().sid, "abc");
+        assert!(verify_share_token(&token, "other", "share").is_err());
+        // A share link is not an access token
+        assert!(verify_share_token(&token, "secret", "share-access").is_err());
+
+        share.expires_at = Some(Utc::now() - Duration::hours(1));
+        let expired = create_share_token("secret", &share).unwrap();
+        assert!(verify_share_token(&expired, "secret", "share").is_err());
+    }
+}
@@ -0,0 +1,398 @@
+//! Library-wide thumbnail rebuild / backfill
+//!
+//! Walks every photo in id order and regenerates thumbnails that are
+//! missing, point at deleted files, or were made with different rendition
+//! settings. Progress is checkpointed in `background_jobs` after every photo,
+//! so an interrupted run picks up after the last processed id. Also home to
+//! the garbage collection pass for thumbnails no photo references.
+
+use crate::config::Config;
+use crate::db::Database;
+use crate::models::{BackgroundJob, JobStatus, Photo, PhotoRendition};
+use crate::thumbnail::ThumbnailGenerator;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+use tokio::sync::Mutex;
+use walkdir::WalkDir;
+
+pub const JOB_NAME: &str = "thumbnail_rebuild";
+
+/// Photos fetched per database round-trip
+const BATCH_SIZE: i64 = 50;
+
+/// Only one rebuild runs per process at a time
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RebuildOptions {
+    /// Regenerate every thumbnail, even ones that look up to date
+    pub force: bool,
+    /// Start from the beginning instead of resuming an interrupted run
+    pub restart: bool,
+}
+
+pub fn is_running() -> bool {
+    RUNNING.load(Ordering::SeqCst)
+}
+
+/// Proof that this caller holds the single rebuild slot; released on drop
+pub struct RunGuard(());
+
+/// Claim the rebuild slot, or `None` if a rebuild is already running
+pub fn try_start() -> Option<RunGuard> {
+    RUNNING
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .ok()
+        .map(|_| RunGuard(()))
+}
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Whether a photo's thumbnails need to be (re)generated
+///
+/// Thumbnails stored outside the content-addressed layout (older
+/// `thumb_<uuid>.jpg` files) count as stale so a rebuild migrates them.
+pub fn needs_rebuild(photo: &Photo, renditions: &[PhotoRendition], config: &Config) -> bool {
+    let (Some(thumbnail_path), Some(hash)) = (&photo.thumbnail_path, &photo.file_hash) else {
+        return true;
+    };
+    let misplaced = |r: &PhotoRendition| {
+        let expected = crate::config::ThumbnailFormat::parse(&r.format)
+            .map(|f| ThumbnailGenerator::rendition_path(config, hash, r.max_size, f));
+        expected.as_deref() != Some(Path::new(&r.path))
+    };
+
+    photo.thumbnail_spec.as_deref() != Some(config.thumbnails.signature().as_str())
+        || renditions.is_empty()
+        || !Path::new(thumbnail_path).exists()
+        || renditions.iter().any(|r| !Path::new(&r.path).exists() || misplaced(r))
+}
+
+/// Run (or resume) the rebuild job, calling `on_progress` after each batch
+/// and once more when finished
+///
+/// The caller claims the slot with [`try_start`] first, so it can report a
+/// conflict before handing the work off to a background task.
+pub async fn run(
+    _guard: RunGuard,
+    db: Arc<Mutex<Database>>,
+    config: Config,
+    options: RebuildOptions,
+    mut on_progress: impl FnMut(&BackgroundJob),
+) -> Result<BackgroundJob> {
+    let now = chrono::Utc::now();
+
+    let mut job = {
+        let db = db.lock().await;
+        let mut job = match db.get_job(JOB_NAME)? {
+            Some(job) if job.status == JobStatus::Running && !options.restart => {
+                tracing::info!("Resuming thumbnail rebuild after photo {}", job.last_id);
+                job
+            }
+            _ => BackgroundJob {
+                name: JOB_NAME.to_string(),
+                status: JobStatus::Running,
+                last_id: 0,
+                total: 0,
+                processed: 0,
+                succeeded: 0,
+                failed: 0,
+                force: options.force,
+                started_at: now,
+                updated_at: now,
+            },
+        };
+        job.force |= options.force;
+        job.total = job.processed + db.count_photos_after(job.last_id)?;
+        job.updated_at = now;
+        db.save_job(&job)?;
+        job
+    };
+    on_progress(&job);
+
+    loop {
+        let batch = db.lock().await.list_photos_after(job.last_id, BATCH_SIZE)?;
+        if batch.is_empty() {
+            break;
+        }
+
+        for photo in batch {
+            match rebuild_photo(&db, &config, &photo, job.force).await {
+                Ok(true) => job.succeeded += 1,
+                Ok(false) => {}
+                Err(e) => {
+                    tracing::warn!("Failed to rebuild thumbnails for photo {}: {:#}", photo.id, e);
+                    job.failed += 1;
+                }
+            }
+
+            job.processed += 1;
+            job.last_id = photo.id;
+            job.updated_at = chrono::Utc::now();
+            db.lock().await.save_job(&job)?;
+        }
+
+        on_progress(&job);
+    }
+
+    job.status = JobStatus::Completed;
+    job.total = job.processed;
+    job.updated_at = chrono::Utc::now();
+    db.lock().await.save_job(&job)?;
+    on_progress(&job);
+
+    tracing::info!(
+        processed = job.processed,
+        regenerated = job.succeeded,
+        failed = job.failed,
+        "Thumbnail rebuild completed"
+    );
+    Ok(job)
+}
+
+/// Regenerate one photo's thumbnails if needed; returns whether it did
+async fn rebuild_photo(
+    db: &Arc<Mutex<Database>>,
+    config: &Config,
+    photo: &Photo,
+    force: bool,
+) -> Result<bool> {
+    let old = db.lock().await.list_renditions(photo.id)?;
+    if !force && !needs_rebuild(photo, &old, config) {
+        return Ok(false);
+    }
+
+    let content_hash = match &photo.file_hash {
+        Some(hash) => hash.clone(),
+        None => {
+            let path = Path::new(&photo.local_path).to_path_buf();
+            tokio::task::spawn_blocking(move || crate::duplicates::hash_file(&path)).await??
+        }
+    };
+    let thumb = ThumbnailGenerator::generate(Path::new(&photo.local_path), &content_hash, config).await?;
+    // Store a freshly computed hash so the photo is not rebuilt on every run
+    db.lock().await.update_thumbnail_info(photo.id, &thumb, Some(&content_hash))?;
+
+    // Drop the files of the previous generation
+    let mut stale: Vec<String> = old.into_iter().map(|r| r.path).collect();
+    stale.extend(photo.thumbnail_path.clone());
+    stale.sort();
+    stale.dedup();
+    for path in stale {
+        if thumb.renditions.iter().any(|r| r.path == path) {
+            continue;
+        }
+        if let Err(e) = tokio::fs::remove_file(&path).await
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            tracing::warn!("Failed to delete old thumbnail {}: {}", path, e);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Files younger than this are left alone by garbage collection, so a
+/// thumbnail written after the reference list was read is never removed
+const GC_GRACE_PERIOD: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Default, Serialize)]
+pub struct GcReport {
+    pub scanned: usize,
+    pub removed: Vec<String>,
+    pub bytes_freed: u64,
+}
+
+/// Remove thumbnail files that no photo references
+///
+/// The on-demand cache (`.thumbnails/cache`) manages its own size and is
+/// skipped.
+pub async fn collect_garbage(db: &Arc<Mutex<Database>>, config: &Config, dry_run: bool) -> Result<GcReport> {
+    let referenced = db.lock().await.list_thumbnail_paths()?;
+    let dir = ThumbnailGenerator::thumbnail_dir(config);
+
+    let report = tokio::task::spawn_blocking(move || sweep(&dir, &referenced, GC_GRACE_PERIOD, dry_run)).await??;
+    tracing::info!(
+        scanned = report.scanned,
+        removed = report.removed.len(),
+        bytes_freed = report.bytes_freed,
+        dry_run = dry_run,
+        "Thumbnail garbage collection completed"
+    );
+    Ok(report)
+}
+
+fn sweep(dir: &Path, referenced: &HashSet<String>, min_age: Duration, dry_run: bool) -> Result<GcReport> {
+    let mut report = GcReport::default();
+    if !dir.exists() {
+        return Ok(report);
+    }
+
+    let cache_dir = dir.join("cache");
+    let now = SystemTime::now();
+    for entry in WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|e| e.path() != cache_dir)
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+    {
+        report.scanned += 1;
+        let path = entry.path();
+        if referenced.contains(path.to_string_lossy().as_ref()) {
+            continue;
+        }
+
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        let age = meta.modified().ok().and_then(|m| now.duration_since(m).ok()).unwrap_or_default();
+        if age < min_age {
+            continue;
+        }
+
+        if !dry_run && let Err(e) = std::fs::remove_file(path) {
+            tracing::warn!("Failed to delete orphaned thumbnail {}: {}", path.display(), e);
+            continue;
+        }
+        report.bytes_freed += meta.len();
+        report.removed.push(path.to_string_lossy().to_string());
+    }
+
+    // Drop shard directories left empty
+    if !dry_run {
+        for entry in WalkDir::new(dir)
+            .min_depth(1)
+            .contents_first(true)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_dir() && !e.path().starts_with(&cache_dir))
+        {
+            // Fails harmlessly for non-empty directories
+            let _ = std::fs::remove_dir(entry.path());
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rebuild_and_resume() {
+        let dir = std::env::temp_dir().join(format!("skynas_backfill_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join(".skynas")).unwrap();
+        let mut config = Config::default();
+        config.storage.base_path = dir.clone();
+
+        let db = Database::new(dir.join(".skynas").join("test.db")).unwrap();
+        for i in 0..3 {
+            let path = dir.join(format!("p{}.png", i));
+            image::DynamicImage::ImageRgb8(image::ImageBuffer::from_pixel(40, 30, image::Rgb([i * 50, 0, 0])))
+                .save(&path)
+                .unwrap();
+            db.insert_photo(&Photo {
+                filename: format!("p{}.png", i),
+                album: "a".to_string(),
+                file_hash: Some(format!("hash{}", i)),
+                local_path: path.to_string_lossy().to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        }
+        let db = Arc::new(Mutex::new(db));
+
+        // Simulate a run interrupted after the first photo
+        let first_id = db.lock().await.list_photos_after(0, 1).unwrap()[0].id;
+        let now = chrono::Utc::now();
+        db.lock().await.save_job(&BackgroundJob {
+            name: JOB_NAME.to_string(),
+            status: JobStatus::Running,
+            last_id: first_id,
+            total: 3,
+            processed: 1,
+            succeeded: 0,
+            failed: 0,
+            force: false,
+            started_at: now,
+            updated_at: now,
+        }).unwrap();
+
+        let job = run(try_start().unwrap(), db.clone(), config.clone(), RebuildOptions::default(), |_| {}).await.unwrap();
+        assert_eq!(job.status, JobStatus::Completed);
+        assert_eq!((job.processed, job.succeeded), (3, 2));
+
+        let photos = db.lock().await.list_all_photos().unwrap();
+        assert!(photos[0].thumbnail_path.is_none());
+        assert!(photos[1..].iter().all(|p| p.thumbnail_path.is_some()));
+
+        // A fresh run only fills in the skipped photo
+        let options = RebuildOptions { force: false, restart: true };
+        let job = run(try_start().unwrap(), db.clone(), config.clone(), options, |_| {}).await.unwrap();
+        assert_eq!((job.processed, job.succeeded), (3, 1));
+
+        // A photo from before hashing gets its hash stored and then settles
+        let path = dir.join("legacy.png");
+        image::DynamicImage::ImageRgb8(image::ImageBuffer::from_pixel(40, 30, image::Rgb([0, 200, 0])))
+            .save(&path)
+            .unwrap();
+        let legacy_id = db.lock().await.insert_photo(&Photo {
+            filename: "legacy.png".to_string(),
+            album: "a".to_string(),
+            local_path: path.to_string_lossy().to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        let job = run(try_start().unwrap(), db.clone(), config.clone(), options, |_| {}).await.unwrap();
+        assert_eq!(job.succeeded, 1);
+        let legacy = db.lock().await.get_photo(legacy_id).unwrap().unwrap();
+        assert_eq!(legacy.file_hash, Some(crate::duplicates::hash_file(&path).unwrap()));
+        let job = run(try_start().unwrap(), db.clone(), config, options, |_| {}).await.unwrap();
+        assert_eq!(job.succeeded, 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sweep_removes_unreferenced() {
+        let dir = std::env::temp_dir().join(format!("skynas_gc_{}", uuid::Uuid::new_v4()));
+        let shard = dir.join("ab").join("cd");
+        std::fs::create_dir_all(&shard).unwrap();
+        std::fs::create_dir_all(dir.join("ef").join("01")).unwrap();
+        std::fs::create_dir_all(dir.join("cache")).unwrap();
+
+        let kept = shard.join("abcd_256.jpg");
+        std::fs::write(&kept, b"kept").unwrap();
+        std::fs::write(shard.join("abcd_768.jpg"), b"orphan").unwrap();
+        std::fs::write(dir.join("ef").join("01").join("ef01_256.jpg"), b"orphan").unwrap();
+        std::fs::write(dir.join("cache").join("x.jpg"), b"cached").unwrap();
+
+        let referenced: HashSet<String> = [kept.to_string_lossy().to_string()].into();
+
+        let report = sweep(&dir, &referenced, Duration::ZERO, true).unwrap();
+        assert_eq!(report.removed.len(), 2);
+        assert!(shard.join("abcd_768.jpg").exists());
+
+        // Files younger than the grace period are skipped
+        let report = sweep(&dir, &referenced, Duration::from_secs(3600), false).unwrap();
+        assert!(report.removed.is_empty());
+
+        let report = sweep(&dir, &referenced, Duration::ZERO, false).unwrap();
+        assert_eq!((report.scanned, report.removed.len(), report.bytes_freed), (3, 2, 12));
+        assert!(kept.exists());
+        assert!(!dir.join("ef").exists());
+        assert!(dir.join("cache").join("x.jpg").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -37,4 +37,61 @@ pub enum Commands {
 
     /// Run as menu bar app (macOS only)
     MenuBar,
+
+    /// Find and merge files with identical content
+    Duplicates {
+        #[command(subcommand)]
+        action: DuplicatesAction,
+    },
+
+    /// Maintain generated thumbnails
+    Thumbnails {
+        #[command(subcommand)]
+        action: ThumbnailsAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DuplicatesAction {
+    /// List groups of identical files with their albums and paths
+    List,
+
+    /// Keep one file per group and delete the redundant copies
+    Merge {
+        /// Only merge the group with this SHA-256 hash
+        #[arg(long)]
+        hash: Option<String>,
+
+        /// Photo ID to keep (defaults to the oldest copy)
+        #[arg(long)]
+        keep: Option<i64>,
+
+        /// Show what would be removed without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ThumbnailsAction {
+    /// Regenerate missing or stale thumbnails, resuming an interrupted run
+    Rebuild {
+        /// Regenerate every thumbnail, not just missing or stale ones
+        #[arg(long)]
+        force: bool,
+
+        /// Start over instead of resuming an interrupted run
+        #[arg(long)]
+        restart: bool,
+    },
+
+    /// Show the state of the last rebuild
+    Status,
+
+    /// Delete thumbnail files that no photo references
+    Gc {
+        /// Show what would be removed without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
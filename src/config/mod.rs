@@ -8,6 +8,8 @@ pub struct Config {
     pub sync: SyncConfig,
     pub heic_converter: HeicConverterConfig,
     pub features: FeaturesConfig,
+    pub dedup: DedupConfig,
+    pub thumbnails: ThumbnailConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,9 +37,18 @@ pub struct SyncConfig {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HeicConverterConfig {
-    pub backend: String, // "image", "libheif", "sips"
+    pub backend: String, // "libheif", "sips", "command" ("image" cannot decode HEIC)
     pub generate_jpeg: bool,
     pub jpeg_quality: u8,
+    /// Argument template for the `command` backend, run without a shell;
+    /// `{input}`, `{output}` and `{quality}` are substituted per argument
+    pub command: String,
+    /// Seconds before a `command` conversion is killed
+    pub command_timeout_secs: u64,
+    /// Maximum number of conversions running at once; further uploads queue
+    pub max_concurrent: usize,
+    /// Leave GPS location out of the EXIF copied into JPEG variants
+    pub strip_gps: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,6 +59,118 @@ pub struct FeaturesConfig {
     pub qr_code_enabled: bool,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupConfig {
+    /// Default Hamming distance (out of 64 bits) below which two perceptual
+    /// hashes are treated as near-duplicates
+    pub phash_max_distance: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThumbnailConfig {
+    /// Renditions generated for every photo; several entries may share a
+    /// size with different formats so clients can negotiate via `Accept`
+    pub renditions: Vec<RenditionConfig>,
+    /// Size cap of the on-demand resize cache (`.thumbnails/cache`)
+    pub cache_max_bytes: u64,
+    /// Largest width/height accepted by `/api/photos/:id/image?w=&h=`
+    pub max_on_demand_size: u32,
+    /// Encoder quality for on-demand renditions (1-100)
+    pub on_demand_quality: u8,
+    /// Commands tried in order for videos and files `image` cannot decode
+    pub external_thumbnailers: Vec<ExternalThumbnailerConfig>,
+    /// Maximum number of external thumbnailer processes at once
+    pub external_concurrency: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternalThumbnailerConfig {
+    pub name: String,
+    /// Split on whitespace; `{input}`, `{output}` and `{size}` are
+    /// substituted in each argument. The output is decoded as an image.
+    pub command: String,
+    /// Lower-case file extensions handled; empty means any file
+    pub extensions: Vec<String>,
+    pub timeout_secs: u64,
+}
+
+impl ThumbnailConfig {
+    /// Compact description of the rendition settings, stored with each
+    /// photo so thumbnails made under different settings can be detected
+    pub fn signature(&self) -> String {
+        self.renditions
+            .iter()
+            .map(|r| format!("{}:{}:{}", r.max_size, r.format.as_str(), r.quality))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenditionConfig {
+    pub max_size: u32, // longest edge in pixels
+    pub format: ThumbnailFormat,
+    pub quality: u8, // 1-100
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailFormat {
+    Jpeg,
+    Webp,
+    Avif,
+}
+
+impl ThumbnailFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ThumbnailFormat::Jpeg => "jpeg",
+            ThumbnailFormat::Webp => "webp",
+            ThumbnailFormat::Avif => "avif",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "jpeg" | "jpg" => Some(ThumbnailFormat::Jpeg),
+            "webp" => Some(ThumbnailFormat::Webp),
+            "avif" => Some(ThumbnailFormat::Avif),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ThumbnailFormat::Jpeg => "jpg",
+            ThumbnailFormat::Webp => "webp",
+            ThumbnailFormat::Avif => "avif",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ThumbnailFormat::Jpeg => "image/jpeg",
+            ThumbnailFormat::Webp => "image/webp",
+            ThumbnailFormat::Avif => "image/avif",
+        }
+    }
+
+    /// This format if the build can encode it, otherwise JPEG
+    pub fn or_fallback(self) -> Self {
+        if self.is_supported() { self } else { ThumbnailFormat::Jpeg }
+    }
+
+    /// Whether this build can encode the format (WebP and AVIF encoders
+    /// are behind the `webp` and `avif` cargo features)
+    pub fn is_supported(&self) -> bool {
+        match self {
+            ThumbnailFormat::Jpeg => true,
+            ThumbnailFormat::Webp => cfg!(feature = "webp"),
+            ThumbnailFormat::Avif => cfg!(feature = "avif"),
+        }
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/tmp"));
@@ -72,9 +195,22 @@ impl Default for Config {
                 sync_delay_seconds: 5,
             },
             heic_converter: HeicConverterConfig {
-                backend: "image".to_string(),
+                // libheif works everywhere when compiled in; sips is macOS-only,
+                // elsewhere fall back to the external `heif-convert` command
+                backend: if cfg!(feature = "libheif") {
+                    "libheif"
+                } else if cfg!(target_os = "macos") {
+                    "sips"
+                } else {
+                    "command"
+                }
+                .to_string(),
                 generate_jpeg: true,
                 jpeg_quality: 85,
+                command: "heif-convert -q {quality} {input} {output}".to_string(),
+                command_timeout_secs: 60,
+                max_concurrent: 2,
+                strip_gps: false,
             },
             features: FeaturesConfig {
                 mdns_enabled: true,
@@ -82,6 +218,37 @@ impl Default for Config {
                 notification_enabled: true,
                 qr_code_enabled: true,
             },
+            dedup: DedupConfig {
+                phash_max_distance: 10,
+            },
+            thumbnails: ThumbnailConfig {
+                renditions: vec![
+                    RenditionConfig { max_size: 256, format: ThumbnailFormat::Webp, quality: 75 },
+                    RenditionConfig { max_size: 256, format: ThumbnailFormat::Jpeg, quality: 80 },
+                    RenditionConfig { max_size: 768, format: ThumbnailFormat::Webp, quality: 80 },
+                    RenditionConfig { max_size: 768, format: ThumbnailFormat::Jpeg, quality: 85 },
+                    RenditionConfig { max_size: 1600, format: ThumbnailFormat::Jpeg, quality: 85 },
+                ],
+                cache_max_bytes: 512 * 1024 * 1024, // 512MB
+                max_on_demand_size: 4096,
+                on_demand_quality: 85,
+                external_thumbnailers: vec![
+                    ExternalThumbnailerConfig {
+                        name: "ffmpeg".to_string(),
+                        command: "ffmpeg -y -loglevel error -ss 1 -i {input} -frames:v 1 {output}".to_string(),
+                        extensions: ["mp4", "mov", "m4v", "3gp"].iter().map(|e| e.to_string()).collect(),
+                        timeout_secs: 30,
+                    },
+                    // Picks a representative frame; also covers clips shorter than 1s
+                    ExternalThumbnailerConfig {
+                        name: "ffmpegthumbnailer".to_string(),
+                        command: "ffmpegthumbnailer -i {input} -o {output} -s 0".to_string(),
+                        extensions: ["mp4", "mov", "m4v", "3gp"].iter().map(|e| e.to_string()).collect(),
+                        timeout_secs: 30,
+                    },
+                ],
+                external_concurrency: 2,
+            },
         }
     }
 }
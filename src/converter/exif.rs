@@ -0,0 +1,140 @@
+//! Adjusting an EXIF block (a TIFF stream) before it is copied into a
+//! converted JPEG variant
+
+use super::tiff::Tiff;
+
+const TAG_ORIENTATION: u16 = 0x0112;
+const TAG_GPS_IFD: u16 = 0x8825;
+
+/// Prepare EXIF copied from a HEIC for its JPEG variant
+///
+/// Converters write upright pixels, so `Orientation` is reset to 1 (keeping
+/// the original value would rotate the image twice). With `strip_gps` the
+/// GPS IFD is removed from IFD0 and its bytes are zeroed, so the location
+/// cannot be recovered from the variant. Returns `None` for blocks that are
+/// not valid TIFF.
+pub fn prepare_for_variant(exif: &[u8], strip_gps: bool) -> Option<Vec<u8>> {
+    let tiff = Tiff::new(exif)?;
+    if tiff.u16(2)? != 42 {
+        return None;
+    }
+    let ifd0 = tiff.first_ifd()? as usize;
+    let (entries, next) = tiff.entries(ifd0)?;
+
+    let mut out = exif.to_vec();
+
+    let mut kept = Vec::with_capacity(entries.len());
+    for &entry in &entries {
+        match tiff.u16(entry)? {
+            TAG_ORIENTATION if tiff.u16(entry + 2)? == 3 => {
+                if let Some(target) = region_mut(&mut out, entry + 8, 2) {
+                    target.copy_from_slice(&tiff.encode_u16(1));
+                }
+                kept.push(entry);
+            }
+            TAG_GPS_IFD if strip_gps => {
+                if let Some(&gps) = tiff.values(entry).first() {
+                    for (offset, len) in ifd_regions(&tiff, gps as usize) {
+                        if let Some(target) = region_mut(&mut out, offset, len) {
+                            target.fill(0);
+                        }
+                    }
+                }
+            }
+            _ => kept.push(entry),
+        }
+    }
+
+    if kept.len() != entries.len() {
+        // Rewrite IFD0 without the removed entries; the freed tail is zeroed
+        let mut ifd = Vec::with_capacity(2 + entries.len() * 12 + 4);
+        ifd.extend_from_slice(&tiff.encode_u16(kept.len() as u16));
+        for &entry in &kept {
+            ifd.extend_from_slice(&out[entry..entry + 12]);
+        }
+        ifd.extend_from_slice(&tiff.encode_u32(next));
+        ifd.resize(2 + entries.len() * 12 + 4, 0);
+        out[ifd0..ifd0 + ifd.len()].copy_from_slice(&ifd);
+    }
+
+    Some(out)
+}
+
+/// `len` bytes at `offset`, if they lie within `out`
+///
+/// Entry tables are bounds-checked; out-of-line values come straight from
+/// the file and may not be.
+fn region_mut(out: &mut [u8], offset: usize, len: usize) -> Option<&mut [u8]> {
+    out.get_mut(offset..offset.checked_add(len)?)
+}
+
+/// Byte ranges of an IFD and the out-of-line values it points to
+fn ifd_regions(tiff: &Tiff, offset: usize) -> Vec<(usize, usize)> {
+    let Some((entries, _)) = tiff.entries(offset) else {
+        return Vec::new();
+    };
+    let mut regions: Vec<(usize, usize)> = entries
+        .iter()
+        .filter_map(|&entry| tiff.value_location(entry))
+        .filter(|&(_, size)| size > 4)
+        .collect();
+    regions.push((offset, 2 + entries.len() * 12 + 4));
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prepare_for_variant() {
+        // IFD0: Orientation=6, GPS IFD pointer; GPS IFD: GPSLatitude (3 RATIONAL)
+        let mut exif = b"MM\0\x2a\0\0\0\x08".to_vec();
+        exif.extend_from_slice(&[0, 2]);
+        exif.extend_from_slice(&[0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0]);
+        exif.extend_from_slice(&[0x88, 0x25, 0, 4, 0, 0, 0, 1, 0, 0, 0, 38]);
+        exif.extend_from_slice(&[0, 0, 0, 0]);
+        assert_eq!(exif.len(), 38);
+        exif.extend_from_slice(&[0, 1]);
+        exif.extend_from_slice(&[0x00, 0x02, 0, 5, 0, 0, 0, 3, 0, 0, 0, 56]);
+        exif.extend_from_slice(&[0, 0, 0, 0]);
+        for value in [52u32, 1, 31, 1, 0, 1] {
+            exif.extend_from_slice(&value.to_be_bytes());
+        }
+
+        let kept = prepare_for_variant(&exif, false).unwrap();
+        let tiff = Tiff::new(&kept).unwrap();
+        let (tags, _) = tiff.read_ifd(8).unwrap();
+        assert_eq!(tags[&TAG_ORIENTATION], vec![1]);
+        assert_eq!(tags[&TAG_GPS_IFD], vec![38]);
+        assert_eq!(&kept[38..], &exif[38..]);
+
+        let stripped = prepare_for_variant(&exif, true).unwrap();
+        let tiff = Tiff::new(&stripped).unwrap();
+        let (tags, next) = tiff.read_ifd(8).unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[&TAG_ORIENTATION], vec![1]);
+        assert_eq!(next, 0);
+        assert!(stripped[26..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_strip_gps_with_oversized_count() {
+        // GPS IFD entry claims 0xFFFFFFFF RATIONALs (~34 GB) at offset 56
+        let mut exif = b"MM\0\x2a\0\0\0\x08".to_vec();
+        exif.extend_from_slice(&[0, 1]);
+        exif.extend_from_slice(&[0x88, 0x25, 0, 4, 0, 0, 0, 1, 0, 0, 0, 26]);
+        exif.extend_from_slice(&[0, 0, 0, 0]);
+        assert_eq!(exif.len(), 26);
+        exif.extend_from_slice(&[0, 1]);
+        exif.extend_from_slice(&[0x00, 0x02, 0, 5, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 44]);
+        exif.extend_from_slice(&[0, 0, 0, 0]);
+        exif.extend_from_slice(&[0xAB; 8]);
+
+        let stripped = prepare_for_variant(&exif, true).unwrap();
+        let (tags, _) = Tiff::new(&stripped).unwrap().read_ifd(8).unwrap();
+        assert!(tags.is_empty());
+        // The GPS IFD itself is still zeroed
+        assert!(stripped[26..44].iter().all(|&b| b == 0));
+    }
+}
@@ -0,0 +1,45 @@
+//! HEIC/HEIF decoding through libheif (`libheif` cargo feature)
+
+use anyhow::{Context, Result};
+use image::{DynamicImage, RgbImage, RgbaImage};
+use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+use std::path::Path;
+
+/// Decode the primary image of a HEIC/HEIF file
+///
+/// The container's rotation and mirroring are already applied, so the
+/// result is upright and needs no EXIF orientation tag.
+pub fn decode(path: &Path) -> Result<DynamicImage> {
+    let lib_heif = LibHeif::new();
+    let path_str = path.to_str().context("HEIF path is not valid UTF-8")?;
+    let ctx = HeifContext::read_from_file(path_str)
+        .with_context(|| format!("Failed to read HEIF file: {:?}", path))?;
+    let handle = ctx.primary_image_handle()?;
+
+    let has_alpha = handle.has_alpha_channel();
+    let chroma = if has_alpha { RgbChroma::Rgba } else { RgbChroma::Rgb };
+    // Transformations (irot/imir/clap) are applied by default
+    let decoded = lib_heif
+        .decode(&handle, ColorSpace::Rgb(chroma), None)
+        .with_context(|| format!("Failed to decode HEIF image: {:?}", path))?;
+
+    let planes = decoded.planes();
+    let plane = planes.interleaved.context("Decoded HEIF image has no interleaved plane")?;
+    let (width, height) = (plane.width, plane.height);
+    let channels = if has_alpha { 4 } else { 3 };
+    let row_bytes = width as usize * channels;
+
+    // Rows may be padded; copy them out tightly packed
+    let mut pixels = Vec::with_capacity(row_bytes * height as usize);
+    for row in plane.data.chunks(plane.stride).take(height as usize) {
+        pixels.extend_from_slice(&row[..row_bytes]);
+    }
+
+    let image = if has_alpha {
+        RgbaImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8)
+    } else {
+        RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8)
+    }
+    .context("HEIF plane size does not match its dimensions")?;
+    Ok(image)
+}
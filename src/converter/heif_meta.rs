@@ -0,0 +1,255 @@
+//! EXIF and ICC blocks read straight from a HEIF container (ISO BMFF)
+//!
+//! Independent of the conversion backend, so variants written by libheif,
+//! `sips` or an external command all get the same metadata.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Metadata blocks of a HEIF file
+#[derive(Debug, Default, PartialEq)]
+pub struct HeifMetadata {
+    /// EXIF as a TIFF stream (starting at the `II`/`MM` header)
+    pub exif: Option<Vec<u8>>,
+    /// Colour profile from a `colr` box of type `prof` or `rICC`
+    pub icc: Option<Vec<u8>>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn uint(&mut self, size: usize) -> Option<u64> {
+        let bytes = self.bytes(size)?;
+        Some(bytes.iter().fold(0, |acc, &b| (acc << 8) | b as u64))
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.uint(1).map(|v| v as u8)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.uint(2).map(|v| v as u16)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.uint(4).map(|v| v as u32)
+    }
+
+    /// Next box as (type, body)
+    fn next_box(&mut self) -> Option<(&'a [u8], &'a [u8])> {
+        let start = self.pos;
+        let size = self.u32()? as u64;
+        let kind = self.bytes(4)?;
+        let size = match size {
+            0 => (self.data.len() - start) as u64,
+            1 => self.uint(8)?,
+            size => size,
+        };
+        let header = (self.pos - start) as u64;
+        let body = self.bytes(size.checked_sub(header)? as usize)?;
+        Some((kind, body))
+    }
+}
+
+/// Children of a box body, as (type, body)
+fn boxes(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    let mut reader = Reader::new(data);
+    std::iter::from_fn(move || reader.next_box())
+}
+
+fn find_box<'a>(data: &'a [u8], kind: &[u8]) -> Option<&'a [u8]> {
+    boxes(data).find(|(k, _)| *k == kind).map(|(_, body)| body)
+}
+
+/// IDs of `Exif` items listed in `iinf`
+fn exif_item_ids(iinf: &[u8]) -> Option<Vec<u32>> {
+    let mut reader = Reader::new(iinf);
+    let version = reader.u8()?;
+    reader.bytes(3)?;
+    reader.uint(if version == 0 { 2 } else { 4 })?;
+
+    let mut ids = Vec::new();
+    for (kind, infe) in boxes(&iinf[reader.pos..]) {
+        if kind != b"infe" {
+            continue;
+        }
+        let mut reader = Reader::new(infe);
+        let version = reader.u8()?;
+        if version < 2 {
+            continue;
+        }
+        reader.bytes(3)?;
+        let id = if version == 2 { reader.u16()? as u32 } else { reader.u32()? };
+        reader.u16()?;
+        if reader.bytes(4)? == b"Exif" {
+            ids.push(id);
+        }
+    }
+    Some(ids)
+}
+
+/// File extents (offset, length) of an item according to `iloc`
+fn item_extents(iloc: &[u8], item_id: u32) -> Option<Vec<(u64, u64)>> {
+    let mut reader = Reader::new(iloc);
+    let version = reader.u8()?;
+    reader.bytes(3)?;
+    let sizes = reader.u8()?;
+    let (offset_size, length_size) = ((sizes >> 4) as usize, (sizes & 0xF) as usize);
+    let sizes = reader.u8()?;
+    let base_offset_size = (sizes >> 4) as usize;
+    let index_size = if version >= 1 { (sizes & 0xF) as usize } else { 0 };
+    let count = if version < 2 { reader.u16()? as u32 } else { reader.u32()? };
+
+    for _ in 0..count {
+        let id = if version < 2 { reader.u16()? as u32 } else { reader.u32()? };
+        let construction_method = if version >= 1 { reader.u16()? & 0xF } else { 0 };
+        reader.u16()?;
+        let base_offset = reader.uint(base_offset_size)?;
+        let extent_count = reader.u16()?;
+        let mut extents = Vec::with_capacity(extent_count as usize);
+        for _ in 0..extent_count {
+            reader.uint(index_size)?;
+            let offset = reader.uint(offset_size)?;
+            let length = reader.uint(length_size)?;
+            extents.push((base_offset.checked_add(offset)?, length));
+        }
+        // Only plain file offsets; EXIF is never stored in `idat` in practice
+        if id == item_id && construction_method == 0 {
+            return Some(extents);
+        }
+    }
+    None
+}
+
+/// Read EXIF and ICC blocks from HEIF file contents
+pub fn parse(data: &[u8]) -> HeifMetadata {
+    let Some(meta) = find_box(data, b"meta") else {
+        return HeifMetadata::default();
+    };
+    // `meta` is a full box: skip version and flags
+    let meta = meta.get(4..).unwrap_or_default();
+
+    let exif = find_box(meta, b"iinf")
+        .and_then(exif_item_ids)
+        .unwrap_or_default()
+        .into_iter()
+        .find_map(|id| {
+            let extents = item_extents(find_box(meta, b"iloc")?, id)?;
+            let mut item = Vec::new();
+            for (offset, length) in extents {
+                item.extend_from_slice(data.get(offset as usize..offset.checked_add(length)? as usize)?);
+            }
+            // The item starts with the offset of the TIFF header (usually
+            // after an `Exif\0\0` prefix)
+            let skip = u32::from_be_bytes(item.get(..4)?.try_into().ok()?) as usize;
+            let tiff = item.get(4 + skip..)?;
+            (tiff.starts_with(b"II") || tiff.starts_with(b"MM")).then(|| tiff.to_vec())
+        });
+
+    let icc = find_box(meta, b"iprp")
+        .and_then(|iprp| find_box(iprp, b"ipco"))
+        .and_then(|ipco| {
+            boxes(ipco)
+                .filter(|(kind, _)| *kind == b"colr")
+                .find_map(|(_, colr)| match colr.get(..4)? {
+                    b"prof" | b"rICC" => Some(colr[4..].to_vec()),
+                    _ => None,
+                })
+        });
+
+    HeifMetadata { exif, icc }
+}
+
+/// Read EXIF and ICC blocks from a HEIF file
+pub fn read(path: &Path) -> Result<HeifMetadata> {
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+    Ok(parse(&data))
+}
+
+/// Build a minimal HEIF container holding an EXIF item and an ICC profile
+#[cfg(test)]
+pub(crate) fn sample_container(exif: &[u8], icc: &[u8]) -> Vec<u8> {
+    fn bx(kind: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut out = ((8 + body.len()) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(body);
+        out
+    }
+
+    let mut item = vec![0, 0, 0, 6];
+    item.extend_from_slice(b"Exif\0\0");
+    item.extend_from_slice(exif);
+
+    let mut infe = vec![2, 0, 0, 0, 0, 7, 0, 0];
+    infe.extend_from_slice(b"Exif\0");
+    let mut iinf = vec![0, 0, 0, 0, 0, 1];
+    iinf.extend(bx(b"infe", &infe));
+
+    let mut colr = b"prof".to_vec();
+    colr.extend_from_slice(icc);
+    let iprp = bx(b"iprp", &bx(b"ipco", &bx(b"colr", &colr)));
+
+    let build = |offset: u32| {
+        // iloc v0: 4-byte offsets and lengths, no base offset, one item
+        let mut iloc = vec![0, 0, 0, 0, 0x44, 0x00, 0, 1, 0, 7, 0, 0, 0, 1];
+        iloc.extend_from_slice(&offset.to_be_bytes());
+        iloc.extend_from_slice(&(item.len() as u32).to_be_bytes());
+
+        let mut meta = vec![0, 0, 0, 0];
+        meta.extend(bx(b"iinf", &iinf));
+        meta.extend(bx(b"iloc", &iloc));
+        meta.extend(iprp.clone());
+
+        let mut out = bx(b"ftyp", b"heic\0\0\0\0mif1heic");
+        out.extend(bx(b"meta", &meta));
+        out
+    };
+    // The item lives in `mdat` right after `meta`
+    let offset = build(0).len() as u32 + 8;
+    let mut out = build(offset);
+    out.extend(bx(b"mdat", &item));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sample_container() {
+        let exif = b"MM\0\x2a\0\0\0\x08\0\0\0\0\0\0";
+        let icc = b"fake icc profile";
+        let parsed = parse(&sample_container(exif, icc));
+        assert_eq!(parsed.exif.as_deref(), Some(&exif[..]));
+        assert_eq!(parsed.icc.as_deref(), Some(&icc[..]));
+
+        assert_eq!(parse(b"not a heif file"), HeifMetadata::default());
+    }
+
+    #[test]
+    fn test_item_extents_overflowing_offset() {
+        // version 0, 8-byte offsets/lengths/base offset, one item with one extent
+        let mut iloc = vec![0, 0, 0, 0, 0x88, 0x80];
+        iloc.extend(1u16.to_be_bytes()); // item count
+        iloc.extend(1u16.to_be_bytes()); // item id
+        iloc.extend(0u16.to_be_bytes()); // data reference index
+        iloc.extend(u64::MAX.to_be_bytes()); // base offset
+        iloc.extend(1u16.to_be_bytes()); // extent count
+        iloc.extend(2u64.to_be_bytes()); // extent offset
+        iloc.extend(4u64.to_be_bytes()); // extent length
+        assert_eq!(item_extents(&iloc, 1), None);
+    }
+}
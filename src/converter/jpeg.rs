@@ -0,0 +1,132 @@
+//! Splicing metadata segments into encoded JPEG files
+//!
+//! The `image` encoder writes bare JFIF streams and external converters
+//! write whatever they like, so EXIF (APP1 `Exif`) and colour profiles
+//! (APP2 `ICC_PROFILE`) are inserted afterwards, right after SOI.
+
+const SOI: [u8; 2] = [0xFF, 0xD8];
+const APP0: u8 = 0xE0;
+const APP1: u8 = 0xE1;
+const APP2: u8 = 0xE2;
+const SOS: u8 = 0xDA;
+const EXIF_SIGNATURE: &[u8] = b"Exif\0\0";
+const ICC_SIGNATURE: &[u8] = b"ICC_PROFILE\0";
+
+/// Largest payload of one marker segment (the length field counts itself)
+const MAX_SEGMENT_PAYLOAD: usize = 65535 - 2;
+
+/// Build the APP2 segments carrying an ICC profile
+///
+/// Profiles larger than one segment are split and numbered as described in
+/// the ICC specification (annex B.4).
+fn icc_segments(icc: &[u8]) -> Vec<u8> {
+    let chunk_size = MAX_SEGMENT_PAYLOAD - ICC_SIGNATURE.len() - 2;
+    let chunks: Vec<&[u8]> = icc.chunks(chunk_size).collect();
+    let count = chunks.len() as u8;
+
+    let mut out = Vec::with_capacity(icc.len() + chunks.len() * 18);
+    for (i, chunk) in chunks.iter().enumerate() {
+        let length = (2 + ICC_SIGNATURE.len() + 2 + chunk.len()) as u16;
+        out.extend_from_slice(&[0xFF, APP2]);
+        out.extend_from_slice(&length.to_be_bytes());
+        out.extend_from_slice(ICC_SIGNATURE);
+        out.push(i as u8 + 1);
+        out.push(count);
+        out.extend_from_slice(chunk);
+    }
+    out
+}
+
+/// Build the APP1 segment carrying an EXIF block (a TIFF stream)
+fn exif_segment(exif: &[u8]) -> Option<Vec<u8>> {
+    let length = u16::try_from(2 + EXIF_SIGNATURE.len() + exif.len()).ok()?;
+    let mut out = Vec::with_capacity(length as usize + 2);
+    out.extend_from_slice(&[0xFF, APP1]);
+    out.extend_from_slice(&length.to_be_bytes());
+    out.extend_from_slice(EXIF_SIGNATURE);
+    out.extend_from_slice(exif);
+    Some(out)
+}
+
+/// Insert EXIF and/or an ICC profile into a JPEG stream
+///
+/// Existing segments of the same kind are replaced. New segments go after
+/// SOI and any leading APP0 (JFIF) segment, which must stay first. EXIF
+/// over 64KB and profiles over 255 segments (~16MB) do not fit in a JPEG
+/// and are left out.
+pub fn embed_metadata(jpeg: &[u8], exif: Option<&[u8]>, icc: Option<&[u8]>) -> anyhow::Result<Vec<u8>> {
+    if jpeg.len() < 4 || jpeg[..2] != SOI {
+        anyhow::bail!("Not a JPEG stream");
+    }
+    let exif = exif.filter(|e| !e.is_empty()).and_then(exif_segment);
+    let icc = icc
+        .filter(|icc| !icc.is_empty() && icc.len() <= 255 * (MAX_SEGMENT_PAYLOAD - ICC_SIGNATURE.len() - 2))
+        .map(icc_segments);
+
+    let mut out = Vec::with_capacity(jpeg.len());
+    out.extend_from_slice(&SOI);
+    let mut inserted = false;
+    let mut pos = 2;
+    // Walk the header segments up to the scan data
+    while pos + 4 <= jpeg.len() && jpeg[pos] == 0xFF && jpeg[pos + 1] != SOS {
+        let marker = jpeg[pos + 1];
+        let length = u16::from_be_bytes([jpeg[pos + 2], jpeg[pos + 3]]) as usize;
+        let end = (pos + 2 + length).min(jpeg.len());
+        let body = &jpeg[(pos + 4).min(end)..end];
+
+        if !inserted && marker != APP0 {
+            out.extend(exif.iter().chain(icc.iter()).flatten());
+            inserted = true;
+        }
+        let replaced = (marker == APP1 && exif.is_some() && body.starts_with(EXIF_SIGNATURE))
+            || (marker == APP2 && icc.is_some() && body.starts_with(ICC_SIGNATURE));
+        if !replaced {
+            out.extend_from_slice(&jpeg[pos..end]);
+        }
+        pos = end;
+    }
+    if !inserted {
+        out.extend(exif.iter().chain(icc.iter()).flatten());
+    }
+    out.extend_from_slice(&jpeg[pos..]);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embed_metadata() {
+        let img = image::DynamicImage::ImageRgb8(image::ImageBuffer::from_pixel(4, 4, image::Rgb([200, 100, 50])));
+        let mut jpeg = Vec::new();
+        img.write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageOutputFormat::Jpeg(90))
+            .unwrap();
+
+        // Large enough to need two segments
+        let icc: Vec<u8> = (0..70_000u32).map(|i| (i % 251) as u8).collect();
+        let first = embed_metadata(&jpeg, Some(b"MM\0\x2a old"), Some(&icc)).unwrap();
+        // Embedding again replaces rather than duplicates
+        let out = embed_metadata(&first, Some(b"MM\0\x2a new"), Some(&icc)).unwrap();
+
+        // Still decodes, and the profile can be reassembled in order
+        assert!(image::load_from_memory(&out).is_ok());
+        let mut pos = 2;
+        let mut reassembled = Vec::new();
+        let mut exif = Vec::new();
+        while pos + 4 <= out.len() && out[pos] == 0xFF && out[pos + 1] != SOS {
+            let length = u16::from_be_bytes([out[pos + 2], out[pos + 3]]) as usize;
+            let body = &out[pos + 4..pos + 2 + length];
+            if out[pos + 1] == APP2 && body.starts_with(ICC_SIGNATURE) {
+                assert_eq!(body[13], 2);
+                reassembled.extend_from_slice(&body[14..]);
+            }
+            if out[pos + 1] == APP1 && body.starts_with(EXIF_SIGNATURE) {
+                exif.push(body[EXIF_SIGNATURE.len()..].to_vec());
+            }
+            pos += 2 + length;
+        }
+        assert_eq!(reassembled, icc);
+        assert_eq!(exif, vec![b"MM\0\x2a new".to_vec()]);
+    }
+}
@@ -1,6 +1,73 @@
 use crate::config::HeicConverterConfig;
-use std::path::Path;
-use std::process::Command;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+mod exif;
+#[cfg(feature = "libheif")]
+mod heif;
+mod heif_meta;
+pub mod jpeg;
+pub mod raw;
+mod tiff;
+
+/// Whether the file is HEIC/HEIF by extension
+pub fn is_heif(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.eq_ignore_ascii_case("heic") || e.eq_ignore_ascii_case("heif"))
+        .unwrap_or(false)
+}
+
+/// Decode any supported image, using libheif for HEIC/HEIF when available
+/// and the embedded preview for RAW files
+///
+/// Shared by the JPEG converter and the thumbnail generator so both see the
+/// same (upright) pixels.
+pub fn decode_image(path: &Path) -> anyhow::Result<image::DynamicImage> {
+    if raw::is_raw(path) {
+        return raw::decode_preview(path);
+    }
+
+    #[cfg(feature = "libheif")]
+    if is_heif(path) {
+        return heif::decode(path);
+    }
+
+    Ok(image::open(path)?)
+}
+
+/// Caps the number of conversions running at once
+static PERMITS: OnceLock<Semaphore> = OnceLock::new();
+/// Conversions waiting for a permit
+static QUEUED: AtomicUsize = AtomicUsize::new(0);
+/// Conversions holding a permit
+static RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of conversions (queued, running) right now
+pub fn queue_status() -> (usize, usize) {
+    (QUEUED.load(Ordering::SeqCst), RUNNING.load(Ordering::SeqCst))
+}
+
+/// Decrements a counter when dropped, so aborted tasks stay accounted for
+struct CountGuard(&'static AtomicUsize);
+
+impl CountGuard {
+    fn new(counter: &'static AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter)
+    }
+}
+
+impl Drop for CountGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
 
 pub struct HeicConverter {
     config: HeicConverterConfig,
@@ -11,62 +78,133 @@ impl HeicConverter {
         Self { config }
     }
 
+    /// Whether `convert` would produce a variant for this file
+    pub fn applies_to(&self, input_path: &Path) -> bool {
+        self.config.generate_jpeg && (is_heif(input_path) || raw::is_raw(input_path))
+    }
+
+    /// Convert on the blocking thread pool, at most `max_concurrent` at once
+    ///
+    /// When every slot is busy `on_queued` receives the 1-based queue
+    /// position; `on_started` runs once the conversion actually begins.
+    pub async fn convert_queued(
+        self,
+        input_path: PathBuf,
+        on_queued: impl FnOnce(usize),
+        on_started: impl FnOnce(),
+    ) -> anyhow::Result<Option<PathBuf>> {
+        let semaphore = PERMITS.get_or_init(|| Semaphore::new(self.config.max_concurrent.max(1)));
+        let _permit = match semaphore.try_acquire() {
+            Ok(permit) => permit,
+            Err(_) => {
+                let _queued = CountGuard::new(&QUEUED);
+                on_queued(QUEUED.load(Ordering::SeqCst));
+                semaphore.acquire().await?
+            }
+        };
+
+        let _running = CountGuard::new(&RUNNING);
+        on_started();
+        tokio::task::spawn_blocking(move || self.convert(&input_path)).await?
+    }
+
     pub fn convert(&self, input_path: &Path) -> anyhow::Result<Option<std::path::PathBuf>> {
-        if !self.config.generate_jpeg {
+        if !self.applies_to(input_path) {
             return Ok(None);
         }
 
-        // Check if file is HEIC
-        let extension = input_path
-            .extension()
-            .and_then(|e| e.to_str())
-            .map(|e| e.to_lowercase());
+        let output_path = input_path.with_extension("jpg");
 
-        if extension.as_deref() != Some("heic") && extension.as_deref() != Some("heif") {
+        // A same-stem JPEG already next to a fresh original is someone
+        // else's photo (a RAW+JPEG pair, an earlier upload); it is never
+        // replaced, so such files simply get no variant
+        if output_path.exists() {
             return Ok(None);
         }
 
-        let output_path = input_path.with_extension("jpg");
+        // RAW previews need no backend
+        if raw::is_raw(input_path) {
+            return self.convert_raw(input_path, &output_path);
+        }
 
-        match self.config.backend.as_str() {
+        let converted = match self.config.backend.as_str() {
             "image" => self.convert_with_image(input_path, &output_path),
             "libheif" => self.convert_with_libheif(input_path, &output_path),
             "sips" => self.convert_with_sips(input_path, &output_path),
-            _ => {
-                eprintln!(
-                    "Unknown converter backend: {}, falling back to image",
-                    self.config.backend
-                );
-                self.convert_with_image(input_path, &output_path)
+            "command" => self.convert_with_command(input_path, &output_path),
+            other => Err(anyhow::anyhow!(
+                "unknown heic_converter backend `{}` (expected libheif, sips or command)",
+                other
+            )),
+        }?;
+
+        if let Some(output) = &converted {
+            // The variant is still usable without metadata
+            if let Err(e) = self.copy_metadata(input_path, output) {
+                tracing::warn!("Failed to copy metadata into {:?}: {:#}", output, e);
             }
         }
+        Ok(converted)
+    }
+
+    /// Copy EXIF (capture date, camera, GPS unless `strip_gps`) and the
+    /// colour profile from the HEIC into its JPEG variant
+    fn copy_metadata(&self, input: &Path, output: &Path) -> anyhow::Result<()> {
+        let metadata = heif_meta::read(input)?;
+        let exif = metadata
+            .exif
+            .as_deref()
+            .and_then(|block| exif::prepare_for_variant(block, self.config.strip_gps));
+        if exif.is_none() && metadata.icc.is_none() {
+            return Ok(());
+        }
+
+        let jpeg = std::fs::read(output)?;
+        let jpeg = jpeg::embed_metadata(&jpeg, exif.as_deref(), metadata.icc.as_deref())?;
+        std::fs::write(output, jpeg)?;
+        Ok(())
     }
 
+    /// The `image` crate cannot decode HEIC, so this backend only reports
+    /// how to get a working one instead of silently producing no variant
     fn convert_with_image(
         &self,
         _input: &Path,
         _output: &Path,
     ) -> anyhow::Result<Option<std::path::PathBuf>> {
-        // Note: image crate has limited HEIC support
-        // This is a placeholder - in production, use libheif-rs
-        eprintln!(
-            "Image crate HEIC support is limited. Consider using 'libheif' or 'sips' backend."
-        );
+        Err(anyhow::anyhow!(
+            "the `image` backend cannot decode HEIC; build skynas with the `libheif` feature \
+             or set heic_converter.backend to `sips` (macOS) or `command`"
+        ))
+    }
+
+    #[cfg(feature = "libheif")]
+    fn convert_with_libheif(
+        &self,
+        input: &Path,
+        output: &Path,
+    ) -> anyhow::Result<Option<std::path::PathBuf>> {
+        use image::codecs::jpeg::JpegEncoder;
+
+        // JPEG has no alpha channel
+        let rgb = heif::decode(input)?.to_rgb8();
+        let mut bytes = Vec::new();
+        JpegEncoder::new_with_quality(&mut bytes, self.config.jpeg_quality)
+            .encode_image(&rgb)?;
 
-        // For now, just return None to indicate no conversion
-        // In a real implementation, you'd use libheif-rs crate
-        Ok(None)
+        std::fs::write(output, bytes)?;
+        Ok(Some(output.to_path_buf()))
     }
 
+    #[cfg(not(feature = "libheif"))]
     fn convert_with_libheif(
         &self,
         _input: &Path,
         _output: &Path,
     ) -> anyhow::Result<Option<std::path::PathBuf>> {
-        // This would require libheif-sys or libheif-rs
-        // Placeholder implementation
-        eprintln!("libheif backend not yet implemented");
-        Ok(None)
+        Err(anyhow::anyhow!(
+            "libheif backend requires building skynas with the `libheif` feature"
+        ))
     }
 
     fn convert_with_sips(
@@ -94,4 +232,280 @@ impl HeicConverter {
             Err(anyhow::anyhow!("sips conversion failed"))
         }
     }
+
+    /// Write the embedded preview of a RAW file as its JPEG variant
+    fn convert_raw(
+        &self,
+        input: &Path,
+        output: &Path,
+    ) -> anyhow::Result<Option<std::path::PathBuf>> {
+        let bytes = raw::preview_jpeg(input, self.config.jpeg_quality)?;
+        std::fs::write(output, bytes)?;
+        Ok(Some(output.to_path_buf()))
+    }
+
+    /// Run the configured command template, e.g. `heif-convert`,
+    /// ImageMagick's `magick` or `vips copy`
+    ///
+    /// The command runs without a shell and is killed after
+    /// `command_timeout_secs`. The output only counts once it decodes; a
+    /// failure carries the command's stderr.
+    fn convert_with_command(
+        &self,
+        input: &Path,
+        output: &Path,
+    ) -> anyhow::Result<Option<std::path::PathBuf>> {
+        let args = render_command(&self.config.command, input, output, self.config.jpeg_quality);
+        let (program, args) = args
+            .split_first()
+            .ok_or_else(|| anyhow::anyhow!("Empty converter command"))?;
+
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Failed to start {}: {}", program, e))?;
+
+        // Drain stderr on a separate thread so a chatty command cannot block
+        // on a full pipe while we wait for it
+        let mut stderr_pipe = child.stderr.take();
+        let stderr_reader = std::thread::spawn(move || {
+            let mut stderr = String::new();
+            if let Some(pipe) = stderr_pipe.as_mut() {
+                let _ = pipe.read_to_string(&mut stderr);
+            }
+            stderr
+        });
+
+        let timeout = Duration::from_secs(self.config.command_timeout_secs);
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break Some(status);
+            }
+            if started.elapsed() >= timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                break None;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        };
+        let stderr = stderr_reader.join().unwrap_or_default();
+        let stderr = stderr.trim();
+
+        let result = match status {
+            None => Err(anyhow::anyhow!(
+                "{} timed out after {}s",
+                program,
+                self.config.command_timeout_secs
+            )),
+            Some(status) if !status.success() => {
+                Err(anyhow::anyhow!("{} exited with {}: {}", program, status, stderr))
+            }
+            Some(_) => validate_output(output).map_err(|e| {
+                if stderr.is_empty() {
+                    e
+                } else {
+                    anyhow::anyhow!("{}: {}", e, stderr)
+                }
+            }),
+        };
+
+        match result {
+            Ok(()) => Ok(Some(output.to_path_buf())),
+            Err(e) => {
+                // Never leave a partial or bogus variant next to the original
+                let _ = std::fs::remove_file(output);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Build the argument list for the `command` backend
+fn render_command(template: &str, input: &Path, output: &Path, quality: u8) -> Vec<String> {
+    template
+        .split_whitespace()
+        .map(|arg| {
+            arg.replace("{input}", &input.to_string_lossy())
+                .replace("{output}", &output.to_string_lossy())
+                .replace("{quality}", &quality.to_string())
+        })
+        .collect()
+}
+
+/// Check that a converter produced a decodable image
+fn validate_output(output: &Path) -> anyhow::Result<()> {
+    if !output.is_file() {
+        anyhow::bail!("Converter did not produce {:?}", output);
+    }
+    image::io::Reader::open(output)?
+        .with_guessed_format()?
+        .decode()
+        .map_err(|e| anyhow::anyhow!("Converter output is not a decodable image: {}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_command() {
+        let args = render_command(
+            "magick {input} -quality {quality} {output}",
+            Path::new("/photos/My Trip/IMG_1.HEIC"),
+            Path::new("/photos/My Trip/IMG_1.jpg"),
+            85,
+        );
+        assert_eq!(args, vec![
+            "magick", "/photos/My Trip/IMG_1.HEIC", "-quality", "85", "/photos/My Trip/IMG_1.jpg",
+        ]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_backend() {
+        let dir = std::env::temp_dir().join(format!("skynas_conv_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // PNG bytes behind a HEIC extension stand in for a real conversion
+        let input = dir.join("IMG_1.heic");
+        image::DynamicImage::ImageRgb8(image::ImageBuffer::from_pixel(4, 4, image::Rgb([9, 8, 7])))
+            .save_with_format(&input, image::ImageFormat::Png)
+            .unwrap();
+        let output = dir.join("IMG_1.jpg");
+
+        let converter = |command: &str, command_timeout_secs| {
+            HeicConverter::new(HeicConverterConfig {
+                backend: "command".to_string(),
+                generate_jpeg: true,
+                jpeg_quality: 85,
+                command: command.to_string(),
+                command_timeout_secs,
+                max_concurrent: 1,
+                strip_gps: false,
+            })
+        };
+
+        let converted = converter("cp {input} {output}", 5).convert(&input).unwrap();
+        assert_eq!(converted, Some(output.clone()));
+        std::fs::remove_file(&output).unwrap();
+
+        // Output that does not decode is rejected and removed
+        let err = converter("touch {output}", 5).convert(&input).unwrap_err();
+        assert!(err.to_string().contains("not a decodable image"));
+        assert!(!output.exists());
+
+        // Failures carry stderr
+        let err = converter("ls {input}.missing", 5).convert(&input).unwrap_err();
+        assert!(err.to_string().contains("IMG_1.heic.missing"), "{}", err);
+
+        let err = converter("sleep 5", 1).convert(&input).unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_existing_same_stem_jpeg_survives() {
+        let dir = std::env::temp_dir().join(format!("skynas_conv_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("IMG_0001.HEIC");
+        image::DynamicImage::ImageRgb8(image::ImageBuffer::from_pixel(4, 4, image::Rgb([9, 8, 7])))
+            .save_with_format(&input, image::ImageFormat::Png)
+            .unwrap();
+        let existing = dir.join("IMG_0001.jpg");
+        std::fs::write(&existing, b"the user's own jpeg").unwrap();
+
+        let converter = HeicConverter::new(HeicConverterConfig {
+            backend: "command".to_string(),
+            generate_jpeg: true,
+            jpeg_quality: 85,
+            command: "cp {input} {output}".to_string(),
+            command_timeout_secs: 5,
+            max_concurrent: 1,
+            strip_gps: false,
+        });
+        assert_eq!(converter.convert(&input).unwrap(), None);
+        assert_eq!(std::fs::read(&existing).unwrap(), b"the user's own jpeg");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// A HEIC with EXIF and an ICC profile keeps both in its JPEG variant
+    #[cfg(unix)]
+    #[test]
+    fn test_metadata_round_trip() {
+        let dir = std::env::temp_dir().join(format!("skynas_meta_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // IFD0: Make, Orientation=6, DateTime, GPS IFD -> GPSLatitudeRef "N"
+        let mut exif = b"II\x2a\0\x08\0\0\0".to_vec();
+        exif.extend_from_slice(&[4, 0]);
+        exif.extend_from_slice(&[0x0F, 0x01, 2, 0, 6, 0, 0, 0, 62, 0, 0, 0]);
+        exif.extend_from_slice(&[0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0]);
+        exif.extend_from_slice(&[0x32, 0x01, 2, 0, 20, 0, 0, 0, 68, 0, 0, 0]);
+        exif.extend_from_slice(&[0x25, 0x88, 4, 0, 1, 0, 0, 0, 88, 0, 0, 0]);
+        exif.extend_from_slice(&[0, 0, 0, 0]);
+        exif.extend_from_slice(b"Apple\0");
+        exif.extend_from_slice(b"2025:07:01 10:00:00\0");
+        exif.extend_from_slice(&[1, 0]);
+        exif.extend_from_slice(&[0x01, 0x00, 2, 0, 2, 0, 0, 0, b'N', 0, 0, 0]);
+        exif.extend_from_slice(&[0, 0, 0, 0]);
+        let icc: Vec<u8> = (0..3000u32).map(|i| (i % 253) as u8).collect();
+
+        let input = dir.join("IMG_2.heic");
+        std::fs::write(&input, heif_meta::sample_container(&exif, &icc)).unwrap();
+        // Stand-in for the external tool: it writes a JPEG without metadata
+        let plain = dir.join("plain.jpg");
+        image::DynamicImage::ImageRgb8(image::ImageBuffer::from_pixel(4, 4, image::Rgb([9, 8, 7])))
+            .save_with_format(&plain, image::ImageFormat::Jpeg)
+            .unwrap();
+
+        for strip_gps in [false, true] {
+            let converter = HeicConverter::new(HeicConverterConfig {
+                backend: "command".to_string(),
+                generate_jpeg: true,
+                jpeg_quality: 85,
+                command: format!("cp {} {{output}}", plain.display()),
+                command_timeout_secs: 5,
+                max_concurrent: 1,
+                strip_gps,
+            });
+            let output = converter.convert(&input).unwrap().unwrap();
+            let jpeg = std::fs::read(&output).unwrap();
+            assert!(image::load_from_memory(&jpeg).is_ok());
+
+            // Collect APP1 Exif and APP2 ICC segments
+            let (mut copied_exif, mut copied_icc) = (None, Vec::new());
+            let mut pos = 2;
+            while jpeg[pos] == 0xFF && jpeg[pos + 1] != 0xDA {
+                let length = u16::from_be_bytes([jpeg[pos + 2], jpeg[pos + 3]]) as usize;
+                let body = &jpeg[pos + 4..pos + 2 + length];
+                match jpeg[pos + 1] {
+                    0xE1 if body.starts_with(b"Exif\0\0") => copied_exif = Some(body[6..].to_vec()),
+                    0xE2 if body.starts_with(b"ICC_PROFILE\0") => copied_icc.extend_from_slice(&body[14..]),
+                    _ => {}
+                }
+                pos += 2 + length;
+            }
+            assert_eq!(copied_icc, icc);
+
+            let copied_exif = copied_exif.unwrap();
+            let tiff = tiff::Tiff::new(&copied_exif).unwrap();
+            let (tags, _) = tiff.read_ifd(8).unwrap();
+            // Pixels are upright, so orientation must not rotate them again
+            assert_eq!(tags[&0x0112], vec![1]);
+            assert!(tags.contains_key(&0x010F));
+            assert!(copied_exif.windows(19).any(|w| w == b"2025:07:01 10:00:00"));
+            assert_eq!(tags.contains_key(&0x8825), !strip_gps);
+            assert_eq!(copied_exif[88..].contains(&b'N'), !strip_gps);
+            std::fs::remove_file(&output).unwrap();
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
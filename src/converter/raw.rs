@@ -0,0 +1,251 @@
+//! Embedded JPEG previews in TIFF-based RAW files (DNG/ProRAW, CR2, NEF, ARW, ...)
+//!
+//! Sensor data cannot be decoded here, but these containers carry a
+//! camera-rendered JPEG preview, usually at full size. The IFD chain and
+//! SubIFDs are walked and the largest baseline JPEG found is used. The
+//! preview itself has no orientation; IFD0's `Orientation` tag applies.
+
+use super::tiff::Tiff;
+use anyhow::{Context, Result};
+use image::DynamicImage;
+use std::path::Path;
+
+const RAW_EXTENSIONS: &[&str] = &[
+    "dng", "cr2", "nef", "nrw", "arw", "sr2", "pef", "orf", "rw2", "srw", "3fr", "erf",
+];
+
+const TAG_COMPRESSION: u16 = 0x0103;
+const TAG_PHOTOMETRIC: u16 = 0x0106;
+const TAG_STRIP_OFFSETS: u16 = 0x0111;
+const TAG_ORIENTATION: u16 = 0x0112;
+const TAG_STRIP_BYTE_COUNTS: u16 = 0x0117;
+const TAG_SUB_IFDS: u16 = 0x014A;
+const TAG_JPEG_OFFSET: u16 = 0x0201;
+const TAG_JPEG_LENGTH: u16 = 0x0202;
+
+/// Photometric interpretations of raw sensor data (CFA, LinearRaw)
+const RAW_PHOTOMETRIC: &[u32] = &[32803, 34892];
+
+/// Guards against malformed or cyclic IFD chains
+const MAX_IFDS: usize = 64;
+
+/// Whether the file is a supported RAW format by extension
+pub fn is_raw(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| RAW_EXTENSIONS.iter().any(|raw| e.eq_ignore_ascii_case(raw)))
+        .unwrap_or(false)
+}
+
+/// The largest embedded preview of a RAW file
+#[derive(Debug, PartialEq)]
+pub struct RawPreview {
+    pub jpeg: Vec<u8>,
+    /// EXIF orientation (1-8) from IFD0
+    pub orientation: u16,
+}
+
+/// Whether a JPEG stream uses a DCT process `image` can decode into a
+/// photo, as opposed to the lossless JPEG used for raw sensor data
+fn is_baseline_jpeg(jpeg: &[u8]) -> bool {
+    if !jpeg.starts_with(&[0xFF, 0xD8]) {
+        return false;
+    }
+    let mut pos = 2;
+    while pos + 4 <= jpeg.len() {
+        if jpeg[pos] != 0xFF {
+            return false;
+        }
+        match jpeg[pos + 1] {
+            // Fill bytes
+            0xFF => pos += 1,
+            0xC0..=0xC2 => return true,
+            0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF | 0xDA | 0xD9 => return false,
+            _ => pos += 2 + u16::from_be_bytes([jpeg[pos + 2], jpeg[pos + 3]]) as usize,
+        }
+    }
+    false
+}
+
+/// Find the largest decodable JPEG preview in a TIFF-based RAW file
+pub fn find_preview(data: &[u8]) -> Option<RawPreview> {
+    let tiff = Tiff::new(data)?;
+    // 42 for TIFF/DNG/CR2/NEF/ARW; Olympus and Panasonic use their own magic
+    if !matches!(tiff.u16(2)?, 42 | 0x4F52 | 0x5352 | 0x55) {
+        return None;
+    }
+
+    let mut orientation = 1;
+    let mut best: Option<&[u8]> = None;
+    let mut pending = vec![tiff.first_ifd()?];
+    let mut visited = Vec::new();
+
+    while let Some(offset) = pending.pop() {
+        if offset == 0 || visited.contains(&offset) || visited.len() >= MAX_IFDS {
+            continue;
+        }
+        visited.push(offset);
+        let Some((tags, next)) = tiff.read_ifd(offset as usize) else {
+            continue;
+        };
+        let tag = |id: u16| tags.get(&id).and_then(|v| v.first().copied());
+
+        if visited.len() == 1 {
+            orientation = tag(TAG_ORIENTATION).filter(|o| (1..=8).contains(o)).unwrap_or(1) as u16;
+        }
+        pending.push(next);
+        if let Some(sub_ifds) = tags.get(&TAG_SUB_IFDS) {
+            pending.extend(sub_ifds);
+        }
+
+        let mut candidates = Vec::new();
+        if let (Some(start), Some(len)) = (tag(TAG_JPEG_OFFSET), tag(TAG_JPEG_LENGTH)) {
+            candidates.push((start, len));
+        }
+        let is_jpeg = matches!(tag(TAG_COMPRESSION), Some(6 | 7));
+        let is_sensor_data = tag(TAG_PHOTOMETRIC).is_some_and(|p| RAW_PHOTOMETRIC.contains(&p));
+        if is_jpeg
+            && !is_sensor_data
+            && let (Some([start]), Some([len])) = (
+                tags.get(&TAG_STRIP_OFFSETS).map(Vec::as_slice),
+                tags.get(&TAG_STRIP_BYTE_COUNTS).map(Vec::as_slice),
+            )
+        {
+            candidates.push((*start, *len));
+        }
+
+        for (start, len) in candidates {
+            let Some(jpeg) = data.get(start as usize..(start as usize).saturating_add(len as usize)) else {
+                continue;
+            };
+            if best.is_none_or(|b| jpeg.len() > b.len()) && is_baseline_jpeg(jpeg) {
+                best = Some(jpeg);
+            }
+        }
+    }
+
+    best.map(|jpeg| RawPreview {
+        jpeg: jpeg.to_vec(),
+        orientation,
+    })
+}
+
+/// Read the embedded preview of a RAW file
+pub fn read_preview(path: &Path) -> Result<RawPreview> {
+    let data = std::fs::read(path)?;
+    find_preview(&data).with_context(|| format!("No embedded JPEG preview in {:?}", path))
+}
+
+/// Rotate/flip an image according to an EXIF orientation value
+fn apply_orientation(img: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+/// Decode the embedded preview, upright
+pub fn decode_preview(path: &Path) -> Result<DynamicImage> {
+    let preview = read_preview(path)?;
+    let img = image::load_from_memory_with_format(&preview.jpeg, image::ImageFormat::Jpeg)
+        .with_context(|| format!("Failed to decode preview of {:?}", path))?;
+    Ok(apply_orientation(img, preview.orientation))
+}
+
+/// The embedded preview as an upright JPEG
+///
+/// Returned as stored when no rotation is needed; otherwise re-encoded at
+/// `quality`, since the preview carries no orientation tag of its own.
+pub fn preview_jpeg(path: &Path, quality: u8) -> Result<Vec<u8>> {
+    let preview = read_preview(path)?;
+    if preview.orientation == 1 {
+        return Ok(preview.jpeg);
+    }
+
+    let img = image::load_from_memory_with_format(&preview.jpeg, image::ImageFormat::Jpeg)?;
+    let rgb = apply_orientation(img, preview.orientation).to_rgb8();
+    let mut bytes = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, quality).encode_image(&rgb)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jpeg(width: u32, height: u32) -> Vec<u8> {
+        let img = DynamicImage::ImageRgb8(image::ImageBuffer::from_pixel(width, height, image::Rgb([90, 60, 30])));
+        let mut bytes = Vec::new();
+        img.write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageOutputFormat::Jpeg(80))
+            .unwrap();
+        bytes
+    }
+
+    fn entry(out: &mut Vec<u8>, tag: u16, kind: u16, count: u32, value: u32) {
+        out.extend_from_slice(&tag.to_le_bytes());
+        out.extend_from_slice(&kind.to_le_bytes());
+        out.extend_from_slice(&count.to_le_bytes());
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// IFD0 with a small JPEGInterchangeFormat thumbnail and orientation,
+    /// pointing to a SubIFD holding a full-size strip preview (DNG layout)
+    fn dng(small: &[u8], large: &[u8]) -> Vec<u8> {
+        let ifd0 = 8u32;
+        let sub_ifd = ifd0 + 2 + 4 * 12 + 4;
+        let small_at = sub_ifd + 2 + 4 * 12 + 4;
+        let large_at = small_at + small.len() as u32;
+
+        let mut out = b"II*\0".to_vec();
+        out.extend_from_slice(&ifd0.to_le_bytes());
+        out.extend_from_slice(&4u16.to_le_bytes());
+        entry(&mut out, TAG_ORIENTATION, 3, 1, 6);
+        entry(&mut out, TAG_SUB_IFDS, 4, 1, sub_ifd);
+        entry(&mut out, TAG_JPEG_OFFSET, 4, 1, small_at);
+        entry(&mut out, TAG_JPEG_LENGTH, 4, 1, small.len() as u32);
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&4u16.to_le_bytes());
+        entry(&mut out, TAG_COMPRESSION, 3, 1, 7);
+        entry(&mut out, TAG_PHOTOMETRIC, 3, 1, 6);
+        entry(&mut out, TAG_STRIP_OFFSETS, 4, 1, large_at);
+        entry(&mut out, TAG_STRIP_BYTE_COUNTS, 4, 1, large.len() as u32);
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(small);
+        out.extend_from_slice(large);
+        out
+    }
+
+    #[test]
+    fn test_find_largest_preview() {
+        let small = jpeg(16, 12);
+        let large = jpeg(160, 120);
+        let preview = find_preview(&dng(&small, &large)).unwrap();
+        assert_eq!(preview.jpeg, large);
+        assert_eq!(preview.orientation, 6);
+
+        assert!(find_preview(b"II*\0\xff\xff\xff\xff").is_none());
+        assert!(find_preview(&small).is_none());
+        assert!(is_raw(Path::new("IMG_0001.DNG")));
+        assert!(!is_raw(Path::new("IMG_0001.JPG")));
+    }
+
+    #[test]
+    fn test_preview_is_upright() {
+        let path = std::env::temp_dir().join(format!("skynas_raw_{}.dng", uuid::Uuid::new_v4()));
+        std::fs::write(&path, dng(&jpeg(16, 12), &jpeg(160, 120))).unwrap();
+
+        // Orientation 6: rotate 90° clockwise
+        let img = decode_preview(&path).unwrap();
+        assert_eq!((img.width(), img.height()), (120, 160));
+        let upright = image::load_from_memory(&preview_jpeg(&path, 85).unwrap()).unwrap();
+        assert_eq!((upright.width(), upright.height()), (120, 160));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+//! Minimal TIFF structure reader shared by RAW preview extraction and EXIF
+//! editing (an EXIF block is a TIFF stream)
+
+use std::collections::HashMap;
+
+/// Guards against malformed or cyclic IFD structures
+pub const MAX_ENTRIES: usize = 1024;
+
+pub struct Tiff<'a> {
+    pub data: &'a [u8],
+    pub little_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    /// Read the byte order mark; the magic number is left to the caller
+    pub fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(..2)? {
+            b"II" => true,
+            b"MM" => false,
+            _ => return None,
+        };
+        Some(Self { data, little_endian })
+    }
+
+    pub fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    }
+
+    pub fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    }
+
+    pub fn encode_u16(&self, value: u16) -> [u8; 2] {
+        if self.little_endian { value.to_le_bytes() } else { value.to_be_bytes() }
+    }
+
+    pub fn encode_u32(&self, value: u32) -> [u8; 4] {
+        if self.little_endian { value.to_le_bytes() } else { value.to_be_bytes() }
+    }
+
+    /// Offset of the first IFD
+    pub fn first_ifd(&self) -> Option<u32> {
+        self.u32(4)
+    }
+
+    /// Offset of each 12-byte entry of the IFD at `offset`, and the offset of
+    /// the next IFD
+    pub fn entries(&self, offset: usize) -> Option<(Vec<usize>, u32)> {
+        let count = self.u16(offset)? as usize;
+        if count > MAX_ENTRIES || offset + 2 + count * 12 > self.data.len() {
+            return None;
+        }
+        let entries = (0..count).map(|i| offset + 2 + i * 12).collect();
+        let next = self.u32(offset + 2 + count * 12).unwrap_or(0);
+        Some((entries, next))
+    }
+
+    /// Size in bytes of the value of the entry at `entry`, and where it lives
+    /// (values that fit in four bytes are stored inline)
+    pub fn value_location(&self, entry: usize) -> Option<(usize, usize)> {
+        let unit = match self.u16(entry + 2)? {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 | 11 | 13 => 4,
+            5 | 10 | 12 => 8,
+            _ => return None,
+        };
+        let size = unit.checked_mul(self.u32(entry + 4)? as usize)?;
+        let start = if size <= 4 { entry + 8 } else { self.u32(entry + 8)? as usize };
+        Some((start, size))
+    }
+
+    /// SHORT/LONG/IFD values of the entry at `entry`
+    pub fn values(&self, entry: usize) -> Vec<u32> {
+        let (Some(kind), Some(count)) = (self.u16(entry + 2), self.u32(entry + 4)) else {
+            return Vec::new();
+        };
+        let count = count as usize;
+        if !matches!(kind, 3 | 4 | 13) || count > MAX_ENTRIES {
+            return Vec::new();
+        }
+        let Some((start, _)) = self.value_location(entry) else {
+            return Vec::new();
+        };
+        (0..count)
+            .map_while(|i| match kind {
+                3 => self.u16(start + i * 2).map(u32::from),
+                _ => self.u32(start + i * 4),
+            })
+            .collect()
+    }
+
+    /// Tag values of one IFD and the offset of the next one
+    pub fn read_ifd(&self, offset: usize) -> Option<(HashMap<u16, Vec<u32>>, u32)> {
+        let (entries, next) = self.entries(offset)?;
+        let mut tags = HashMap::new();
+        for entry in entries {
+            tags.insert(self.u16(entry)?, self.values(entry));
+        }
+        Some((tags, next))
+    }
+}
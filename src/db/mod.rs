@@ -1,12 +1,160 @@
 use crate::models::*;
+use crate::thumbnail::GeneratedThumbnail;
 use anyhow::Result;
-use rusqlite::{Connection, params};
+use rusqlite::{Connection, Row, params};
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 
 pub struct Database {
     pub conn: Connection,
 }
 
+/// New location of a photo whose files were moved to another album directory
+pub struct PhotoMove {
+    pub id: i64,
+    pub album: String,
+    pub filename: String,
+    pub local_path: String,
+}
+
+/// Column list shared by every query that builds a [`Photo`] via [`photo_from_row`]
+const PHOTO_COLUMNS: &str = "id, filename, album, file_hash, size_bytes, created_at, uploaded_at, local_path, has_jpeg_variant, thumbnail_path, width, height, title, caption, rating, phash, duration_ms, video_codec, frame_rate, latitude, longitude, blurhash, thumbnail_spec, is_raw, pending_review, favorite";
+
+fn photo_from_row(row: &Row) -> rusqlite::Result<Photo> {
+    Ok(Photo {
+        id: row.get(0)?,
+        filename: row.get(1)?,
+        album: row.get(2)?,
+        file_hash: row.get(3)?,
+        size_bytes: row.get(4)?,
+        created_at: row.get(5)?,
+        uploaded_at: row.get(6)?,
+        local_path: row.get(7)?,
+        has_jpeg_variant: row.get(8)?,
+        thumbnail_path: row.get(9).ok(),
+        width: row.get(10).ok(),
+        height: row.get(11).ok(),
+        title: row.get(12).ok(),
+        caption: row.get(13).ok(),
+        rating: row.get(14).ok(),
+        perceptual_hash: row.get(15).ok(),
+        duration_ms: row.get(16).ok(),
+        video_codec: row.get(17).ok(),
+        frame_rate: row.get(18).ok(),
+        latitude: row.get(19).ok(),
+        longitude: row.get(20).ok(),
+        blurhash: row.get(21).ok(),
+        thumbnail_spec: row.get(22).ok(),
+        is_raw: row.get(23).unwrap_or(false),
+        pending_review: row.get(24).unwrap_or(false),
+        favorite: row.get(25).unwrap_or(false),
+    })
+}
+
+/// Delete a photo row and everything that references it
+fn delete_photo_rows(conn: &Connection, id: i64) -> rusqlite::Result<bool> {
+    conn.execute("DELETE FROM photo_albums WHERE photo_id = ?1", [id])?;
+    conn.execute("DELETE FROM photo_renditions WHERE photo_id = ?1", [id])?;
+    conn.execute("DELETE FROM photo_tags WHERE photo_id = ?1", [id])?;
+    conn.execute("UPDATE albums SET cover_photo_id = NULL WHERE cover_photo_id = ?1", [id])?;
+    Ok(conn.execute("DELETE FROM photos WHERE id = ?1", [id])? > 0)
+}
+
+/// Register an album path such as `Family/2025/Summer`, creating missing
+/// ancestors and linking each level to its parent; returns the album id
+fn ensure_album_row(conn: &Connection, name: &str) -> rusqlite::Result<i64> {
+    let mut parent_id: Option<i64> = None;
+    let mut path = String::new();
+    for segment in name.split('/') {
+        if !path.is_empty() {
+            path.push('/');
+        }
+        path.push_str(segment);
+        let id = conn.query_row(
+            "INSERT INTO albums (name, parent_id, created_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(name) DO UPDATE SET parent_id = excluded.parent_id
+             RETURNING id",
+            params![path, parent_id, chrono::Utc::now()],
+            |row| row.get(0),
+        )?;
+        parent_id = Some(id);
+    }
+    Ok(parent_id.unwrap_or_default())
+}
+
+/// Point photo rows at their new album and files
+///
+/// A membership of the target album recorded in `photo_albums` becomes
+/// redundant and is removed, as is a cover the photo no longer belongs to.
+fn move_photo_rows(conn: &Connection, moves: &[PhotoMove]) -> rusqlite::Result<()> {
+    for m in moves {
+        ensure_album_row(conn, &m.album)?;
+        conn.execute(
+            "UPDATE photos SET album = ?2, filename = ?3, local_path = ?4 WHERE id = ?1",
+            params![m.id, m.album, m.filename, m.local_path],
+        )?;
+        conn.execute(
+            "DELETE FROM photo_albums WHERE photo_id = ?1 AND album = ?2",
+            params![m.id, m.album],
+        )?;
+        conn.execute(
+            "UPDATE albums SET cover_photo_id = NULL
+             WHERE cover_photo_id = ?1 AND name <> ?2
+               AND name NOT IN (SELECT album FROM photo_albums WHERE photo_id = ?1)",
+            params![m.id, m.album],
+        )?;
+    }
+    Ok(())
+}
+
+const ALBUM_COLUMNS: &str = "id, name, description, cover_photo_id, sort_order, created_at, parent_id";
+
+fn album_from_row(row: &Row) -> rusqlite::Result<Album> {
+    Ok(Album {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        cover_photo_id: row.get(3)?,
+        sort_order: row.get(4)?,
+        created_at: row.get(5)?,
+        parent_id: row.get(6)?,
+    })
+}
+
+/// SQL condition on `column` matching album `?1`, or with `?2` set also its sub-albums
+fn album_match(column: &str) -> String {
+    format!(
+        "({0} = ?1 OR (?2 AND substr({0}, 1, length(?1) + 1) = ?1 || '/'))",
+        column
+    )
+}
+
+fn guest_link_from_row(row: &Row) -> rusqlite::Result<GuestLink> {
+    Ok(GuestLink {
+        id: row.get(0)?,
+        album: row.get(1)?,
+        max_files: row.get(2)?,
+        max_bytes: row.get(3)?,
+        files_used: row.get(4)?,
+        bytes_used: row.get(5)?,
+        require_review: row.get::<_, i32>(6)? != 0,
+        expires_at: row.get(7)?,
+        created_at: row.get(8)?,
+    })
+}
+
+fn share_from_row(row: &Row) -> rusqlite::Result<Share> {
+    Ok(Share {
+        id: row.get(0)?,
+        photo_id: row.get(1)?,
+        album: row.get(2)?,
+        password_hash: row.get(3)?,
+        disable_download: row.get::<_, i32>(4)? != 0,
+        expires_at: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
 impl Database {
     pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
         let conn = Connection::open(db_path)?;
@@ -83,6 +231,55 @@ impl Database {
             "ALTER TABLE photos ADD COLUMN height INTEGER",
             [],
         );
+        let _ = self.conn.execute(
+            "ALTER TABLE photos ADD COLUMN title TEXT",
+            [],
+        );
+        let _ = self.conn.execute(
+            "ALTER TABLE photos ADD COLUMN caption TEXT",
+            [],
+        );
+        let _ = self.conn.execute(
+            "ALTER TABLE photos ADD COLUMN rating INTEGER",
+            [],
+        );
+        let _ = self.conn.execute(
+            "ALTER TABLE photos ADD COLUMN phash INTEGER",
+            [],
+        );
+        for column in [
+            "duration_ms INTEGER",
+            "video_codec TEXT",
+            "frame_rate REAL",
+            "latitude REAL",
+            "longitude REAL",
+            "blurhash TEXT",
+            "thumbnail_spec TEXT",
+        ] {
+            let _ = self.conn.execute(&format!("ALTER TABLE photos ADD COLUMN {}", column), []);
+        }
+        // 新增 is_raw 列时按扩展名标记已有的 RAW 文件
+        if self.conn.execute("ALTER TABLE photos ADD COLUMN is_raw BOOLEAN DEFAULT FALSE", []).is_ok() {
+            let mut stmt = self.conn.prepare("SELECT id, local_path FROM photos")?;
+            let raw_ids: Vec<i64> = stmt
+                .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+                .filter_map(|r| r.ok())
+                .filter(|(_, path)| crate::converter::raw::is_raw(Path::new(path)))
+                .map(|(id, _)| id)
+                .collect();
+            for id in raw_ids {
+                self.conn.execute("UPDATE photos SET is_raw = 1 WHERE id = ?1", [id])?;
+            }
+        }
+
+        let _ = self.conn.execute(
+            "ALTER TABLE photos ADD COLUMN pending_review BOOLEAN NOT NULL DEFAULT FALSE",
+            [],
+        );
+        let _ = self.conn.execute(
+            "ALTER TABLE photos ADD COLUMN favorite BOOLEAN NOT NULL DEFAULT FALSE",
+            [],
+        );
 
         // 新增 upload_tasks 表
         self.conn.execute_batch(
@@ -101,6 +298,92 @@ impl Database {
 
             CREATE INDEX IF NOT EXISTS idx_upload_tasks_status ON upload_tasks(status);
 
+            -- 照片在主相册之外所属的其他相册（合并重复文件时记录）
+            CREATE TABLE IF NOT EXISTS photo_albums (
+                photo_id INTEGER NOT NULL REFERENCES photos(id),
+                album TEXT NOT NULL,
+                PRIMARY KEY (photo_id, album)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_photo_albums_album ON photo_albums(album);
+
+            -- 照片标签
+            CREATE TABLE IF NOT EXISTS photo_tags (
+                photo_id INTEGER NOT NULL REFERENCES photos(id),
+                tag TEXT NOT NULL,
+                PRIMARY KEY (photo_id, tag)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_photo_tags_tag ON photo_tags(tag);
+
+            -- 每张照片的缩略图版本（尺寸 + 格式）
+            CREATE TABLE IF NOT EXISTS photo_renditions (
+                photo_id INTEGER NOT NULL REFERENCES photos(id),
+                max_size INTEGER NOT NULL,
+                format TEXT NOT NULL,
+                path TEXT NOT NULL,
+                width INTEGER NOT NULL,
+                height INTEGER NOT NULL,
+                size_bytes INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (photo_id, max_size, format)
+            );
+
+            -- 可恢复的后台任务（如缩略图重建），按名称只保留最近一次
+            CREATE TABLE IF NOT EXISTS background_jobs (
+                name TEXT PRIMARY KEY,
+                status TEXT NOT NULL,
+                last_id INTEGER NOT NULL DEFAULT 0,
+                total INTEGER NOT NULL DEFAULT 0,
+                processed INTEGER NOT NULL DEFAULT 0,
+                succeeded INTEGER NOT NULL DEFAULT 0,
+                failed INTEGER NOT NULL DEFAULT 0,
+                force INTEGER NOT NULL DEFAULT 0,
+                started_at TIMESTAMP NOT NULL,
+                updated_at TIMESTAMP NOT NULL
+            );
+
+            -- 公开分享链接；撤销即删除，已签发的 token 随之失效
+            CREATE TABLE IF NOT EXISTS shares (
+                id TEXT PRIMARY KEY,
+                photo_id INTEGER REFERENCES photos(id),
+                album TEXT,
+                password_hash TEXT,
+                disable_download INTEGER NOT NULL DEFAULT 0,
+                expires_at TIMESTAMP,
+                created_at TIMESTAMP NOT NULL
+            );
+
+            -- 访客上传链接及其已用额度
+            CREATE TABLE IF NOT EXISTS guest_links (
+                id TEXT PRIMARY KEY,
+                album TEXT NOT NULL,
+                max_files INTEGER,
+                max_bytes INTEGER,
+                files_used INTEGER NOT NULL DEFAULT 0,
+                bytes_used INTEGER NOT NULL DEFAULT 0,
+                require_review INTEGER NOT NULL DEFAULT 0,
+                expires_at TIMESTAMP,
+                created_at TIMESTAMP NOT NULL
+            );
+
+            -- 通过访客链接发起、尚未完成的上传
+            CREATE TABLE IF NOT EXISTS guest_uploads (
+                upload_id TEXT PRIMARY KEY,
+                link_id TEXT NOT NULL,
+                total_size INTEGER NOT NULL
+            );
+
+            -- 相册；照片仍通过 photos.album 中的相册名关联
+            CREATE TABLE IF NOT EXISTS albums (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                description TEXT,
+                cover_photo_id INTEGER REFERENCES photos(id),
+                sort_order INTEGER NOT NULL DEFAULT 0,
+                created_at TIMESTAMP NOT NULL,
+                parent_id INTEGER REFERENCES albums(id)
+            );
+
             CREATE TABLE IF NOT EXISTS admin_config (
                 id INTEGER PRIMARY KEY CHECK (id = 1),
                 jwt_secret TEXT NOT NULL,
@@ -111,14 +394,52 @@ impl Database {
             "#
         )?;
 
+        let _ = self.conn.execute(
+            "ALTER TABLE guest_links ADD COLUMN require_review INTEGER NOT NULL DEFAULT 0",
+            [],
+        );
+
+        let _ = self.conn.execute(
+            "ALTER TABLE albums ADD COLUMN parent_id INTEGER REFERENCES albums(id)",
+            [],
+        );
+        let _ = self.conn.execute("ALTER TABLE admin_config ADD COLUMN link_secret TEXT", []);
+        let _ = self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_albums_parent ON albums(parent_id)",
+            [],
+        );
+
+        // 补齐只存在于照片记录中的相册（包括 albums 表之前的数据）
+        self.conn.execute(
+            "INSERT OR IGNORE INTO albums (name, created_at)
+             SELECT album, COALESCE(MIN(uploaded_at), CURRENT_TIMESTAMP) FROM (
+                 SELECT album, uploaded_at FROM photos
+                 UNION ALL
+                 SELECT pa.album, p.uploaded_at FROM photo_albums pa JOIN photos p ON p.id = pa.photo_id
+             ) GROUP BY album",
+            [],
+        )?;
+
+        // 为嵌套相册补齐上级相册并关联 parent_id
+        let names: Vec<String> = {
+            let mut stmt = self.conn.prepare("SELECT name FROM albums WHERE name LIKE '%/%'")?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        for name in names {
+            ensure_album_row(&self.conn, &name)?;
+        }
+
         Ok(())
     }
 
     // Photo operations
     pub fn insert_photo(&self, photo: &Photo) -> Result<i64> {
+        ensure_album_row(&self.conn, &photo.album)?;
         let id: i64 = self.conn.query_row(
-            "INSERT INTO photos (filename, album, file_hash, size_bytes, created_at, local_path, has_jpeg_variant, thumbnail_path, width, height)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            "INSERT INTO photos (filename, album, file_hash, size_bytes, created_at, local_path, has_jpeg_variant, thumbnail_path, width, height, title, caption, rating,
+                                 duration_ms, video_codec, frame_rate, latitude, longitude, is_raw, pending_review)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)
              ON CONFLICT(file_hash) DO UPDATE SET
                  uploaded_at = excluded.uploaded_at
              RETURNING id",
@@ -133,6 +454,16 @@ impl Database {
                 photo.thumbnail_path,
                 photo.width,
                 photo.height,
+                photo.title,
+                photo.caption,
+                photo.rating,
+                photo.duration_ms,
+                photo.video_codec,
+                photo.frame_rate,
+                photo.latitude,
+                photo.longitude,
+                photo.is_raw,
+                photo.pending_review,
             ],
             |row| row.get(0),
         )?;
@@ -141,54 +472,28 @@ impl Database {
 
     #[allow(dead_code)]
     pub fn find_photo_by_hash(&self, file_hash: &str) -> Result<Option<Photo>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, filename, album, file_hash, size_bytes, created_at, uploaded_at, local_path, has_jpeg_variant, thumbnail_path, width, height
-             FROM photos WHERE file_hash = ?1"
-        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM photos WHERE file_hash = ?1",
+            PHOTO_COLUMNS
+        ))?;
         let mut rows = stmt.query(params![file_hash])?;
 
         if let Some(row) = rows.next()? {
-            Ok(Some(Photo {
-                id: row.get(0)?,
-                filename: row.get(1)?,
-                album: row.get(2)?,
-                file_hash: row.get(3)?,
-                size_bytes: row.get(4)?,
-                created_at: row.get(5)?,
-                uploaded_at: row.get(6)?,
-                local_path: row.get(7)?,
-                has_jpeg_variant: row.get(8)?,
-                thumbnail_path: row.get(9).ok(),
-                width: row.get(10).ok(),
-                height: row.get(11).ok(),
-            }))
+            Ok(Some(photo_from_row(row)?))
         } else {
             Ok(None)
         }
     }
 
-    #[allow(dead_code)]
     pub fn list_photos_by_album(&self, album: &str) -> Result<Vec<Photo>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, filename, album, file_hash, size_bytes, created_at, uploaded_at, local_path, has_jpeg_variant, thumbnail_path, width, height
-             FROM photos WHERE album = ?1 ORDER BY uploaded_at DESC"
-        )?;
-        let rows = stmt.query_map(params![album], |row| {
-            Ok(Photo {
-                id: row.get(0)?,
-                filename: row.get(1)?,
-                album: row.get(2)?,
-                file_hash: row.get(3)?,
-                size_bytes: row.get(4)?,
-                created_at: row.get(5)?,
-                uploaded_at: row.get(6)?,
-                local_path: row.get(7)?,
-                has_jpeg_variant: row.get(8)?,
-                thumbnail_path: row.get(9).ok(),
-                width: row.get(10).ok(),
-                height: row.get(11).ok(),
-            })
-        })?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM photos
+             WHERE (album = ?1 OR id IN (SELECT photo_id FROM photo_albums WHERE album = ?1))
+               AND NOT pending_review
+             ORDER BY uploaded_at DESC",
+            PHOTO_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![album], photo_from_row)?;
 
         let mut photos = Vec::new();
         for row in rows {
@@ -197,35 +502,47 @@ impl Database {
         Ok(photos)
     }
 
+    /// Fetch photos by id, in the order given; unknown ids are skipped, and
+    /// so are photos awaiting review unless `include_pending` is set
+    pub fn list_photos_by_ids(&self, ids: &[i64], include_pending: bool) -> Result<Vec<Photo>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM photos
+             WHERE id IN (SELECT value FROM json_each(?1)) AND (?2 OR NOT pending_review)",
+            PHOTO_COLUMNS
+        ))?;
+        let ids_json = serde_json::to_string(ids)?;
+        let mut by_id = HashMap::new();
+        for row in stmt.query_map(params![ids_json, include_pending], photo_from_row)? {
+            let photo = row?;
+            by_id.insert(photo.id, photo);
+        }
+        Ok(ids.iter().filter_map(|id| by_id.remove(id)).collect())
+    }
+
     /// List photos with pagination and optional album filter
     pub fn list_photos(
         &self,
         album: Option<&str>,
+        include_sub_albums: bool,
+        order: PhotoOrder,
         limit: i32,
         offset: i64,
     ) -> Result<(Vec<Photo>, i64)> {
+        let order_by = order.order_by();
+        let in_album = format!(
+            "({} OR id IN (SELECT photo_id FROM photo_albums WHERE {}))",
+            album_match("album"),
+            album_match("album")
+        );
         let photos = if let Some(album) = album {
-            let mut stmt = self.conn.prepare(
-                "SELECT id, filename, album, file_hash, size_bytes, created_at, uploaded_at, local_path, has_jpeg_variant, thumbnail_path, width, height
-                 FROM photos WHERE album = ?1 ORDER BY uploaded_at DESC LIMIT ?2 OFFSET ?3"
-            )?;
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT {} FROM photos
+                 WHERE {} AND NOT pending_review
+                 ORDER BY {} LIMIT ?3 OFFSET ?4",
+                PHOTO_COLUMNS, in_album, order_by
+            ))?;
 
-            let rows = stmt.query_map(params![album, limit, offset], |row| {
-                Ok(Photo {
-                    id: row.get(0)?,
-                    filename: row.get(1)?,
-                    album: row.get(2)?,
-                    file_hash: row.get(3)?,
-                    size_bytes: row.get(4)?,
-                    created_at: row.get(5)?,
-                    uploaded_at: row.get(6)?,
-                    local_path: row.get(7)?,
-                    has_jpeg_variant: row.get(8)?,
-                    thumbnail_path: row.get(9).ok(),
-                    width: row.get(10).ok(),
-                    height: row.get(11).ok(),
-                })
-            })?;
+            let rows = stmt.query_map(params![album, include_sub_albums, limit, offset], photo_from_row)?;
 
             let mut items = Vec::new();
             for row in rows {
@@ -233,27 +550,12 @@ impl Database {
             }
             items
         } else {
-            let mut stmt = self.conn.prepare(
-                "SELECT id, filename, album, file_hash, size_bytes, created_at, uploaded_at, local_path, has_jpeg_variant, thumbnail_path, width, height
-                 FROM photos ORDER BY uploaded_at DESC LIMIT ?1 OFFSET ?2"
-            )?;
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT {} FROM photos WHERE NOT pending_review ORDER BY {} LIMIT ?1 OFFSET ?2",
+                PHOTO_COLUMNS, order_by
+            ))?;
 
-            let rows = stmt.query_map(params![limit, offset], |row| {
-                Ok(Photo {
-                    id: row.get(0)?,
-                    filename: row.get(1)?,
-                    album: row.get(2)?,
-                    file_hash: row.get(3)?,
-                    size_bytes: row.get(4)?,
-                    created_at: row.get(5)?,
-                    uploaded_at: row.get(6)?,
-                    local_path: row.get(7)?,
-                    has_jpeg_variant: row.get(8)?,
-                    thumbnail_path: row.get(9).ok(),
-                    width: row.get(10).ok(),
-                    height: row.get(11).ok(),
-                })
-            })?;
+            let rows = stmt.query_map(params![limit, offset], photo_from_row)?;
 
             let mut items = Vec::new();
             for row in rows {
@@ -265,13 +567,13 @@ impl Database {
         // Get total count
         let total: i64 = if let Some(album) = album {
             self.conn.query_row(
-                "SELECT COUNT(*) FROM photos WHERE album = ?1",
-                [album],
+                &format!("SELECT COUNT(*) FROM photos WHERE {} AND NOT pending_review", in_album),
+                params![album, include_sub_albums],
                 |row| row.get(0),
             )?
         } else {
             self.conn.query_row(
-                "SELECT COUNT(*) FROM photos",
+                "SELECT COUNT(*) FROM photos WHERE NOT pending_review",
                 [],
                 |row| row.get(0),
             )?
@@ -280,54 +582,409 @@ impl Database {
         Ok((photos, total))
     }
 
-    /// Get a single photo by ID
-    pub fn get_photo(&self, id: i64) -> Result<Option<Photo>> {
+    /// Photos waiting for moderation, oldest first
+    pub fn list_pending_photos(&self) -> Result<Vec<Photo>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM photos WHERE pending_review ORDER BY uploaded_at",
+            PHOTO_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], photo_from_row)?;
+
+        let mut photos = Vec::new();
+        for row in rows {
+            photos.push(row?);
+        }
+        Ok(photos)
+    }
+
+    pub fn count_pending_photos(&self) -> Result<i64> {
+        let count = self.conn.query_row("SELECT COUNT(*) FROM photos WHERE pending_review", [], |row| row.get(0))?;
+        Ok(count)
+    }
+
+    /// Publish pending photos; returns the ids that were actually pending
+    pub fn approve_photos(&self, ids: &[i64]) -> Result<Vec<i64>> {
+        let ids_json = serde_json::to_string(ids)?;
         let mut stmt = self.conn.prepare(
-            "SELECT id, filename, album, file_hash, size_bytes, created_at, uploaded_at, local_path, has_jpeg_variant, thumbnail_path, width, height
-             FROM photos WHERE id = ?1"
+            "UPDATE photos SET pending_review = 0
+             WHERE pending_review AND id IN (SELECT value FROM json_each(?1))
+             RETURNING id",
         )?;
+        let rows = stmt.query_map(params![ids_json], |row| row.get(0))?;
+
+        let mut approved = Vec::new();
+        for row in rows {
+            approved.push(row?);
+        }
+        Ok(approved)
+    }
+
+    /// Delete a photo together with its album memberships and renditions
+    pub fn delete_photo(&self, id: i64) -> Result<bool> {
+        let tx = self.conn.unchecked_transaction()?;
+        let deleted = delete_photo_rows(&tx, id)?;
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    /// Delete several photos in one transaction; returns the ids that existed
+    pub fn delete_photos(&self, ids: &[i64]) -> Result<Vec<i64>> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut deleted = Vec::new();
+        for &id in ids {
+            if delete_photo_rows(&tx, id)? {
+                deleted.push(id);
+            }
+        }
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    /// Move photos to other albums in one transaction
+    pub fn move_photos(&self, moves: &[PhotoMove]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        move_photo_rows(&tx, moves)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Add or remove a tag on several photos; returns the ids that exist
+    pub fn set_photo_tag(&self, ids: &[i64], tag: &str, present: bool) -> Result<Vec<i64>> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut updated = Vec::new();
+        for &id in ids {
+            let exists: bool = tx.query_row("SELECT EXISTS(SELECT 1 FROM photos WHERE id = ?1)", [id], |row| row.get(0))?;
+            if !exists {
+                continue;
+            }
+            if present {
+                tx.execute("INSERT OR IGNORE INTO photo_tags (photo_id, tag) VALUES (?1, ?2)", params![id, tag])?;
+            } else {
+                tx.execute("DELETE FROM photo_tags WHERE photo_id = ?1 AND tag = ?2", params![id, tag])?;
+            }
+            updated.push(id);
+        }
+        tx.commit()?;
+        Ok(updated)
+    }
+
+    /// Set the favorite flag on several photos; returns the ids that exist
+    pub fn set_favorite(&self, ids: &[i64], favorite: bool) -> Result<Vec<i64>> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut updated = Vec::new();
+        for &id in ids {
+            if tx.execute("UPDATE photos SET favorite = ?2 WHERE id = ?1", params![id, favorite])? > 0 {
+                updated.push(id);
+            }
+        }
+        tx.commit()?;
+        Ok(updated)
+    }
+
+    pub fn list_photo_tags(&self, photo_id: i64) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("SELECT tag FROM photo_tags WHERE photo_id = ?1 ORDER BY tag")?;
+        let rows = stmt.query_map([photo_id], |row| row.get(0))?;
+
+        let mut tags = Vec::new();
+        for row in rows {
+            tags.push(row?);
+        }
+        Ok(tags)
+    }
+
+    /// All tags with the number of photos carrying them
+    pub fn list_tags(&self) -> Result<Vec<(String, i64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT t.tag, COUNT(*) as count FROM photo_tags t JOIN photos p ON p.id = t.photo_id
+             WHERE NOT p.pending_review
+             GROUP BY t.tag ORDER BY count DESC, t.tag"
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        let mut tags = Vec::new();
+        for row in rows {
+            tags.push(row?);
+        }
+        Ok(tags)
+    }
+
+    /// Get a single photo by ID
+    pub fn get_photo(&self, id: i64) -> Result<Option<Photo>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM photos WHERE id = ?1",
+            PHOTO_COLUMNS
+        ))?;
 
         let mut rows = stmt.query(params![id])?;
 
         if let Some(row) = rows.next()? {
-            Ok(Some(Photo {
-                id: row.get(0)?,
-                filename: row.get(1)?,
-                album: row.get(2)?,
-                file_hash: row.get(3)?,
-                size_bytes: row.get(4)?,
-                created_at: row.get(5)?,
-                uploaded_at: row.get(6)?,
-                local_path: row.get(7)?,
-                has_jpeg_variant: row.get(8)?,
-                thumbnail_path: row.get(9).ok(),
-                width: row.get(10).ok(),
-                height: row.get(11).ok(),
-            }))
+            Ok(Some(photo_from_row(row)?))
         } else {
             Ok(None)
         }
     }
 
-    /// Get thumbnail path for a photo
-    pub fn get_thumbnail_path(&self, id: i64) -> Result<Option<String>> {
-        let path: Option<String> = self.conn.query_row(
-            "SELECT thumbnail_path FROM photos WHERE id = ?1",
-            [id],
+    /// Update the user-editable descriptive metadata of a photo
+    ///
+    /// Returns `false` when no photo with the given id exists.
+    pub fn update_photo_metadata(
+        &self,
+        id: i64,
+        title: Option<&str>,
+        caption: Option<&str>,
+        rating: Option<i32>,
+    ) -> Result<bool> {
+        let rows_affected = self.conn.execute(
+            "UPDATE photos SET title = ?1, caption = ?2, rating = ?3 WHERE id = ?4",
+            params![title, caption, rating, id],
+        )?;
+        Ok(rows_affected > 0)
+    }
+
+    /// List every photo in the library
+    pub fn list_all_photos(&self) -> Result<Vec<Photo>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM photos ORDER BY id",
+            PHOTO_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], photo_from_row)?;
+
+        let mut photos = Vec::new();
+        for row in rows {
+            photos.push(row?);
+        }
+        Ok(photos)
+    }
+
+    /// Albums a photo belongs to besides its own `album`
+    #[allow(dead_code)]
+    pub fn list_album_memberships(&self, photo_id: i64) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT album FROM photo_albums WHERE photo_id = ?1 ORDER BY album"
+        )?;
+        let rows = stmt.query_map(params![photo_id], |row| row.get(0))?;
+
+        let mut albums = Vec::new();
+        for row in rows {
+            albums.push(row?);
+        }
+        Ok(albums)
+    }
+
+    /// Fold duplicate photo rows into `keep_id`
+    ///
+    /// The duplicates' albums (and their own memberships) become memberships
+    /// of the kept photo, their tags and favorite flag carry over, sync
+    /// history and album covers are re-pointed, and the duplicate rows are
+    /// removed, all in one transaction.
+    pub fn merge_photo_rows(&self, keep_id: i64, duplicate_ids: &[i64], albums: &[String]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+
+        let keep_album: String = tx.query_row(
+            "SELECT album FROM photos WHERE id = ?1",
+            [keep_id],
             |row| row.get(0),
-        ).ok().flatten();
+        )?;
 
-        Ok(path)
+        for album in albums {
+            if *album != keep_album {
+                tx.execute(
+                    "INSERT OR IGNORE INTO photo_albums (photo_id, album) VALUES (?1, ?2)",
+                    params![keep_id, album],
+                )?;
+            }
+        }
+
+        for id in duplicate_ids {
+            tx.execute(
+                "INSERT OR IGNORE INTO photo_albums (photo_id, album)
+                 SELECT ?1, album FROM photo_albums WHERE photo_id = ?2 AND album != ?3",
+                params![keep_id, id, keep_album],
+            )?;
+            tx.execute(
+                "INSERT OR IGNORE INTO photo_tags (photo_id, tag)
+                 SELECT ?1, tag FROM photo_tags WHERE photo_id = ?2",
+                params![keep_id, id],
+            )?;
+            tx.execute(
+                "UPDATE photos SET favorite = favorite OR COALESCE((SELECT favorite FROM photos WHERE id = ?2), FALSE)
+                 WHERE id = ?1",
+                params![keep_id, id],
+            )?;
+            tx.execute(
+                "UPDATE sync_history SET photo_id = ?1 WHERE photo_id = ?2",
+                params![keep_id, id],
+            )?;
+            tx.execute(
+                "UPDATE albums SET cover_photo_id = ?1 WHERE cover_photo_id = ?2",
+                params![keep_id, id],
+            )?;
+            delete_photo_rows(&tx, *id)?;
+        }
+
+        tx.commit()?;
+        Ok(())
     }
 
-    /// List all albums with photo counts
-    pub fn list_albums(&self) -> Result<Vec<(String, i64)>> {
+    /// Record whether a photo has a converted JPEG variant next to it
+    pub fn set_jpeg_variant(&self, id: i64, has_jpeg_variant: bool) -> Result<()> {
+        self.conn.execute(
+            "UPDATE photos SET has_jpeg_variant = ?1 WHERE id = ?2",
+            params![has_jpeg_variant, id],
+        )?;
+        Ok(())
+    }
+
+    /// Record the result of thumbnail generation for a photo, replacing any
+    /// renditions from a previous run
+    ///
+    /// `file_hash` fills in the content hash of rows that lack one (photos
+    /// from before hashing); it is skipped if another row already claims it.
+    /// Videos keep the resolution read from their container at ingest: the
+    /// poster frame may have been extracted at a reduced size.
+    pub fn update_thumbnail_info(&self, id: i64, thumb: &GeneratedThumbnail, file_hash: Option<&str>) -> Result<()> {
+        let video_dims = "width IS NOT NULL AND height IS NOT NULL AND (duration_ms IS NOT NULL OR video_codec IS NOT NULL)";
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            &format!("UPDATE photos SET thumbnail_path = ?1, phash = ?4, blurhash = ?5, thumbnail_spec = ?6,
+                 width = CASE WHEN {video_dims} THEN width ELSE ?2 END,
+                 height = CASE WHEN {video_dims} THEN height ELSE ?3 END,
+                 file_hash = COALESCE(file_hash, (SELECT ?8 WHERE NOT EXISTS (SELECT 1 FROM photos WHERE file_hash = ?8)))
+             WHERE id = ?7"),
+            params![
+                thumb.path.to_string_lossy(),
+                thumb.width,
+                thumb.height,
+                // SQLite integers are signed; the hash bits are stored as-is
+                thumb.perceptual_hash as i64,
+                thumb.blurhash,
+                thumb.spec,
+                id,
+                file_hash
+            ],
+        )?;
+
+        tx.execute("DELETE FROM photo_renditions WHERE photo_id = ?1", [id])?;
+        for r in &thumb.renditions {
+            tx.execute(
+                "INSERT INTO photo_renditions (photo_id, max_size, format, path, width, height, size_bytes)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![id, r.max_size, r.format, r.path, r.width, r.height, r.size_bytes],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Every thumbnail file path referenced by a photo or rendition
+    pub fn list_thumbnail_paths(&self) -> Result<HashSet<String>> {
         let mut stmt = self.conn.prepare(
-            "SELECT album, COUNT(*) as count FROM photos GROUP BY album ORDER BY count DESC"
+            "SELECT thumbnail_path FROM photos WHERE thumbnail_path IS NOT NULL
+             UNION
+             SELECT path FROM photo_renditions"
+        )?;
+
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+
+        let mut paths = HashSet::new();
+        for row in rows {
+            paths.insert(row?);
+        }
+        Ok(paths)
+    }
+
+    /// Photos with an id greater than `after_id`, in id order
+    pub fn list_photos_after(&self, after_id: i64, limit: i64) -> Result<Vec<Photo>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM photos WHERE id > ?1 ORDER BY id LIMIT ?2",
+            PHOTO_COLUMNS
+        ))?;
+
+        let rows = stmt.query_map(params![after_id, limit], photo_from_row)?;
+
+        let mut photos = Vec::new();
+        for row in rows {
+            photos.push(row?);
+        }
+        Ok(photos)
+    }
+
+    /// Number of photos with an id greater than `after_id`
+    pub fn count_photos_after(&self, after_id: i64) -> Result<i64> {
+        let count = self.conn.query_row(
+            "SELECT COUNT(*) FROM photos WHERE id > ?1",
+            [after_id],
+            |row| row.get(0),
+        )?;
+        Ok(count)
+    }
+
+    /// Thumbnail renditions of a photo, smallest first
+    pub fn list_renditions(&self, photo_id: i64) -> Result<Vec<PhotoRendition>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT max_size, format, path, width, height, size_bytes FROM photo_renditions
+             WHERE photo_id = ?1 ORDER BY max_size, format"
+        )?;
+
+        let rows = stmt.query_map([photo_id], |row| {
+            Ok(PhotoRendition {
+                max_size: row.get(0)?,
+                format: row.get(1)?,
+                path: row.get(2)?,
+                width: row.get(3)?,
+                height: row.get(4)?,
+                size_bytes: row.get(5)?,
+            })
+        })?;
+
+        let mut renditions = Vec::new();
+        for row in rows {
+            renditions.push(row?);
+        }
+        Ok(renditions)
+    }
+
+    /// All (photo id, perceptual hash) pairs for photos that have a hash
+    pub fn list_perceptual_hashes(&self) -> Result<Vec<(i64, u64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, phash FROM photos WHERE phash IS NOT NULL AND NOT pending_review ORDER BY id"
         )?;
 
         let rows = stmt.query_map([], |row| {
-            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)? as u64))
+        })?;
+
+        let mut hashes = Vec::new();
+        for row in rows {
+            hashes.push(row?);
+        }
+        Ok(hashes)
+    }
+
+    /// List all albums with photo counts and sizes
+    pub fn list_albums(&self) -> Result<Vec<AlbumSummary>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT a.id, a.name, a.description, a.cover_photo_id, a.sort_order, a.created_at, a.parent_id,
+                    COUNT(p.id) AS count, COALESCE(SUM(p.size_bytes), 0), MAX(p.id)
+             FROM albums a
+             LEFT JOIN (
+                 SELECT id, album, size_bytes FROM photos WHERE NOT pending_review
+                 UNION
+                 SELECT p.id, pa.album, p.size_bytes FROM photo_albums pa JOIN photos p ON p.id = pa.photo_id
+                 WHERE NOT p.pending_review
+             ) p ON p.album = a.name
+             GROUP BY a.id
+             ORDER BY a.sort_order, count DESC, a.name"
+        )?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok(AlbumSummary {
+                album: album_from_row(row)?,
+                count: row.get(7)?,
+                size_bytes: row.get(8)?,
+                latest_photo_id: row.get(9)?,
+            })
         })?;
 
         let mut albums = Vec::new();
@@ -337,6 +994,167 @@ impl Database {
         Ok(albums)
     }
 
+    /// Register an album found on disk; existing albums are left untouched
+    pub fn ensure_album(&self, name: &str) -> Result<()> {
+        ensure_album_row(&self.conn, name)?;
+        Ok(())
+    }
+
+    /// Returns None when an album with this name already exists; missing
+    /// parent albums are created along the way
+    pub fn create_album(&self, name: &str, description: Option<&str>) -> Result<Option<Album>> {
+        if self.get_album_by_name(name)?.is_some() {
+            return Ok(None);
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        let id = ensure_album_row(&tx, name)?;
+        tx.execute("UPDATE albums SET description = ?2 WHERE id = ?1", params![id, description])?;
+        tx.commit()?;
+        self.get_album(id)
+    }
+
+    pub fn has_sub_albums(&self, id: i64) -> Result<bool> {
+        let found = self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM albums WHERE parent_id = ?1)",
+            params![id],
+            |row| row.get(0),
+        )?;
+        Ok(found)
+    }
+
+    pub fn get_album(&self, id: i64) -> Result<Option<Album>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM albums WHERE id = ?1",
+            ALBUM_COLUMNS
+        ))?;
+        let mut rows = stmt.query(params![id])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(album_from_row(row)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_album_by_name(&self, name: &str) -> Result<Option<Album>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM albums WHERE name = ?1",
+            ALBUM_COLUMNS
+        ))?;
+        let mut rows = stmt.query(params![name])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(album_from_row(row)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Save description, cover and sort order; renaming goes through [`Self::rename_album`]
+    pub fn update_album(&self, album: &Album) -> Result<()> {
+        self.conn.execute(
+            "UPDATE albums SET description = ?2, cover_photo_id = ?3, sort_order = ?4 WHERE id = ?1",
+            params![album.id, album.description, album.cover_photo_id, album.sort_order],
+        )?;
+        Ok(())
+    }
+
+    /// Photos whose primary album is `album`, including those pending review
+    pub fn list_album_photos(&self, album: &str) -> Result<Vec<Photo>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM photos WHERE album = ?1 ORDER BY id",
+            PHOTO_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![album], photo_from_row)?;
+
+        let mut photos = Vec::new();
+        for row in rows {
+            photos.push(row?);
+        }
+        Ok(photos)
+    }
+
+    /// Rename or move an album, together with its sub-albums, everywhere it
+    /// is referenced, in one transaction
+    ///
+    /// `local_path`s starting with `old_dir` are rewritten to start with
+    /// `new_dir`; both should end with a path separator.
+    pub fn rename_album(&self, id: i64, old: &str, new: &str, old_dir: &str, new_dir: &str) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for (table, column) in [
+            ("albums", "name"),
+            ("photos", "album"),
+            ("photo_albums", "album"),
+            ("shares", "album"),
+            ("guest_links", "album"),
+            ("upload_chunks", "album"),
+            ("upload_tasks", "album"),
+        ] {
+            tx.execute(
+                &format!(
+                    "UPDATE {0} SET {1} = ?3 || substr({1}, length(?1) + 1) WHERE {2}",
+                    table,
+                    column,
+                    album_match(column)
+                ),
+                params![old, true, new],
+            )?;
+        }
+        tx.execute(
+            "UPDATE photos SET local_path = ?2 || substr(local_path, length(?1) + 1)
+             WHERE substr(local_path, 1, length(?1)) = ?1",
+            params![old_dir, new_dir],
+        )?;
+
+        // Re-attach under the new parent, which may not exist yet
+        let parent_id = match new.rsplit_once('/') {
+            Some((parent, _)) => Some(ensure_album_row(&tx, parent)?),
+            None => None,
+        };
+        tx.execute("UPDATE albums SET parent_id = ?2 WHERE id = ?1", params![id, parent_id])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Fold `source` into `target`: move its photos, memberships, share and
+    /// guest links over and drop the source album
+    pub fn merge_albums(&self, source: &str, target: &str, moves: &[PhotoMove]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        move_photo_rows(&tx, moves)?;
+        tx.execute(
+            "UPDATE OR IGNORE photo_albums SET album = ?2 WHERE album = ?1",
+            params![source, target],
+        )?;
+        tx.execute("DELETE FROM photo_albums WHERE album = ?1", params![source])?;
+        tx.execute(
+            "DELETE FROM photo_albums WHERE album = ?1 AND photo_id IN (SELECT id FROM photos WHERE album = ?1)",
+            params![target],
+        )?;
+        for table in ["shares", "guest_links", "upload_chunks", "upload_tasks"] {
+            tx.execute(
+                &format!("UPDATE {} SET album = ?2 WHERE album = ?1", table),
+                params![source, target],
+            )?;
+        }
+        tx.execute("DELETE FROM albums WHERE name = ?1", params![source])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Delete an album in one transaction, moving its photos elsewhere
+    /// (`moves`) and/or deleting them (`deleted`)
+    ///
+    /// Memberships, share links and guest upload links of the album go with it.
+    pub fn delete_album(&self, name: &str, moves: &[PhotoMove], deleted: &[i64]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        move_photo_rows(&tx, moves)?;
+        for &id in deleted {
+            delete_photo_rows(&tx, id)?;
+        }
+        tx.execute("DELETE FROM photo_albums WHERE album = ?1", params![name])?;
+        tx.execute("DELETE FROM shares WHERE album = ?1", params![name])?;
+        tx.execute("DELETE FROM guest_links WHERE album = ?1", params![name])?;
+        tx.execute("DELETE FROM albums WHERE name = ?1", params![name])?;
+        tx.commit()?;
+        Ok(())
+    }
+
     // Chunked upload operations
     pub fn create_upload_session(
         &self,
@@ -519,7 +1337,252 @@ impl Database {
         Ok(rows_affected)
     }
 
+    // Background job operations
+    pub fn get_job(&self, name: &str) -> Result<Option<BackgroundJob>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT name, status, last_id, total, processed, succeeded, failed, force, started_at, updated_at
+             FROM background_jobs WHERE name = ?1"
+        )?;
+        let mut rows = stmt.query(params![name])?;
+
+        if let Some(row) = rows.next()? {
+            Ok(Some(BackgroundJob {
+                name: row.get(0)?,
+                status: match row.get::<_, String>(1)?.as_str() {
+                    "running" => JobStatus::Running,
+                    "completed" => JobStatus::Completed,
+                    _ => JobStatus::Failed,
+                },
+                last_id: row.get(2)?,
+                total: row.get(3)?,
+                processed: row.get(4)?,
+                succeeded: row.get(5)?,
+                failed: row.get(6)?,
+                force: row.get::<_, i32>(7)? != 0,
+                started_at: row.get(8)?,
+                updated_at: row.get(9)?,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn save_job(&self, job: &BackgroundJob) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO background_jobs (name, status, last_id, total, processed, succeeded, failed, force, started_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+             ON CONFLICT(name) DO UPDATE SET
+                 status = excluded.status,
+                 last_id = excluded.last_id,
+                 total = excluded.total,
+                 processed = excluded.processed,
+                 succeeded = excluded.succeeded,
+                 failed = excluded.failed,
+                 force = excluded.force,
+                 started_at = excluded.started_at,
+                 updated_at = excluded.updated_at",
+            params![
+                job.name, job.status.as_str(), job.last_id, job.total, job.processed,
+                job.succeeded, job.failed, job.force as i32, job.started_at, job.updated_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    // Share operations
+    pub fn insert_share(&self, share: &Share) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO shares (id, photo_id, album, password_hash, disable_download, expires_at, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                share.id, share.photo_id, share.album, share.password_hash,
+                share.disable_download as i32, share.expires_at, share.created_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_share(&self, id: &str) -> Result<Option<Share>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, photo_id, album, password_hash, disable_download, expires_at, created_at
+             FROM shares WHERE id = ?1"
+        )?;
+        let mut rows = stmt.query(params![id])?;
+
+        if let Some(row) = rows.next()? {
+            Ok(Some(share_from_row(row)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn list_shares(&self) -> Result<Vec<Share>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, photo_id, album, password_hash, disable_download, expires_at, created_at
+             FROM shares ORDER BY created_at DESC"
+        )?;
+        let rows = stmt.query_map([], share_from_row)?;
+
+        let mut shares = Vec::new();
+        for row in rows {
+            shares.push(row?);
+        }
+        Ok(shares)
+    }
+
+    /// Returns false when the share did not exist
+    pub fn delete_share(&self, id: &str) -> Result<bool> {
+        let rows = self.conn.execute("DELETE FROM shares WHERE id = ?1", params![id])?;
+        Ok(rows > 0)
+    }
+
+    /// Whether a photo belongs to an album, including albums it was merged into
+    pub fn photo_in_album(&self, photo_id: i64, album: &str) -> Result<bool> {
+        let found = self.conn.query_row(
+            "SELECT EXISTS(
+                 SELECT 1 FROM photos WHERE id = ?1 AND album = ?2 AND NOT pending_review
+                 UNION ALL
+                 SELECT 1 FROM photo_albums pa JOIN photos p ON p.id = pa.photo_id
+                 WHERE pa.photo_id = ?1 AND pa.album = ?2 AND NOT p.pending_review
+             )",
+            params![photo_id, album],
+            |row| row.get(0),
+        )?;
+        Ok(found)
+    }
+
+    // Guest upload link operations
+    pub fn insert_guest_link(&self, link: &GuestLink) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO guest_links (id, album, max_files, max_bytes, files_used, bytes_used, require_review, expires_at, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                link.id, link.album, link.max_files, link.max_bytes, link.files_used,
+                link.bytes_used, link.require_review as i32, link.expires_at, link.created_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_guest_link(&self, id: &str) -> Result<Option<GuestLink>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, album, max_files, max_bytes, files_used, bytes_used, require_review, expires_at, created_at
+             FROM guest_links WHERE id = ?1"
+        )?;
+        let mut rows = stmt.query(params![id])?;
+
+        if let Some(row) = rows.next()? {
+            Ok(Some(guest_link_from_row(row)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn list_guest_links(&self) -> Result<Vec<GuestLink>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, album, max_files, max_bytes, files_used, bytes_used, require_review, expires_at, created_at
+             FROM guest_links ORDER BY created_at DESC"
+        )?;
+        let rows = stmt.query_map([], guest_link_from_row)?;
+
+        let mut links = Vec::new();
+        for row in rows {
+            links.push(row?);
+        }
+        Ok(links)
+    }
+
+    /// Returns false when the link did not exist
+    pub fn delete_guest_link(&self, id: &str) -> Result<bool> {
+        let rows = self.conn.execute("DELETE FROM guest_links WHERE id = ?1", params![id])?;
+        self.conn.execute("DELETE FROM guest_uploads WHERE link_id = ?1", params![id])?;
+        Ok(rows > 0)
+    }
+
+    /// Reserve one file and `total_size` bytes of a link's quota for an
+    /// upload; returns false when the quota would be exceeded
+    pub fn reserve_guest_upload(&self, link_id: &str, upload_id: &str, total_size: i64) -> Result<bool> {
+        let tx = self.conn.unchecked_transaction()?;
+        let reserved = tx.execute(
+            "UPDATE guest_links SET files_used = files_used + 1, bytes_used = bytes_used + ?2
+             WHERE id = ?1
+               AND (max_files IS NULL OR files_used + 1 <= max_files)
+               AND (max_bytes IS NULL OR bytes_used + ?2 <= max_bytes)",
+            params![link_id, total_size],
+        )? == 1;
+        if reserved {
+            tx.execute(
+                "INSERT INTO guest_uploads (upload_id, link_id, total_size) VALUES (?1, ?2, ?3)",
+                params![upload_id, link_id, total_size],
+            )?;
+        }
+        tx.commit()?;
+        Ok(reserved)
+    }
+
+    /// Whether an upload was started through a link that requires review
+    pub fn guest_upload_requires_review(&self, upload_id: &str) -> Result<bool> {
+        let required = self.conn.query_row(
+            "SELECT EXISTS(
+                 SELECT 1 FROM guest_uploads u JOIN guest_links l ON l.id = u.link_id
+                 WHERE u.upload_id = ?1 AND l.require_review
+             )",
+            params![upload_id],
+            |row| row.get(0),
+        )?;
+        Ok(required)
+    }
+
+    /// Link id and reserved size of an unfinished guest upload
+    pub fn get_guest_upload(&self, upload_id: &str) -> Result<Option<(String, i64)>> {
+        let mut stmt = self.conn.prepare("SELECT link_id, total_size FROM guest_uploads WHERE upload_id = ?1")?;
+        let mut rows = stmt.query(params![upload_id])?;
+
+        if let Some(row) = rows.next()? {
+            Ok(Some((row.get(0)?, row.get(1)?)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Forget a guest upload; with `release` its reservation is returned to the link
+    pub fn finish_guest_upload(&self, upload_id: &str, release: bool) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        if release {
+            tx.execute(
+                "UPDATE guest_links SET
+                     files_used = MAX(files_used - 1, 0),
+                     bytes_used = MAX(bytes_used - (SELECT total_size FROM guest_uploads WHERE upload_id = ?1), 0)
+                 WHERE id = (SELECT link_id FROM guest_uploads WHERE upload_id = ?1)",
+                params![upload_id],
+            )?;
+        }
+        tx.execute("DELETE FROM guest_uploads WHERE upload_id = ?1", params![upload_id])?;
+        tx.commit()?;
+        Ok(())
+    }
+
     // Admin Config operations
+    /// Secret for share and guest-upload links, generated randomly on first use
+    ///
+    /// Kept apart from the admin `jwt_secret`, which defaults to a well-known
+    /// value, so link and unlock tokens cannot be forged on a stock install.
+    pub fn get_or_create_link_secret(&self, default_secret: &str) -> Result<String> {
+        self.get_or_create_admin_config(default_secret)?;
+        let existing: Option<String> =
+            self.conn
+                .query_row("SELECT link_secret FROM admin_config WHERE id = 1", [], |row| row.get(0))?;
+        if let Some(secret) = existing {
+            return Ok(secret);
+        }
+        let secret = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+        self.conn.execute(
+            "UPDATE admin_config SET link_secret = ?1 WHERE id = 1",
+            params![secret],
+        )?;
+        Ok(secret)
+    }
+
     #[allow(dead_code)]
     pub fn get_or_create_admin_config(&self, default_secret: &str) -> Result<AdminConfig> {
         let mut stmt = self.conn.prepare(
@@ -554,3 +1617,5 @@ impl Database {
         }
     }
 }
+
+
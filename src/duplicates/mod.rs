@@ -0,0 +1,482 @@
+//! Library-wide exact-duplicate detection and merging
+//!
+//! Files are considered duplicates when their SHA-256 matches. Besides rows
+//! in `photos` this also finds untracked copies on disk: re-uploading the
+//! same content into another album writes a second file but keeps the
+//! original row (`file_hash` is unique).
+
+use crate::db::Database;
+use crate::models::Photo;
+use anyhow::Result;
+use serde::Serialize;
+use sha2::Digest;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateEntry {
+    /// `None` for files on disk that have no database row
+    pub photo_id: Option<i64>,
+    pub album: String,
+    pub path: String,
+    pub size_bytes: i64,
+    #[serde(skip)]
+    thumbnail_path: Option<String>,
+    /// Whether a converted `.jpg` next to `path` belongs to this entry
+    #[serde(skip)]
+    has_jpeg_variant: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateGroup {
+    pub file_hash: String,
+    pub size_bytes: i64,
+    pub entries: Vec<DuplicateEntry>,
+    /// Bytes freed by keeping a single copy (originals only)
+    pub reclaimable_bytes: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MergeReport {
+    pub file_hash: String,
+    pub kept_photo_id: Option<i64>,
+    pub kept_path: String,
+    pub removed_paths: Vec<String>,
+    pub albums_linked: Vec<String>,
+    pub bytes_reclaimed: u64,
+}
+
+/// SHA-256 of a file, streamed in 8KB blocks
+pub fn hash_file(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = sha2::Sha256::new();
+    let mut buffer = [0u8; 8192];
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// The JPEG variant written next to a converted original
+///
+/// Only originals flagged `has_jpeg_variant` own one; a same-stem `.jpg`
+/// next to any other file is an unrelated photo (e.g. the camera JPEG of a
+/// RAW+JPEG pair) and must never be treated as a variant.
+fn jpeg_variant_path(path: &Path, has_jpeg_variant: bool) -> Option<PathBuf> {
+    if !has_jpeg_variant {
+        return None;
+    }
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    (ext != "jpg" && ext != "jpeg").then(|| path.with_extension("jpg"))
+}
+
+fn is_hidden(name: &str) -> bool {
+    name.starts_with('.')
+}
+
+/// Album name of a file on disk: its directory relative to `base_path`
+fn album_of(base_path: &Path, path: &Path) -> String {
+    path.parent()
+        .and_then(|p| p.strip_prefix(base_path).ok())
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Group every photo row and untracked media file by content hash
+///
+/// Untracked files are only hashed when another file of the same size
+/// exists, so a scan of a mostly unique library stays cheap. This does
+/// blocking I/O; run it off the async runtime.
+pub fn find_groups(base_path: &Path, photos: &[Photo]) -> Result<Vec<DuplicateGroup>> {
+    let mut tracked: HashSet<PathBuf> = HashSet::new();
+    for photo in photos {
+        let path = PathBuf::from(&photo.local_path);
+        if let Some(variant) = jpeg_variant_path(&path, photo.has_jpeg_variant) {
+            tracked.insert(variant);
+        }
+        tracked.insert(path);
+    }
+
+    // Untracked media files under the library root
+    let mut untracked: Vec<(PathBuf, u64)> = Vec::new();
+    let walker = WalkDir::new(base_path)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !is_hidden(&e.file_name().to_string_lossy()));
+    for entry in walker.filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let path = entry.path();
+        let is_sidecar = path
+            .extension()
+            .map(|e| e.eq_ignore_ascii_case("xmp"))
+            .unwrap_or(false);
+        if is_sidecar || tracked.contains(path) || is_untracked_variant(path, &tracked) {
+            continue;
+        }
+        if let Ok(meta) = entry.metadata() {
+            untracked.push((path.to_path_buf(), meta.len()));
+        }
+    }
+
+    let mut size_counts: HashMap<u64, usize> = HashMap::new();
+    let mut rows: Vec<(&Photo, u64)> = Vec::new();
+    for photo in photos {
+        // Rows whose file is gone cannot be kept or merged
+        if let Ok(meta) = std::fs::metadata(&photo.local_path) {
+            *size_counts.entry(meta.len()).or_default() += 1;
+            rows.push((photo, meta.len()));
+        }
+    }
+    for (_, size) in &untracked {
+        *size_counts.entry(*size).or_default() += 1;
+    }
+
+    let mut by_hash: HashMap<String, Vec<DuplicateEntry>> = HashMap::new();
+    for (photo, size) in rows {
+        let hash = match &photo.file_hash {
+            Some(hash) => hash.clone(),
+            None if size_counts[&size] > 1 => hash_file(Path::new(&photo.local_path))?,
+            None => continue,
+        };
+        by_hash.entry(hash).or_default().push(DuplicateEntry {
+            photo_id: Some(photo.id),
+            album: photo.album.clone(),
+            path: photo.local_path.clone(),
+            size_bytes: size as i64,
+            thumbnail_path: photo.thumbnail_path.clone(),
+            has_jpeg_variant: photo.has_jpeg_variant,
+        });
+    }
+    for (path, size) in untracked {
+        if size_counts[&size] < 2 {
+            continue;
+        }
+        let hash = match hash_file(&path) {
+            Ok(hash) => hash,
+            Err(e) => {
+                tracing::warn!("Failed to hash {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        let has_jpeg_variant = owns_untracked_variant(&path, &tracked);
+        by_hash.entry(hash).or_default().push(DuplicateEntry {
+            photo_id: None,
+            album: album_of(base_path, &path),
+            path: path.to_string_lossy().to_string(),
+            size_bytes: size as i64,
+            thumbnail_path: None,
+            has_jpeg_variant,
+        });
+    }
+
+    let mut groups: Vec<DuplicateGroup> = by_hash
+        .into_iter()
+        .filter(|(_, entries)| entries.len() > 1)
+        .map(|(file_hash, mut entries)| {
+            // Tracked rows first (oldest id first), then untracked files by path
+            entries.sort_by(|a, b| match (a.photo_id, b.photo_id) {
+                (Some(x), Some(y)) => x.cmp(&y),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => a.path.cmp(&b.path),
+            });
+            let size_bytes = entries[0].size_bytes;
+            let reclaimable_bytes = size_bytes * (entries.len() as i64 - 1);
+            DuplicateGroup {
+                file_hash,
+                size_bytes,
+                entries,
+                reclaimable_bytes,
+            }
+        })
+        .collect();
+    groups.sort_by(|a, b| {
+        b.reclaimable_bytes
+            .cmp(&a.reclaimable_bytes)
+            .then_with(|| a.file_hash.cmp(&b.file_hash))
+    });
+    Ok(groups)
+}
+
+const HEIF_EXTENSIONS: [&str; 4] = ["heic", "HEIC", "heif", "HEIF"];
+
+/// A `.jpg` sitting next to an untracked HEIC/HEIF original is that
+/// original's variant; next to a tracked one, the row's flag decides
+fn is_untracked_variant(path: &Path, tracked: &HashSet<PathBuf>) -> bool {
+    let is_jpg = path
+        .extension()
+        .map(|e| e.eq_ignore_ascii_case("jpg"))
+        .unwrap_or(false);
+    is_jpg
+        && HEIF_EXTENSIONS.iter().any(|ext| {
+            let original = path.with_extension(ext);
+            !tracked.contains(&original) && original.exists()
+        })
+}
+
+/// Whether an untracked HEIC/HEIF file has an untracked `.jpg` variant
+fn owns_untracked_variant(path: &Path, tracked: &HashSet<PathBuf>) -> bool {
+    let is_heif = path
+        .extension()
+        .map(|e| HEIF_EXTENSIONS.iter().any(|ext| e == *ext))
+        .unwrap_or(false);
+    let variant = path.with_extension("jpg");
+    is_heif && !tracked.contains(&variant) && variant.exists()
+}
+
+/// Index of the entry to keep: the requested photo if it is in the group,
+/// otherwise the oldest tracked row (entries are sorted that way)
+pub fn pick_keeper(group: &DuplicateGroup, keep_photo_id: Option<i64>) -> usize {
+    keep_photo_id
+        .and_then(|id| group.entries.iter().position(|e| e.photo_id == Some(id)))
+        .unwrap_or(0)
+}
+
+/// Database half of a merge, carried over to [`remove_merged_files`]
+pub struct MergePlan {
+    file_hash: String,
+    keeper: DuplicateEntry,
+    others: Vec<DuplicateEntry>,
+    rendition_paths: Vec<PathBuf>,
+    albums_linked: Vec<String>,
+}
+
+/// Keep one file of a group and remove the rest
+///
+/// Albums of the removed copies are recorded as memberships of the kept
+/// photo. Database changes are committed before any file is deleted.
+pub fn merge_group(db: &Database, group: &DuplicateGroup, keep_photo_id: Option<i64>) -> Result<MergeReport> {
+    let plan = merge_group_rows(db, group, keep_photo_id)?;
+    Ok(remove_merged_files(plan))
+}
+
+/// Fold the database rows of a group into the kept photo
+///
+/// Only touches the database, so callers can hold the database lock for
+/// this step alone and delete files afterwards.
+pub fn merge_group_rows(db: &Database, group: &DuplicateGroup, keep_photo_id: Option<i64>) -> Result<MergePlan> {
+    let keep_index = pick_keeper(group, keep_photo_id);
+    let keeper = group.entries[keep_index].clone();
+    let others: Vec<DuplicateEntry> = group
+        .entries
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != keep_index)
+        .map(|(_, e)| e.clone())
+        .collect();
+
+    let mut albums_linked: Vec<String> = Vec::new();
+    for entry in &others {
+        if entry.album != keeper.album && !albums_linked.contains(&entry.album) {
+            albums_linked.push(entry.album.clone());
+        }
+    }
+
+    // Thumbnail renditions of the rows about to be removed; paths are
+    // content-addressed, so skip any the kept photo shares
+    let keeper_renditions: Vec<String> = match keeper.photo_id {
+        Some(id) => db.list_renditions(id)?.into_iter().map(|r| r.path).collect(),
+        None => Vec::new(),
+    };
+    let mut rendition_paths: Vec<PathBuf> = Vec::new();
+    for id in others.iter().filter_map(|e| e.photo_id) {
+        for r in db.list_renditions(id)? {
+            if !keeper_renditions.contains(&r.path) {
+                rendition_paths.push(PathBuf::from(r.path));
+            }
+        }
+    }
+
+    if let Some(keep_id) = keeper.photo_id {
+        let duplicate_ids: Vec<i64> = others.iter().filter_map(|e| e.photo_id).collect();
+        db.merge_photo_rows(keep_id, &duplicate_ids, &albums_linked)?;
+    } else {
+        // Nothing in the database to link the albums to
+        albums_linked.clear();
+    }
+
+    Ok(MergePlan {
+        file_hash: group.file_hash.clone(),
+        keeper,
+        others,
+        rendition_paths,
+        albums_linked,
+    })
+}
+
+/// Delete the removed copies, their variants and thumbnails from disk
+///
+/// Uses blocking file I/O; call from `spawn_blocking` in async code.
+pub fn remove_merged_files(plan: MergePlan) -> MergeReport {
+    let MergePlan { file_hash, keeper, others, rendition_paths, albums_linked } = plan;
+    let keeper_path = Path::new(&keeper.path);
+    let keeper_sidecar = crate::xmp::sidecar_path(keeper_path);
+    let keeper_variant = jpeg_variant_path(keeper_path, keeper.has_jpeg_variant);
+    let mut removed_paths = Vec::new();
+    let mut bytes_reclaimed = 0u64;
+
+    for entry in &others {
+        let path = Path::new(&entry.path);
+        let mut candidates: Vec<PathBuf> = vec![path.to_path_buf()];
+        if let Some(variant) = jpeg_variant_path(path, entry.has_jpeg_variant)
+            && Some(&variant) != keeper_variant.as_ref()
+        {
+            candidates.push(variant);
+        }
+        if let Some(thumb) = &entry.thumbnail_path
+            && keeper.thumbnail_path.as_ref() != Some(thumb)
+        {
+            candidates.push(PathBuf::from(thumb));
+        }
+
+        // Carry a sidecar over if the kept file has none, otherwise drop it
+        let sidecar = crate::xmp::sidecar_path(path);
+        if sidecar.exists() && sidecar != keeper_sidecar {
+            if !keeper_sidecar.exists() {
+                if let Err(e) = std::fs::rename(&sidecar, &keeper_sidecar) {
+                    tracing::warn!("Failed to move XMP sidecar {}: {}", sidecar.display(), e);
+                }
+            } else {
+                candidates.push(sidecar);
+            }
+        }
+
+        candidates.retain(|c| !rendition_paths.contains(c));
+        for candidate in candidates {
+            let Ok(meta) = std::fs::metadata(&candidate) else {
+                continue;
+            };
+            match std::fs::remove_file(&candidate) {
+                Ok(()) => {
+                    bytes_reclaimed += meta.len();
+                    removed_paths.push(candidate.to_string_lossy().to_string());
+                }
+                Err(e) => tracing::warn!("Failed to delete duplicate {}: {}", candidate.display(), e),
+            }
+        }
+    }
+
+    for path in rendition_paths {
+        let Ok(meta) = std::fs::metadata(&path) else {
+            continue;
+        };
+        match std::fs::remove_file(&path) {
+            Ok(()) => {
+                bytes_reclaimed += meta.len();
+                removed_paths.push(path.to_string_lossy().to_string());
+            }
+            Err(e) => tracing::warn!("Failed to delete thumbnail {}: {}", path.display(), e),
+        }
+    }
+
+    MergeReport {
+        file_hash,
+        kept_photo_id: keeper.photo_id,
+        kept_path: keeper.path,
+        removed_paths,
+        albums_linked,
+        bytes_reclaimed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_library(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("skynas-dup-{}-{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("A")).unwrap();
+        std::fs::create_dir_all(dir.join("B")).unwrap();
+        std::fs::create_dir_all(dir.join(".thumbnails")).unwrap();
+        std::fs::create_dir_all(dir.join(".skynas")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_find_and_merge_untracked_copy() {
+        let base = temp_library("merge");
+        let db = Database::new(base.join(".skynas").join("test.db")).unwrap();
+
+        let original = base.join("A").join("IMG_1.jpg");
+        let copy = base.join("B").join("IMG_1.jpg");
+        std::fs::write(&original, b"same bytes").unwrap();
+        std::fs::write(&copy, b"same bytes").unwrap();
+        std::fs::write(base.join("B").join("other.jpg"), b"different!").unwrap();
+        std::fs::write(base.join(".thumbnails").join("t.jpg"), b"same bytes").unwrap();
+
+        let photo = Photo {
+            filename: "IMG_1.jpg".to_string(),
+            album: "A".to_string(),
+            file_hash: Some(hash_file(&original).unwrap()),
+            size_bytes: 10,
+            local_path: original.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let id = db.insert_photo(&photo).unwrap();
+
+        let photos = db.list_all_photos().unwrap();
+        let groups = find_groups(&base, &photos).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].entries.len(), 2);
+        assert_eq!(groups[0].entries[0].photo_id, Some(id));
+        assert_eq!(groups[0].reclaimable_bytes, 10);
+
+        let report = merge_group(&db, &groups[0], None).unwrap();
+        assert_eq!(report.kept_photo_id, Some(id));
+        assert_eq!(report.albums_linked, vec!["B".to_string()]);
+        assert_eq!(report.bytes_reclaimed, 10);
+        assert!(original.exists());
+        assert!(!copy.exists());
+        assert_eq!(db.list_album_memberships(id).unwrap(), vec!["B".to_string()]);
+
+        let (photos_in_b, total) = db.list_photos(Some("B"), false, Default::default(), 10, 0).unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(photos_in_b[0].id, id);
+
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_merge_keeps_same_stem_jpeg_of_other_photo() {
+        let base = temp_library("stem");
+        let db = Database::new(base.join(".skynas").join("test.db")).unwrap();
+
+        let original = base.join("A").join("IMG_1.png");
+        let copy = base.join("B").join("IMG_1.png");
+        let neighbour = base.join("B").join("IMG_1.jpg");
+        std::fs::write(&original, b"png bytes").unwrap();
+        std::fs::write(&copy, b"png bytes").unwrap();
+        std::fs::write(&neighbour, b"a real jpeg").unwrap();
+
+        for (path, album) in [(&original, "A"), (&copy, "B"), (&neighbour, "B")] {
+            let photo = Photo {
+                filename: path.file_name().unwrap().to_string_lossy().to_string(),
+                album: album.to_string(),
+                size_bytes: std::fs::metadata(path).unwrap().len() as i64,
+                local_path: path.to_string_lossy().to_string(),
+                ..Default::default()
+            };
+            db.insert_photo(&photo).unwrap();
+        }
+
+        let photos = db.list_all_photos().unwrap();
+        let groups = find_groups(&base, &photos).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].entries.len(), 2);
+
+        let report = merge_group(&db, &groups[0], None).unwrap();
+        assert_eq!(report.removed_paths, vec![copy.to_string_lossy().to_string()]);
+        assert!(original.exists());
+        assert!(!copy.exists());
+        assert!(neighbour.exists());
+
+        let _ = std::fs::remove_dir_all(&base);
+    }
+}
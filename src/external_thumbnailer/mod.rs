@@ -0,0 +1,171 @@
+//! External thumbnailer commands (ffmpeg, ffmpegthumbnailer, ...)
+//!
+//! Used for videos and for any file the `image` crate cannot decode. The
+//! configured thumbnailers are tried in order until one produces a decodable
+//! frame. Commands run without a shell: the template is split on whitespace
+//! and `{input}`, `{output}` and `{size}` are substituted per argument, so
+//! paths containing spaces are passed through intact.
+
+use crate::config::{Config, ExternalThumbnailerConfig};
+use anyhow::{Context, Result};
+use image::DynamicImage;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+/// Caps the number of external processes running at once
+static PERMITS: OnceLock<Semaphore> = OnceLock::new();
+
+/// Build the argument list for one invocation
+pub fn render_command(template: &str, input: &Path, output: &Path, size: u32) -> Vec<String> {
+    template
+        .split_whitespace()
+        .map(|arg| {
+            arg.replace("{input}", &input.to_string_lossy())
+                .replace("{output}", &output.to_string_lossy())
+                .replace("{size}", &size.to_string())
+        })
+        .collect()
+}
+
+fn applies_to(thumbnailer: &ExternalThumbnailerConfig, input: &Path) -> bool {
+    if thumbnailer.extensions.is_empty() {
+        return true;
+    }
+    let ext = input
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    thumbnailer.extensions.iter().any(|e| e.eq_ignore_ascii_case(&ext))
+}
+
+/// Produce a frame for `input` with the first thumbnailer that succeeds
+pub async fn extract_frame(input: &Path, config: &Config) -> Result<DynamicImage> {
+    let thumbnailers: Vec<&ExternalThumbnailerConfig> = config
+        .thumbnails
+        .external_thumbnailers
+        .iter()
+        .filter(|t| applies_to(t, input))
+        .collect();
+    if thumbnailers.is_empty() {
+        anyhow::bail!("No external thumbnailer configured for {:?}", input);
+    }
+
+    let size = config.thumbnails.renditions.iter().map(|r| r.max_size).max().unwrap_or(0);
+    let dir = crate::thumbnail::ThumbnailGenerator::thumbnail_dir(config);
+    tokio::fs::create_dir_all(&dir).await?;
+
+    let semaphore = PERMITS.get_or_init(|| Semaphore::new(config.thumbnails.external_concurrency.max(1)));
+    let _permit = semaphore.acquire().await?;
+
+    let mut errors = Vec::new();
+    for thumbnailer in thumbnailers {
+        // Hidden so a crash never leaves something that looks like a thumbnail
+        let output = dir.join(format!(".frame_{}.jpg", uuid::Uuid::new_v4()));
+        let result = run_one(thumbnailer, input, &output, size).await;
+        let _ = tokio::fs::remove_file(&output).await;
+
+        match result {
+            Ok(img) => return Ok(img),
+            Err(e) => {
+                tracing::debug!("Thumbnailer {} failed for {:?}: {:#}", thumbnailer.name, input, e);
+                errors.push(format!("{}: {:#}", thumbnailer.name, e));
+            }
+        }
+    }
+
+    anyhow::bail!("All external thumbnailers failed for {:?} ({})", input, errors.join("; "))
+}
+
+async fn run_one(
+    thumbnailer: &ExternalThumbnailerConfig,
+    input: &Path,
+    output: &Path,
+    size: u32,
+) -> Result<DynamicImage> {
+    let args = render_command(&thumbnailer.command, input, output, size);
+    let (program, args) = args.split_first().context("Empty thumbnailer command")?;
+
+    let child = tokio::process::Command::new(program)
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("Failed to start {}", program))?;
+
+    let timeout = Duration::from_secs(thumbnailer.timeout_secs);
+    let result = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| anyhow::anyhow!("Timed out after {}s", thumbnailer.timeout_secs))??;
+
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        anyhow::bail!("Exited with {}: {}", result.status, stderr.trim());
+    }
+
+    let output = output.to_path_buf();
+    tokio::task::spawn_blocking(move || -> Result<DynamicImage> {
+        // The output extension is only a hint; sniff the actual format
+        let img = image::io::Reader::open(&output)?
+            .with_guessed_format()?
+            .decode()
+            .context("Thumbnailer output is not a decodable image")?;
+        Ok(img)
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_command() {
+        let args = render_command(
+            "ffmpegthumbnailer -i {input} -o {output} -s {size}",
+            Path::new("/photos/My Trip/clip.mov"),
+            Path::new("/tmp/out.jpg"),
+            768,
+        );
+        assert_eq!(args, vec![
+            "ffmpegthumbnailer", "-i", "/photos/My Trip/clip.mov", "-o", "/tmp/out.jpg", "-s", "768",
+        ]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_fallback_order_and_timeout() {
+        let dir = std::env::temp_dir().join(format!("skynas_ext_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // PNG bytes behind a video extension: only the external path can read it
+        let input = dir.join("clip.mp4");
+        DynamicImage::ImageRgb8(image::ImageBuffer::from_pixel(8, 6, image::Rgb([1, 2, 3])))
+            .save_with_format(&input, image::ImageFormat::Png)
+            .unwrap();
+
+        let mut config = Config::default();
+        config.storage.base_path = dir.clone();
+        let thumbnailer = |name: &str, command: &str, timeout_secs| ExternalThumbnailerConfig {
+            name: name.to_string(),
+            command: command.to_string(),
+            extensions: vec!["mp4".to_string()],
+            timeout_secs,
+        };
+        config.thumbnails.external_thumbnailers = vec![
+            thumbnailer("missing", "skynas-no-such-binary {input} {output}", 5),
+            thumbnailer("slow", "sleep 5", 1),
+            thumbnailer("copy", "cp {input} {output}", 5),
+        ];
+
+        let img = extract_frame(&input, &config).await.unwrap();
+        assert_eq!((img.width(), img.height()), (8, 6));
+
+        config.thumbnails.external_thumbnailers.truncate(2);
+        assert!(extract_frame(&input, &config).await.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+//! Bounded on-disk cache for on-demand image renditions
+//!
+//! Files live flat in `.thumbnails/cache`. Recency is tracked in memory and
+//! seeded from file modification times on startup, so the least recently
+//! served entries are evicted first once the total size exceeds the cap.
+
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+pub struct ImageCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    /// key -> (size in bytes, last access tick)
+    entries: HashMap<String, (u64, u64)>,
+    /// last access tick -> key, oldest first
+    recency: BTreeMap<u64, String>,
+    total_bytes: u64,
+    tick: u64,
+}
+
+impl CacheState {
+    fn touch(&mut self, key: &str) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.recency.remove(&entry.1);
+            entry.1 = tick;
+            self.recency.insert(tick, key.to_string());
+        }
+    }
+
+    fn insert(&mut self, key: String, size: u64) {
+        self.remove(&key);
+        self.tick += 1;
+        self.entries.insert(key.clone(), (size, self.tick));
+        self.recency.insert(self.tick, key);
+        self.total_bytes += size;
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((size, tick)) = self.entries.remove(key) {
+            self.recency.remove(&tick);
+            self.total_bytes -= size;
+        }
+    }
+}
+
+impl ImageCache {
+    /// Open the cache directory, indexing files left by a previous run
+    pub fn open(dir: PathBuf, max_bytes: u64) -> Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+
+        let mut existing = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !meta.is_file() || name.starts_with('.') {
+                continue;
+            }
+            let modified = meta.modified().unwrap_or(std::time::UNIX_EPOCH);
+            existing.push((modified, name, meta.len()));
+        }
+        existing.sort();
+
+        let mut state = CacheState::default();
+        for (_, name, size) in existing {
+            state.insert(name, size);
+        }
+
+        let cache = Self {
+            dir,
+            max_bytes,
+            state: Mutex::new(state),
+        };
+        cache.evict();
+        Ok(cache)
+    }
+
+    /// Path of a cached entry, marking it as recently used
+    pub fn get(&self, key: &str) -> Option<PathBuf> {
+        let mut state = self.state.lock().unwrap();
+        if !state.entries.contains_key(key) {
+            return None;
+        }
+
+        let path = self.dir.join(key);
+        if path.exists() {
+            state.touch(key);
+            Some(path)
+        } else {
+            // Removed behind our back
+            state.remove(key);
+            None
+        }
+    }
+
+    /// Store an entry and evict old ones until the cache fits its cap
+    pub fn insert(&self, key: &str, bytes: &[u8]) -> Result<PathBuf> {
+        let path = self.dir.join(key);
+        // Write then rename so concurrent readers never see a partial file
+        let tmp = self.dir.join(format!(".{}.{}", key, uuid::Uuid::new_v4()));
+        std::fs::write(&tmp, bytes)?;
+        std::fs::rename(&tmp, &path)?;
+
+        self.state.lock().unwrap().insert(key.to_string(), bytes.len() as u64);
+        self.evict();
+        Ok(path)
+    }
+
+    /// Total size of all cached entries in bytes
+    #[allow(dead_code)]
+    pub fn total_bytes(&self) -> u64 {
+        self.state.lock().unwrap().total_bytes
+    }
+
+    fn evict(&self) {
+        let mut state = self.state.lock().unwrap();
+        while state.total_bytes > self.max_bytes {
+            let Some(key) = state.recency.values().next().cloned() else {
+                break;
+            };
+            state.remove(&key);
+            if let Err(e) = std::fs::remove_file(self.dir.join(&key)) {
+                tracing::warn!("Failed to evict cached image {}: {}", key, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_eviction() {
+        let dir = std::env::temp_dir().join(format!("skynas_cache_{}", uuid::Uuid::new_v4()));
+        let cache = ImageCache::open(dir.clone(), 25).unwrap();
+
+        cache.insert("a", &[0; 10]).unwrap();
+        cache.insert("b", &[0; 10]).unwrap();
+        // Touch "a" so "b" becomes the oldest
+        assert!(cache.get("a").is_some());
+        cache.insert("c", &[0; 10]).unwrap();
+
+        assert!(cache.get("b").is_none());
+        assert!(!dir.join("b").exists());
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("c").is_some());
+        assert_eq!(cache.total_bytes(), 20);
+
+        // Re-opening picks up what is on disk
+        drop(cache);
+        let reopened = ImageCache::open(dir.clone(), 25).unwrap();
+        assert_eq!(reopened.total_bytes(), 20);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
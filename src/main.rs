@@ -1,20 +1,28 @@
+mod archive;
 mod auth;
+mod backfill;
 mod cli;
 mod config;
 mod converter;
 mod db;
+mod duplicates;
+mod external_thumbnailer;
+mod image_cache;
 mod mdns;
 mod models;
 mod notify;
+mod phash;
 mod qr;
 mod server;
 mod startup;
 mod sync;
 mod thumbnail;
+mod video;
 mod websocket;
+mod xmp;
 
 use clap::Parser;
-use cli::{Cli, Commands};
+use cli::{Cli, Commands, DuplicatesAction, ThumbnailsAction};
 use config::Config;
 use db::Database;
 use qr::{get_best_host, print_server_info};
@@ -63,6 +71,8 @@ async fn main() -> anyhow::Result<()> {
             // TODO: Implement menu bar
             Ok(())
         }
+        Some(Commands::Duplicates { action }) => run_duplicates(action),
+        Some(Commands::Thumbnails { action }) => run_thumbnails(action).await,
         None => {
             // Default: run server interactively
             run_server(cli.port).await
@@ -103,3 +113,111 @@ async fn run_server(port_override: Option<u16>) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+fn run_duplicates(action: DuplicatesAction) -> anyhow::Result<()> {
+    let config = Config::load()?;
+    let db = Database::new(&config.storage.db_path)?;
+
+    println!("Scanning {} for duplicates...", config.storage.base_path.display());
+    let photos = db.list_all_photos()?;
+    let groups = duplicates::find_groups(&config.storage.base_path, &photos)?;
+
+    match action {
+        DuplicatesAction::List => {
+            for group in &groups {
+                println!("\n{} ({} bytes x {})", group.file_hash, group.size_bytes, group.entries.len());
+                for entry in &group.entries {
+                    let id = entry
+                        .photo_id
+                        .map(|id| format!("#{}", id))
+                        .unwrap_or_else(|| "untracked".to_string());
+                    println!("  {:<10} [{}] {}", id, entry.album, entry.path);
+                }
+            }
+            let reclaimable: i64 = groups.iter().map(|g| g.reclaimable_bytes).sum();
+            println!("\n{} duplicate groups, {} bytes reclaimable", groups.len(), reclaimable);
+        }
+        DuplicatesAction::Merge { hash, keep, dry_run } => {
+            let selected: Vec<_> = groups
+                .iter()
+                .filter(|g| hash.as_deref().is_none_or(|h| g.file_hash == h))
+                .collect();
+            if selected.is_empty() {
+                println!("No matching duplicate groups");
+                return Ok(());
+            }
+
+            let mut total_reclaimed = 0u64;
+            for group in selected {
+                if dry_run {
+                    let keeper = duplicates::pick_keeper(group, keep);
+                    println!("\n{}: keep {}", group.file_hash, group.entries[keeper].path);
+                    for (i, entry) in group.entries.iter().enumerate() {
+                        if i != keeper {
+                            println!("  would remove [{}] {}", entry.album, entry.path);
+                        }
+                    }
+                    continue;
+                }
+
+                let report = duplicates::merge_group(&db, group, keep)?;
+                println!("\n{}: kept {}", report.file_hash, report.kept_path);
+                for path in &report.removed_paths {
+                    println!("  removed {}", path);
+                }
+                if !report.albums_linked.is_empty() {
+                    println!("  linked albums: {}", report.albums_linked.join(", "));
+                }
+                total_reclaimed += report.bytes_reclaimed;
+            }
+            if !dry_run {
+                println!("\n{} bytes reclaimed", total_reclaimed);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_thumbnails(action: ThumbnailsAction) -> anyhow::Result<()> {
+    let config = Config::load()?;
+    let db = Database::new(&config.storage.db_path)?;
+
+    match action {
+        ThumbnailsAction::Rebuild { force, restart } => {
+            let db = std::sync::Arc::new(tokio::sync::Mutex::new(db));
+            let options = backfill::RebuildOptions { force, restart };
+            let guard = backfill::try_start().ok_or_else(|| anyhow::anyhow!("A thumbnail rebuild is already running"))?;
+            let job = backfill::run(guard, db, config, options, |job| {
+                eprint!("\r{}/{} processed, {} regenerated, {} failed", job.processed, job.total, job.succeeded, job.failed);
+            })
+            .await?;
+            eprintln!();
+            println!(
+                "Thumbnail rebuild complete: {} photos checked, {} regenerated, {} failed",
+                job.processed, job.succeeded, job.failed
+            );
+        }
+        ThumbnailsAction::Status => match db.get_job(backfill::JOB_NAME)? {
+            Some(job) => println!(
+                "{}: {}/{} processed, {} regenerated, {} failed (last photo #{}, updated {})",
+                job.status.as_str(), job.processed, job.total, job.succeeded, job.failed, job.last_id, job.updated_at
+            ),
+            None => println!("No thumbnail rebuild has been run"),
+        },
+        ThumbnailsAction::Gc { dry_run } => {
+            let db = std::sync::Arc::new(tokio::sync::Mutex::new(db));
+            let report = backfill::collect_garbage(&db, &config, dry_run).await?;
+            let verb = if dry_run { "would remove" } else { "removed" };
+            for path in &report.removed {
+                println!("  {} {}", verb, path);
+            }
+            println!(
+                "{} files scanned, {} {} ({} bytes)",
+                report.scanned, verb, report.removed.len(), report.bytes_freed
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -1,7 +1,7 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Photo {
     pub id: i64,
     pub filename: String,
@@ -15,6 +15,51 @@ pub struct Photo {
     pub thumbnail_path: Option<String>,  // 缩略图路径
     pub width: Option<i32>,              // 图片宽度
     pub height: Option<i32>,             // 图片高度
+    pub title: Option<String>,           // 标题（XMP dc:title）
+    pub caption: Option<String>,         // 说明（XMP dc:description）
+    pub rating: Option<i32>,             // 星级 1-5（XMP xmp:Rating）
+    pub perceptual_hash: Option<i64>,    // 感知哈希（dHash，按位存储）
+    pub duration_ms: Option<i64>,        // 视频时长（毫秒）
+    pub video_codec: Option<String>,     // 视频编码（avc1、hvc1 等）
+    pub frame_rate: Option<f64>,         // 视频帧率
+    pub latitude: Option<f64>,           // 拍摄地点纬度
+    pub longitude: Option<f64>,          // 拍摄地点经度
+    pub blurhash: Option<String>,        // BlurHash 占位图
+    pub thumbnail_spec: Option<String>,  // 生成缩略图时的配置签名
+    pub is_raw: bool,                    // RAW 文件（DNG、CR2、NEF 等）
+    pub pending_review: bool,            // 访客上传，等待管理员审核
+    pub favorite: bool,                  // 收藏
+}
+
+/// 照片缩略图的一个版本（尺寸 + 格式）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhotoRendition {
+    pub max_size: u32,     // 最长边上限
+    pub format: String,    // jpeg / webp / avif
+    pub path: String,      // 文件路径
+    pub width: i32,        // 实际宽度
+    pub height: i32,       // 实际高度
+    pub size_bytes: i64,   // 文件大小
+}
+
+/// 照片列表排序方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PhotoOrder {
+    /// 按上传时间（默认）
+    #[default]
+    Uploaded,
+    /// 按拍摄时间，没有拍摄时间的按上传时间
+    Taken,
+}
+
+impl PhotoOrder {
+    pub fn order_by(&self) -> &'static str {
+        match self {
+            PhotoOrder::Uploaded => "uploaded_at DESC",
+            PhotoOrder::Taken => "COALESCE(created_at, uploaded_at) DESC",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -99,6 +144,107 @@ impl TaskStatus {
     }
 }
 
+/// 可中断、可恢复的后台任务进度（如缩略图重建）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackgroundJob {
+    pub name: String,
+    pub status: JobStatus,
+    pub last_id: i64,      // 最后处理的照片 ID，恢复时从其后继续
+    pub total: i64,
+    pub processed: i64,
+    pub succeeded: i64,
+    pub failed: i64,
+    pub force: bool,       // 是否强制处理所有照片
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Running => "running",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+        }
+    }
+}
+
+/// 相册；照片通过相册名（`Photo::album`）归属，名称即存储目录下的相对路径，
+/// 嵌套相册用 `/` 分隔，如 `Family/2025/Summer`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Album {
+    pub id: i64,
+    pub name: String,
+    /// 上级相册，顶层相册为 None
+    pub parent_id: Option<i64>,
+    pub description: Option<String>,
+    pub cover_photo_id: Option<i64>,
+    /// 列表中按此值升序排列，相同时照片多的在前
+    pub sort_order: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// 相册及其照片统计（不含待审核的照片）
+#[derive(Debug, Clone, Serialize)]
+pub struct AlbumSummary {
+    #[serde(flatten)]
+    pub album: Album,
+    pub count: i64,
+    pub size_bytes: i64,
+    /// 最新上传的照片，未设置封面时用作封面
+    pub latest_photo_id: Option<i64>,
+}
+
+/// 公开分享链接，指向单张照片或整个相册
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Share {
+    pub id: String,
+    pub photo_id: Option<i64>,
+    pub album: Option<String>,
+    #[serde(skip)]
+    pub password_hash: Option<String>,
+    /// 只在分享路由上隐藏原图和打包下载；未鉴权的 `/api/photos` 仍提供原图
+    pub disable_download: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Share {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|t| t <= Utc::now())
+    }
+}
+
+/// 访客上传链接，只能上传到一个固定相册
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuestLink {
+    pub id: String,
+    pub album: String,
+    pub max_files: Option<i64>,
+    pub max_bytes: Option<i64>,
+    /// 已占用的额度（开始上传时预留，失败时归还）
+    pub files_used: i64,
+    pub bytes_used: i64,
+    /// 上传的照片需审核通过后才出现在相册中
+    pub require_review: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl GuestLink {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|t| t <= Utc::now())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AdminConfig {
     pub id: i64,
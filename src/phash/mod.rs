@@ -0,0 +1,109 @@
+//! Perceptual hashing for near-duplicate detection
+//!
+//! A 64-bit difference hash (dHash) survives re-encoding, resizing and format
+//! changes (HEIC -> JPEG, screenshots re-shared through messengers), so two
+//! photos whose hashes differ in only a few bits are visually near-identical.
+
+use image::imageops::FilterType;
+use image::DynamicImage;
+use std::collections::HashMap;
+
+/// Compute the 64-bit difference hash of an image
+///
+/// The image is reduced to 9x8 grayscale and each bit records whether a
+/// pixel is brighter than its right-hand neighbour.
+pub fn dhash(img: &DynamicImage) -> u64 {
+    let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y)[0];
+            let right = small.get_pixel(x + 1, y)[0];
+            hash <<= 1;
+            if left > right {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+/// Number of differing bits between two hashes
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Group ids whose hashes are within `max_distance` of each other
+///
+/// Grouping is transitive (single linkage): if A~B and B~C, all three end up
+/// in one cluster. Only clusters with at least two members are returned,
+/// largest first.
+pub fn cluster(items: &[(i64, u64)], max_distance: u32) -> Vec<Vec<i64>> {
+    let mut parent: Vec<usize> = (0..items.len()).collect();
+
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    for i in 0..items.len() {
+        for j in (i + 1)..items.len() {
+            if hamming_distance(items[i].1, items[j].1) <= max_distance {
+                let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                if a != b {
+                    parent[b] = a;
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<i64>> = HashMap::new();
+    for (i, (id, _)) in items.iter().enumerate() {
+        let root = find(&mut parent, i);
+        groups.entry(root).or_default().push(*id);
+    }
+
+    let mut clusters: Vec<Vec<i64>> = groups.into_values().filter(|g| g.len() > 1).collect();
+    for c in clusters.iter_mut() {
+        c.sort_unstable();
+    }
+    clusters.sort_by(|a, b| b.len().cmp(&a.len()).then(a[0].cmp(&b[0])));
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgb};
+
+    fn gradient(width: u32, height: u32, invert: bool) -> DynamicImage {
+        let img = ImageBuffer::from_fn(width, height, |x, _| {
+            let v = (x * 255 / width) as u8;
+            let v = if invert { 255 - v } else { v };
+            Rgb([v, v, v])
+        });
+        DynamicImage::ImageRgb8(img)
+    }
+
+    #[test]
+    fn test_dhash_is_scale_invariant() {
+        let a = dhash(&gradient(300, 200, false));
+        let b = dhash(&gradient(1200, 800, false));
+        assert!(hamming_distance(a, b) <= 2);
+
+        let c = dhash(&gradient(300, 200, true));
+        assert!(hamming_distance(a, c) > 32);
+    }
+
+    #[test]
+    fn test_cluster() {
+        let items = [(1, 0b0000), (2, 0b0001), (3, 0b0011), (4, u64::MAX), (5, 0xF0F0)];
+        let clusters = cluster(&items, 1);
+        assert_eq!(clusters, vec![vec![1, 2, 3]]);
+
+        assert!(cluster(&items, 0).is_empty());
+    }
+}
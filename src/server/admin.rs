@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::auth::create_token;
 use crate::server::AppState;
+use crate::websocket::WsEvent;
 
 #[derive(Debug, Deserialize)]
 pub struct LoginRequest {
@@ -50,6 +51,12 @@ pub struct AdminStats {
     pub total_size: i64,
     pub album_count: i32,
     pub disk_available: u64,
+    /// HEIC conversions waiting for a converter slot
+    pub conversions_queued: usize,
+    /// HEIC conversions in progress
+    pub conversions_running: usize,
+    /// 等待审核的访客上传
+    pub pending_review: i64,
 }
 
 /// GET /api/admin/stats - 获取统计信息
@@ -82,11 +89,17 @@ pub async fn get_admin_stats(State(state): State<AppState>) -> Result<impl IntoR
     // 获取磁盘可用空间（简化版）
     let disk_available = 0u64; // 暂不实现
 
+    let (conversions_queued, conversions_running) = crate::converter::queue_status();
+    let pending_review = db.count_pending_photos().unwrap_or(0);
+
     Ok(Json(AdminStats {
         total_photos,
         total_size,
         album_count,
         disk_available,
+        conversions_queued,
+        conversions_running,
+        pending_review,
     }))
 }
 
@@ -156,3 +169,205 @@ pub async fn validate_storage_path(
 
     Err(StatusCode::BAD_REQUEST)
 }
+
+#[derive(Debug, Serialize)]
+pub struct DuplicatesResponse {
+    pub groups: Vec<crate::duplicates::DuplicateGroup>,
+    pub total_groups: usize,
+    pub reclaimable_bytes: i64,
+}
+
+/// 扫描整个图库，返回按 SHA-256 分组的重复文件
+async fn scan_duplicates(state: &AppState) -> Result<Vec<crate::duplicates::DuplicateGroup>, StatusCode> {
+    let photos = {
+        let db = state.db.lock().await;
+        db.list_all_photos().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    };
+
+    let base_path = state.config.storage.base_path.clone();
+    tokio::task::spawn_blocking(move || crate::duplicates::find_groups(&base_path, &photos))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|e| {
+            tracing::error!("Duplicate scan failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// GET /api/admin/duplicates - 列出完全重复的文件组
+pub async fn list_duplicates(State(state): State<AppState>) -> Result<impl IntoResponse, StatusCode> {
+    let groups = scan_duplicates(&state).await?;
+    let reclaimable_bytes = groups.iter().map(|g| g.reclaimable_bytes).sum();
+
+    Ok(Json(DuplicatesResponse {
+        total_groups: groups.len(),
+        reclaimable_bytes,
+        groups,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MergeDuplicatesRequest {
+    /// 只合并指定哈希的文件组
+    pub file_hash: Option<String>,
+    /// 未指定 `file_hash` 时必须显式设为 true 才合并全部文件组
+    #[serde(default)]
+    pub all: bool,
+    /// 要保留的照片 ID，默认保留最早入库的一份
+    pub keep_photo_id: Option<i64>,
+}
+
+/// POST /api/admin/duplicates/merge - 合并重复文件，只保留一份
+///
+/// 每组只在更新数据库时持有锁，文件删除放到阻塞线程中进行。
+pub async fn merge_duplicates(
+    State(state): State<AppState>,
+    Json(req): Json<MergeDuplicatesRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    // 合并全部会删除整个库中的文件，必须明确要求
+    if req.file_hash.is_none() && !req.all {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let groups = scan_duplicates(&state).await?;
+
+    let selected: Vec<_> = match &req.file_hash {
+        Some(hash) => groups.into_iter().filter(|g| &g.file_hash == hash).collect(),
+        None => groups,
+    };
+    if req.file_hash.is_some() && selected.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let mut reports = Vec::with_capacity(selected.len());
+    for group in &selected {
+        let plan = {
+            let db = state.db.lock().await;
+            crate::duplicates::merge_group_rows(&db, group, req.keep_photo_id)
+        };
+        let plan = match plan {
+            Ok(plan) => plan,
+            Err(e) => {
+                tracing::error!("Failed to merge duplicate group {}: {}", group.file_hash, e);
+                continue;
+            }
+        };
+        let report = tokio::task::spawn_blocking(move || crate::duplicates::remove_merged_files(plan))
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        reports.push(report);
+    }
+
+    let bytes_reclaimed: u64 = reports.iter().map(|r| r.bytes_reclaimed).sum();
+    tracing::info!(
+        merged_groups = reports.len(),
+        bytes_reclaimed = bytes_reclaimed,
+        "Duplicate merge completed"
+    );
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "merged_groups": reports.len(),
+        "bytes_reclaimed": bytes_reclaimed,
+        "results": reports,
+    })))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RebuildThumbnailsRequest {
+    /// 重新生成所有缩略图，而不只是缺失或过期的
+    #[serde(default)]
+    pub force: bool,
+    /// 忽略中断的任务，从头开始
+    #[serde(default)]
+    pub restart: bool,
+}
+
+/// 在后台运行缩略图重建任务，并通过 WebSocket 推送进度
+pub(crate) fn spawn_thumbnail_rebuild(
+    state: &AppState,
+    guard: crate::backfill::RunGuard,
+    options: crate::backfill::RebuildOptions,
+) {
+    let db = state.db.clone();
+    let config = state.config.clone();
+    let sender = state.event_sender.clone();
+
+    tokio::spawn(async move {
+        let result = crate::backfill::run(guard, db, config, options, |job| {
+            let _ = sender.send(WsEvent::ThumbnailRebuildProgress {
+                processed: job.processed,
+                total: job.total,
+                regenerated: job.succeeded,
+                failed: job.failed,
+                done: job.status != crate::models::JobStatus::Running,
+            });
+        })
+        .await;
+
+        if let Err(e) = result {
+            tracing::error!("Thumbnail rebuild failed: {:#}", e);
+        }
+    });
+}
+
+/// POST /api/admin/thumbnails/rebuild - 重建缺失或过期的缩略图
+pub async fn rebuild_thumbnails(
+    State(state): State<AppState>,
+    body: Option<Json<RebuildThumbnailsRequest>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let Some(guard) = crate::backfill::try_start() else {
+        return Err(StatusCode::CONFLICT);
+    };
+
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+    spawn_thumbnail_rebuild(&state, guard, crate::backfill::RebuildOptions {
+        force: req.force,
+        restart: req.restart,
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(serde_json::json!({
+        "success": true,
+        "message": "Thumbnail rebuild started"
+    }))))
+}
+
+/// GET /api/admin/thumbnails/rebuild - 查询缩略图重建任务状态
+pub async fn get_thumbnail_rebuild_status(State(state): State<AppState>) -> Result<impl IntoResponse, StatusCode> {
+    let db = state.db.lock().await;
+    let job = db.get_job(crate::backfill::JOB_NAME)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(serde_json::json!({
+        "running": crate::backfill::is_running(),
+        "job": job
+    })))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ThumbnailGcRequest {
+    /// 只统计，不删除
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// POST /api/admin/thumbnails/gc - 删除没有照片引用的缩略图文件
+pub async fn collect_thumbnail_garbage(
+    State(state): State<AppState>,
+    body: Option<Json<ThumbnailGcRequest>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+    let report = crate::backfill::collect_garbage(&state.db, &state.config, req.dry_run)
+        .await
+        .map_err(|e| {
+            tracing::error!("Thumbnail garbage collection failed: {:#}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "dry_run": req.dry_run,
+        "scanned": report.scanned,
+        "removed": report.removed,
+        "bytes_freed": report.bytes_freed
+    })))
+}
@@ -0,0 +1,522 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+use super::AppState;
+use super::photos::{move_photo_files, remove_photo_files, undo_renames};
+use crate::db::{Database, PhotoMove};
+use crate::models::{Album, AlbumSummary, Photo};
+
+#[derive(Debug, Serialize)]
+pub struct AlbumItem {
+    #[serde(flatten)]
+    pub summary: AlbumSummary,
+    /// 封面缩略图；未设置封面时使用最新上传的照片
+    pub cover_url: Option<String>,
+}
+
+impl From<AlbumSummary> for AlbumItem {
+    fn from(summary: AlbumSummary) -> Self {
+        let cover_url = summary
+            .album
+            .cover_photo_id
+            .or(summary.latest_photo_id)
+            .map(|id| format!("/api/photos/{}/thumbnail", id));
+        Self { summary, cover_url }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AlbumNode {
+    #[serde(flatten)]
+    pub item: AlbumItem,
+    /// 包含所有子相册的照片数和大小；通过合并同时属于多个相册的照片会重复计算
+    pub total_count: i64,
+    pub total_size_bytes: i64,
+    pub children: Vec<AlbumNode>,
+}
+
+/// 按 `parent_id` 组装相册树，同级相册保持列表中的顺序
+fn album_tree(items: Vec<AlbumItem>) -> Vec<AlbumNode> {
+    let ids: HashSet<i64> = items.iter().map(|item| item.summary.album.id).collect();
+    let mut children: HashMap<Option<i64>, Vec<AlbumItem>> = HashMap::new();
+    for item in items {
+        // 上级相册不存在时作为顶层相册
+        let parent = item.summary.album.parent_id.filter(|id| ids.contains(id));
+        children.entry(parent).or_default().push(item);
+    }
+    build_nodes(&mut children, None)
+}
+
+fn build_nodes(children: &mut HashMap<Option<i64>, Vec<AlbumItem>>, parent: Option<i64>) -> Vec<AlbumNode> {
+    children
+        .remove(&parent)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|item| {
+            let nodes = build_nodes(children, Some(item.summary.album.id));
+            AlbumNode {
+                total_count: item.summary.count + nodes.iter().map(|node| node.total_count).sum::<i64>(),
+                total_size_bytes: item.summary.size_bytes + nodes.iter().map(|node| node.total_size_bytes).sum::<i64>(),
+                item,
+                children: nodes,
+            }
+        })
+        .collect()
+}
+
+/// 启动时登记存储目录下（包括嵌套的）还没有照片的相册目录
+pub(super) fn register_album_dirs(db: &Database, base_path: &std::path::Path) -> anyhow::Result<()> {
+    let mut pending = vec![(base_path.to_path_buf(), String::new())];
+    while let Some((dir, prefix)) = pending.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        for entry in entries.flatten() {
+            // 不跟随符号链接，避免目录循环
+            if !entry.file_type().is_ok_and(|t| t.is_dir()) {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            let album = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+            if super::is_valid_album_name(&album) {
+                db.ensure_album(&album)?;
+                pending.push((entry.path(), album));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListAlbumsQuery {
+    /// 按层级返回相册树，附带递归统计
+    #[serde(default)]
+    pub tree: bool,
+}
+
+/// GET /api/albums - 获取相册列表（含空相册）
+pub async fn list_albums(
+    State(state): State<AppState>,
+    Query(query): Query<ListAlbumsQuery>,
+) -> Result<Response, StatusCode> {
+    let db = state.db.lock().await;
+    let albums = db.list_albums()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let items: Vec<AlbumItem> = albums.into_iter().map(AlbumItem::from).collect();
+    if query.tree {
+        Ok(Json(album_tree(items)).into_response())
+    } else {
+        Ok(Json(items).into_response())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAlbumRequest {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+/// POST /api/albums - 创建空相册及其目录
+pub async fn create_album(
+    State(state): State<AppState>,
+    Json(req): Json<CreateAlbumRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let name = req.name.trim();
+    if !super::is_valid_album_name(name) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let description = req.description.as_deref().map(str::trim).filter(|d| !d.is_empty());
+
+    tokio::fs::create_dir_all(state.config.storage.base_path.join(name))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let album = state
+        .db
+        .lock()
+        .await
+        .create_album(name, description)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::CONFLICT)?;
+    info!(album = %album.name, "Album created");
+
+    Ok((StatusCode::CREATED, Json(album)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAlbumRequest {
+    /// 新名称；相册目录随之改名
+    pub name: Option<String>,
+    /// 空字符串表示清除
+    pub description: Option<String>,
+    /// 0 表示清除（改用最新上传的照片）
+    pub cover_photo_id: Option<i64>,
+    pub sort_order: Option<i64>,
+}
+
+/// 相册目录前缀，末尾带路径分隔符，避免 `Summer` 匹配到 `Summer 2024`
+fn dir_prefix(dir: &std::path::Path) -> String {
+    format!("{}{}", dir.to_string_lossy(), std::path::MAIN_SEPARATOR)
+}
+
+/// PATCH /api/albums/:id - 修改相册
+///
+/// 改名（可改为其他相册下的路径以移动相册）时先重命名目录，再在一个事务中
+/// 更新该相册及其子相册的名称和照片路径；事务失败则把目录改回原名。
+pub async fn update_album(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Json(req): Json<UpdateAlbumRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let db = state.db.lock().await;
+    let mut album = db.get_album(id)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    if let Some(cover) = req.cover_photo_id {
+        if cover != 0 && !db.photo_in_album(cover, &album.name).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        album.cover_photo_id = (cover != 0).then_some(cover);
+    }
+    if let Some(description) = req.description {
+        let description = description.trim();
+        album.description = (!description.is_empty()).then(|| description.to_string());
+    }
+    if let Some(sort_order) = req.sort_order {
+        album.sort_order = sort_order;
+    }
+
+    let new_name = req.name.as_deref().map(str::trim).filter(|name| *name != album.name);
+    if let Some(new_name) = new_name {
+        // 不能移动到自己的子相册下
+        let into_itself = new_name.strip_prefix(album.name.as_str()).is_some_and(|rest| rest.starts_with('/'));
+        if !super::is_valid_album_name(new_name) || into_itself {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        let base_path = &state.config.storage.base_path;
+        let (old_dir, new_dir) = (base_path.join(&album.name), base_path.join(new_name));
+        let taken = db.get_album_by_name(new_name).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if taken.is_some() || new_dir.exists() {
+            return Err(StatusCode::CONFLICT);
+        }
+
+        // 持有数据库锁，期间完成的上传不会写入旧目录
+        let renamed = old_dir.exists();
+        if let Some(parent) = new_dir.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+        let result = if renamed {
+            tokio::fs::rename(&old_dir, &new_dir).await
+        } else {
+            tokio::fs::create_dir_all(&new_dir).await
+        };
+        result.map_err(|e| {
+            warn!(album = %album.name, error = %e, "Failed to rename album directory");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+        if let Err(e) = db.rename_album(album.id, &album.name, new_name, &dir_prefix(&old_dir), &dir_prefix(&new_dir)) {
+            warn!(album = %album.name, error = %e, "Album rename failed, restoring directory");
+            if renamed && let Err(e) = tokio::fs::rename(&new_dir, &old_dir).await {
+                warn!("Failed to move {} back to {}: {}", new_dir.display(), old_dir.display(), e);
+            }
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        info!(from = %album.name, to = %new_name, "Album renamed");
+        album.name = new_name.to_string();
+    }
+
+    db.update_album(&album).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(album))
+}
+
+/// 把照片移动到另一个相册目录，重名时保留两者；任一照片失败则全部撤销
+async fn relocate_photos(
+    photos: &[Photo],
+    album: &str,
+    dir: &std::path::Path,
+) -> Result<(Vec<PhotoMove>, Vec<(PathBuf, PathBuf)>), StatusCode> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut moves = Vec::with_capacity(photos.len());
+    let mut renames = Vec::new();
+    for photo in photos {
+        match move_photo_files(photo, album, dir, true).await {
+            Ok((update, moved)) => {
+                moves.push(update);
+                renames.extend(moved);
+            }
+            Err(e) => {
+                warn!(photo_id = photo.id, error = %e, "Failed to move photo, restoring files");
+                undo_renames(&renames).await;
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        }
+    }
+    Ok((moves, renames))
+}
+
+/// 照片 ID 及其文件路径，按 ID 排序
+fn photo_paths(photos: &[Photo]) -> Vec<(i64, &str)> {
+    let mut paths: Vec<(i64, &str)> = photos.iter().map(|photo| (photo.id, photo.local_path.as_str())).collect();
+    paths.sort_unstable();
+    paths
+}
+
+/// 在不持有锁移动文件之后重新检查：相册未被改名、删除或新增子相册，
+/// 照片也与移动前相同
+fn album_unchanged(db: &Database, album: &Album, photos: &[Photo]) -> Result<bool, StatusCode> {
+    let current = db.get_album(album.id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if current.is_none_or(|current| current.name != album.name)
+        || db.has_sub_albums(album.id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    {
+        return Ok(false);
+    }
+    let now = db.list_album_photos(&album.name).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(photo_paths(&now) == photo_paths(photos))
+}
+
+/// 删除已清空的相册目录；目录中还有未登记的文件时保留
+async fn remove_album_dir(dir: &std::path::Path) {
+    if let Err(e) = tokio::fs::remove_dir(dir).await
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        warn!("Album directory {} left in place: {}", dir.display(), e);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MergeAlbumRequest {
+    /// 目标相册 ID
+    pub into: i64,
+}
+
+/// POST /api/albums/:id/merge - 把相册合并到另一个相册
+///
+/// 照片文件移动到目标相册目录（重名时自动改名），分享链接和访客上传链接
+/// 改为指向目标相册，随后删除原相册。移动文件时不持有数据库锁；期间任一
+/// 相册发生变化则撤销移动并返回 409。
+pub async fn merge_album(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Json(req): Json<MergeAlbumRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if id == req.into {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let (source, target, photos) = {
+        let db = state.db.lock().await;
+        let source = db.get_album(id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::NOT_FOUND)?;
+        let target = db.get_album(req.into)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::NOT_FOUND)?;
+        // 子相册需先单独移动或合并
+        if db.has_sub_albums(source.id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+            return Err(StatusCode::CONFLICT);
+        }
+        let photos = db.list_album_photos(&source.name)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        (source, target, photos)
+    };
+
+    let base_path = &state.config.storage.base_path;
+    let (moves, renames) = relocate_photos(&photos, &target.name, &base_path.join(&target.name)).await?;
+
+    let db = state.db.lock().await;
+    let mut target = match db.get_album(target.id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+        Some(current) if current.name == target.name && album_unchanged(&db, &source, &photos)? => current,
+        _ => {
+            drop(db);
+            warn!(album = %source.name, "Album changed during merge, restoring files");
+            undo_renames(&renames).await;
+            return Err(StatusCode::CONFLICT);
+        }
+    };
+    if let Err(e) = db.merge_albums(&source.name, &target.name, &moves) {
+        drop(db);
+        warn!(album = %source.name, error = %e, "Album merge failed, restoring files");
+        undo_renames(&renames).await;
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    // 目标相册没有封面时沿用原相册的封面
+    if target.cover_photo_id.is_none() && source.cover_photo_id.is_some() {
+        target.cover_photo_id = source.cover_photo_id;
+        db.update_album(&target).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+    drop(db);
+    remove_album_dir(&base_path.join(&source.name)).await;
+    info!(from = %source.name, into = %target.name, photos = moves.len(), "Album merged");
+
+    Ok(Json(target))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteAlbumQuery {
+    /// 同时删除照片；否则照片移到默认相册
+    #[serde(default)]
+    pub delete_photos: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeleteAlbumResponse {
+    pub success: bool,
+    /// 移到默认相册的照片数
+    pub moved: usize,
+    pub deleted: usize,
+}
+
+/// DELETE /api/albums/:id - 删除相册
+///
+/// 仅通过合并关联到该相册的照片留在各自的主相册中，不受影响。
+/// 相册的分享链接和访客上传链接一并删除。有子相册时返回 409。
+/// 照片移到默认相册时不持有数据库锁，期间相册发生变化同样返回 409。
+pub async fn delete_album(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(query): Query<DeleteAlbumQuery>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let db = state.db.lock().await;
+    let album = db.get_album(id)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if db.has_sub_albums(album.id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+        return Err(StatusCode::CONFLICT);
+    }
+    let photos = db.list_album_photos(&album.name)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let base_path = &state.config.storage.base_path;
+
+    let response = if query.delete_photos {
+        let mut renditions = Vec::with_capacity(photos.len());
+        for photo in &photos {
+            renditions.push(db.list_renditions(photo.id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?);
+        }
+        let ids: Vec<i64> = photos.iter().map(|photo| photo.id).collect();
+        db.delete_album(&album.name, &[], &ids)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        drop(db);
+
+        // 文件删除无法回滚，放在事务提交之后
+        for (photo, renditions) in photos.iter().zip(renditions) {
+            remove_photo_files(photo, renditions).await;
+        }
+        DeleteAlbumResponse { success: true, moved: 0, deleted: ids.len() }
+    } else {
+        let default_album = &state.config.storage.default_album;
+        if !photos.is_empty() && album.name == *default_album {
+            return Err(StatusCode::CONFLICT);
+        }
+        drop(db);
+        let (moves, renames) = relocate_photos(&photos, default_album, &base_path.join(default_album)).await?;
+
+        let db = state.db.lock().await;
+        if !album_unchanged(&db, &album, &photos)? {
+            drop(db);
+            warn!(album = %album.name, "Album changed during delete, restoring files");
+            undo_renames(&renames).await;
+            return Err(StatusCode::CONFLICT);
+        }
+        if let Err(e) = db.delete_album(&album.name, &moves, &[]) {
+            drop(db);
+            warn!(album = %album.name, error = %e, "Album delete failed, restoring files");
+            undo_renames(&renames).await;
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        DeleteAlbumResponse { success: true, moved: moves.len(), deleted: 0 }
+    };
+
+    remove_album_dir(&base_path.join(&album.name)).await;
+    info!(album = %album.name, moved = response.moved, deleted = response.deleted, "Album deleted");
+    Ok(Json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cover_url() {
+        let summary = |cover_photo_id, latest_photo_id| AlbumSummary {
+            album: Album {
+                id: 1,
+                name: "Summer".to_string(),
+                parent_id: None,
+                description: None,
+                cover_photo_id,
+                sort_order: 0,
+                created_at: chrono::Utc::now(),
+            },
+            count: 2,
+            size_bytes: 0,
+            latest_photo_id,
+        };
+        let item = AlbumItem::from(summary(Some(3), Some(7)));
+        assert_eq!(item.cover_url.as_deref(), Some("/api/photos/3/thumbnail"));
+        assert_eq!(AlbumItem::from(summary(None, Some(7))).cover_url.as_deref(), Some("/api/photos/7/thumbnail"));
+        assert!(AlbumItem::from(summary(None, None)).cover_url.is_none());
+
+        let json = serde_json::to_value(item).unwrap();
+        assert_eq!(json["name"], "Summer");
+        assert_eq!(json["count"], 2);
+    }
+
+    #[test]
+    fn test_album_tree() {
+        let item = |id, name: &str, parent_id, count| {
+            AlbumItem::from(AlbumSummary {
+                album: Album {
+                    id,
+                    name: name.to_string(),
+                    parent_id,
+                    description: None,
+                    cover_photo_id: None,
+                    sort_order: 0,
+                    created_at: chrono::Utc::now(),
+                },
+                count,
+                size_bytes: count * 10,
+                latest_photo_id: None,
+            })
+        };
+        let tree = album_tree(vec![
+            item(1, "Family", None, 1),
+            item(4, "Family/2025/Summer", Some(3), 5),
+            item(2, "Trips", None, 2),
+            item(3, "Family/2025", Some(1), 0),
+            item(5, "Orphan/Child", Some(99), 1),
+        ]);
+        let names: Vec<&str> = tree.iter().map(|node| node.item.summary.album.name.as_str()).collect();
+        assert_eq!(names, vec!["Family", "Trips", "Orphan/Child"]);
+        assert_eq!(tree[0].total_count, 6);
+        assert_eq!(tree[0].total_size_bytes, 60);
+        assert_eq!(tree[0].children[0].total_count, 5);
+        assert_eq!(tree[0].children[0].children[0].item.summary.album.name, "Family/2025/Summer");
+    }
+
+    #[test]
+    fn test_album_names() {
+        use crate::server::is_valid_album_name;
+        assert!(is_valid_album_name("Summer"));
+        assert!(is_valid_album_name("Family/2025/Summer"));
+        for invalid in ["", "/Summer", "Family//Summer", "Family/", "../etc", "Family/..", ".hidden", "a\\b"] {
+            assert!(!is_valid_album_name(invalid), "{}", invalid);
+        }
+    }
+}
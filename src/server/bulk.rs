@@ -0,0 +1,243 @@
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+use super::AppState;
+use super::photos::{move_photo_files, remove_photo_files, undo_renames};
+use crate::db::PhotoMove;
+use crate::websocket::WsEvent;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum BulkAction {
+    Delete,
+    /// 移动到其他相册（连同 JPEG 变体和 XMP sidecar）
+    Move { album: String },
+    AddTag { tag: String },
+    RemoveTag { tag: String },
+    /// 收藏；`value: false` 取消收藏
+    Favorite {
+        #[serde(default = "default_true")]
+        value: bool,
+    },
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl BulkAction {
+    fn name(&self) -> &'static str {
+        match self {
+            BulkAction::Delete => "delete",
+            BulkAction::Move { .. } => "move",
+            BulkAction::AddTag { .. } => "add_tag",
+            BulkAction::RemoveTag { .. } => "remove_tag",
+            BulkAction::Favorite { .. } => "favorite",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkRequest {
+    pub ids: Vec<i64>,
+    #[serde(flatten)]
+    pub action: BulkAction,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkItemResult {
+    pub id: i64,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkResponse {
+    pub action: &'static str,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// 与请求中的 ID 顺序一致（重复 ID 只保留一次）
+    pub results: Vec<BulkItemResult>,
+}
+
+/// 每个 ID 的处理结果，`Err` 为失败原因
+type Outcomes = HashMap<i64, Result<(), String>>;
+
+const NOT_FOUND: &str = "photo not found";
+
+/// POST /api/photos/bulk - 批量删除、移动、打标签或收藏
+///
+/// 数据库修改在一个事务中完成；单张照片失败（不存在、目标相册有同名文件等）
+/// 不影响其余照片，结果逐项返回，并发送一条汇总的 WebSocket 事件。
+pub async fn bulk_photos(
+    State(state): State<AppState>,
+    Json(req): Json<BulkRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let mut seen = HashSet::new();
+    let ids: Vec<i64> = req.ids.into_iter().filter(|id| seen.insert(*id)).collect();
+    if ids.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let action = req.action.name();
+
+    let outcomes = match req.action {
+        BulkAction::Delete => bulk_delete(&state, &ids).await?,
+        BulkAction::Move { album } => {
+            let album = album.trim().to_string();
+            if !super::is_valid_album_name(&album) {
+                return Err(StatusCode::BAD_REQUEST);
+            }
+            bulk_move(&state, &ids, &album).await?
+        }
+        BulkAction::AddTag { tag } | BulkAction::RemoveTag { tag } if tag.trim().is_empty() => {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        BulkAction::AddTag { tag } => {
+            let db = state.db.lock().await;
+            found(&ids, db.set_photo_tag(&ids, tag.trim(), true))?
+        }
+        BulkAction::RemoveTag { tag } => {
+            let db = state.db.lock().await;
+            found(&ids, db.set_photo_tag(&ids, tag.trim(), false))?
+        }
+        BulkAction::Favorite { value } => {
+            let db = state.db.lock().await;
+            found(&ids, db.set_favorite(&ids, value))?
+        }
+    };
+
+    let results: Vec<BulkItemResult> = ids
+        .iter()
+        .map(|&id| match outcomes.get(&id) {
+            Some(Ok(())) => BulkItemResult { id, success: true, error: None },
+            Some(Err(e)) => BulkItemResult { id, success: false, error: Some(e.clone()) },
+            None => BulkItemResult { id, success: false, error: Some(NOT_FOUND.to_string()) },
+        })
+        .collect();
+    let (succeeded, failed): (Vec<_>, Vec<_>) = results.iter().partition(|r| r.success);
+    let succeeded: Vec<i64> = succeeded.into_iter().map(|r| r.id).collect();
+    let failed: Vec<i64> = failed.into_iter().map(|r| r.id).collect();
+    info!(action, succeeded = succeeded.len(), failed = failed.len(), "Bulk photo operation");
+
+    let response = BulkResponse {
+        action,
+        succeeded: succeeded.len(),
+        failed: failed.len(),
+        results,
+    };
+    let _ = state.event_sender.send(WsEvent::BulkCompleted {
+        action: action.to_string(),
+        succeeded,
+        failed,
+    });
+    Ok(Json(response))
+}
+
+/// 数据库返回实际存在的 ID，其余视为不存在
+fn found(ids: &[i64], updated: anyhow::Result<Vec<i64>>) -> Result<Outcomes, StatusCode> {
+    let updated: HashSet<i64> = updated
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .collect();
+    Ok(ids
+        .iter()
+        .map(|&id| (id, if updated.contains(&id) { Ok(()) } else { Err(NOT_FOUND.to_string()) }))
+        .collect())
+}
+
+async fn bulk_delete(state: &AppState, ids: &[i64]) -> Result<Outcomes, StatusCode> {
+    let (photos, deleted) = {
+        let db = state.db.lock().await;
+        let mut photos = Vec::new();
+        for photo in db.list_photos_by_ids(ids, true).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+            let renditions = db.list_renditions(photo.id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            photos.push((photo, renditions));
+        }
+        let existing: Vec<i64> = photos.iter().map(|(photo, _)| photo.id).collect();
+        let deleted: HashSet<i64> = db
+            .delete_photos(&existing)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .into_iter()
+            .collect();
+        (photos, deleted)
+    };
+
+    // 文件删除无法回滚，放在事务提交之后
+    let mut outcomes = Outcomes::new();
+    for (photo, renditions) in photos {
+        if deleted.contains(&photo.id) {
+            remove_photo_files(&photo, renditions).await;
+            outcomes.insert(photo.id, Ok(()));
+        }
+    }
+    Ok(outcomes)
+}
+
+async fn bulk_move(state: &AppState, ids: &[i64], album: &str) -> Result<Outcomes, StatusCode> {
+    let photos = state
+        .db
+        .lock()
+        .await
+        .list_photos_by_ids(ids, true)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let album_dir = state.config.storage.base_path.join(album);
+    tokio::fs::create_dir_all(&album_dir)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut outcomes = Outcomes::new();
+    let mut renames: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let mut moves: Vec<PhotoMove> = Vec::new();
+
+    for photo in photos {
+        if photo.album == album {
+            outcomes.insert(photo.id, Ok(()));
+            continue;
+        }
+        match move_photo_files(&photo, album, &album_dir, false).await {
+            Ok((update, moved)) => {
+                moves.push(update);
+                renames.extend(moved);
+                outcomes.insert(photo.id, Ok(()));
+            }
+            Err(e) => {
+                outcomes.insert(photo.id, Err(e));
+            }
+        }
+    }
+
+    if let Err(e) = state.db.lock().await.move_photos(&moves) {
+        // 事务失败时把文件移回原处，保持数据库与磁盘一致
+        warn!("Bulk move failed, restoring files: {}", e);
+        undo_renames(&renames).await;
+        for update in &moves {
+            outcomes.insert(update.id, Err("database update failed".to_string()));
+        }
+    }
+    Ok(outcomes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bulk_request() {
+        let req: BulkRequest = serde_json::from_str(r#"{"ids": [1, 2], "action": "move", "album": "Summer"}"#).unwrap();
+        assert_eq!(req.ids, vec![1, 2]);
+        assert!(matches!(req.action, BulkAction::Move { album } if album == "Summer"));
+
+        let req: BulkRequest = serde_json::from_str(r#"{"ids": [1], "action": "favorite"}"#).unwrap();
+        assert!(matches!(req.action, BulkAction::Favorite { value: true }));
+
+        let req: BulkRequest = serde_json::from_str(r#"{"ids": [1], "action": "remove_tag", "tag": "cats"}"#).unwrap();
+        assert_eq!(req.action.name(), "remove_tag");
+
+        assert!(serde_json::from_str::<BulkRequest>(r#"{"ids": [1], "action": "move"}"#).is_err());
+    }
+}
@@ -0,0 +1,299 @@
+use axum::{
+    Json,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tokio_util::io::ReaderStream;
+
+use crate::archive::ZipWriter;
+use crate::models::Photo;
+use super::AppState;
+use super::photos::PhotoItem;
+
+/// 打包时使用的文件版本
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadVariant {
+    /// 原始文件
+    #[default]
+    Original,
+    /// 有 JPEG 变体时用变体（HEIC、RAW），否则用原始文件
+    Jpeg,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DownloadQuery {
+    #[serde(default)]
+    pub variant: DownloadVariant,
+    /// 是否附带 manifest.json（照片元数据）
+    #[serde(default)]
+    pub manifest: bool,
+    /// 逗号分隔的照片 ID（仅 /api/photos/download）
+    pub ids: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadRequest {
+    pub ids: Vec<i64>,
+    #[serde(default)]
+    pub variant: DownloadVariant,
+    #[serde(default)]
+    pub manifest: bool,
+}
+
+/// manifest.json 中的一项
+#[derive(Debug, Serialize)]
+struct ManifestEntry {
+    /// ZIP 内的路径
+    path: String,
+    variant: DownloadVariant,
+    file_hash: Option<String>,
+    #[serde(flatten)]
+    photo: PhotoItem,
+}
+
+/// 一个待打包的文件
+pub(super) struct ZipItem {
+    name: String,
+    source: std::path::PathBuf,
+    variant: DownloadVariant,
+    photo: Photo,
+}
+
+/// 去掉路径分隔符和 `..`，避免解压到目标目录之外
+fn sanitize_component(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if c == '/' || c == '\\' || c.is_control() { '_' } else { c })
+        .collect();
+    match cleaned.trim() {
+        "" | "." | ".." => "_".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+/// 嵌套相册在 ZIP 中保留目录层级，每一级分别清理
+fn album_dir(album: &str) -> String {
+    album.split('/').map(sanitize_component).collect::<Vec<_>>().join("/")
+}
+
+/// 重名时在扩展名前加 ` (n)`
+fn unique_name(name: String, used: &mut HashSet<String>) -> String {
+    if used.insert(name.to_lowercase()) {
+        return name;
+    }
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() && !stem.ends_with('/') => (stem.to_string(), format!(".{}", ext)),
+        _ => (name.clone(), String::new()),
+    };
+    (1..)
+        .map(|n| format!("{} ({}){}", stem, n, ext))
+        .find(|candidate| used.insert(candidate.to_lowercase()))
+        .unwrap()
+}
+
+/// 决定每张照片的源文件和 ZIP 内路径
+pub(super) fn plan(photos: Vec<Photo>, variant: DownloadVariant, with_album: bool) -> Vec<ZipItem> {
+    let mut used = HashSet::from(["manifest.json".to_string()]);
+    photos
+        .into_iter()
+        .map(|photo| {
+            let original = std::path::PathBuf::from(&photo.local_path);
+            let jpeg = (variant == DownloadVariant::Jpeg && photo.has_jpeg_variant)
+                .then(|| original.with_extension("jpg"))
+                .filter(|path| path.exists());
+
+            let mut filename = sanitize_component(&photo.filename);
+            let (source, variant) = match jpeg {
+                Some(path) => {
+                    let stem = std::path::Path::new(&filename)
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().to_string())
+                        .unwrap_or_else(|| filename.clone());
+                    filename = format!("{}.jpg", stem);
+                    (path, DownloadVariant::Jpeg)
+                }
+                None => (original, DownloadVariant::Original),
+            };
+            let name = if with_album {
+                format!("{}/{}", album_dir(&photo.album), filename)
+            } else {
+                filename
+            };
+
+            ZipItem {
+                name: unique_name(name, &mut used),
+                source,
+                variant,
+                photo,
+            }
+        })
+        .collect()
+}
+
+/// 边读磁盘边生成 ZIP，通过管道直接作为响应体返回
+pub(super) fn stream_zip(items: Vec<ZipItem>, manifest: bool, archive_name: &str) -> Response {
+    let (writer, reader) = tokio::io::duplex(256 * 1024);
+
+    tokio::spawn(async move {
+        let mut zip = ZipWriter::new(writer);
+        let mut entries = Vec::new();
+
+        for item in items {
+            // 打包过程中被删除的文件直接跳过
+            let file = match tokio::fs::File::open(&item.source).await {
+                Ok(file) => file,
+                Err(e) => {
+                    tracing::warn!("Skipping {} in ZIP download: {}", item.source.display(), e);
+                    continue;
+                }
+            };
+            let (size, modified) = match file.metadata().await {
+                Ok(meta) => (meta.len(), meta.modified().map(DateTime::<Local>::from).unwrap_or_else(|_| Local::now())),
+                Err(_) => continue,
+            };
+            // 写入失败通常是客户端断开连接
+            if let Err(e) = zip.add(&item.name, file, size, modified).await {
+                tracing::warn!("ZIP download aborted at {}: {}", item.name, e);
+                return;
+            }
+            entries.push(ManifestEntry {
+                path: item.name,
+                variant: item.variant,
+                file_hash: item.photo.file_hash.clone(),
+                photo: PhotoItem::from(item.photo),
+            });
+        }
+
+        if manifest {
+            let json = serde_json::to_vec_pretty(&entries).unwrap_or_default();
+            if let Err(e) = zip.add("manifest.json", &json[..], json.len() as u64, Local::now()).await {
+                tracing::warn!("ZIP download aborted at manifest: {}", e);
+                return;
+            }
+        }
+        if let Err(e) = zip.finish().await {
+            tracing::warn!("ZIP download aborted: {}", e);
+        }
+    });
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/zip")
+        .header(header::CONTENT_DISPOSITION, super::serve::attachment(archive_name))
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from_stream(ReaderStream::new(reader)))
+        .unwrap()
+}
+
+/// GET /api/albums/:id/download - 打包下载整个相册
+pub async fn download_album(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(query): Query<DownloadQuery>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let (album, photos) = {
+        let db = state.db.lock().await;
+        let album = db.get_album(id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::NOT_FOUND)?
+            .name;
+        let photos = db.list_photos_by_album(&album)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        (album, photos)
+    };
+    if photos.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let items = plan(photos, query.variant, false);
+    Ok(stream_zip(items, query.manifest, &format!("{}.zip", sanitize_component(&album))))
+}
+
+async fn download_ids(state: &AppState, ids: &[i64], variant: DownloadVariant, manifest: bool) -> Result<Response, StatusCode> {
+    if ids.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let photos = {
+        let db = state.db.lock().await;
+        db.list_photos_by_ids(ids, false)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    };
+    if photos.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let items = plan(photos, variant, true);
+    let name = format!("skynas-{}.zip", Local::now().format("%Y%m%d-%H%M%S"));
+    Ok(stream_zip(items, manifest, &name))
+}
+
+/// GET /api/photos/download?ids=1,2,3 - 打包下载选中的照片（按相册分目录）
+pub async fn download_photos(
+    State(state): State<AppState>,
+    Query(query): Query<DownloadQuery>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let ids = query
+        .ids
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .filter(|id| !id.trim().is_empty())
+        .map(|id| id.trim().parse::<i64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    download_ids(&state, &ids, query.variant, query.manifest).await
+}
+
+/// POST /api/photos/download - 同上，ID 列表放在请求体中（适合大量选择）
+pub async fn download_photos_post(
+    State(state): State<AppState>,
+    Json(req): Json<DownloadRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    download_ids(&state, &req.ids, req.variant, req.manifest).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_names() {
+        let photo = |id, album: &str, filename: &str| Photo {
+            id,
+            album: album.to_string(),
+            filename: filename.to_string(),
+            local_path: format!("/nonexistent/{}", filename),
+            has_jpeg_variant: true,
+            ..Default::default()
+        };
+        let items = plan(
+            vec![
+                photo(1, "Summer", "IMG_1.HEIC"),
+                photo(2, "Summer", "img_1.heic"),
+                photo(3, "../etc", "passwd"),
+                photo(4, "Summer", "manifest.json"),
+                photo(5, "Family/2025", "IMG_2.HEIC"),
+            ],
+            DownloadVariant::Jpeg,
+            true,
+        );
+        let names: Vec<&str> = items.iter().map(|i| i.name.as_str()).collect();
+        // Missing variants fall back to the original
+        assert_eq!(names, vec![
+            "Summer/IMG_1.HEIC",
+            "Summer/img_1 (1).heic",
+            "_/etc/passwd",
+            "Summer/manifest.json",
+            "Family/2025/IMG_2.HEIC",
+        ]);
+        assert!(items.iter().all(|i| i.variant == DownloadVariant::Original));
+
+        let items = plan(vec![photo(4, "Summer", "manifest.json")], DownloadVariant::Original, false);
+        assert_eq!(items[0].name, "manifest (1).json");
+    }
+}
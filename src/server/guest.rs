@@ -0,0 +1,343 @@
+use axum::{
+    Json,
+    extract::{Multipart, Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::{AppState, link_secret};
+use super::upload::{InitUploadRequest, UploadChunkQuery, complete_upload, init_upload, upload_chunk};
+use crate::auth::{create_guest_token, verify_share_token};
+use crate::models::GuestLink;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateGuestLinkRequest {
+    pub album: String,
+    /// 有效期（小时），不填则不过期
+    pub expires_in_hours: Option<i64>,
+    pub max_files: Option<i64>,
+    pub max_bytes: Option<i64>,
+    /// 上传的照片需管理员审核后才出现在相册中
+    #[serde(default)]
+    pub require_review: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GuestLinkItem {
+    #[serde(flatten)]
+    pub link: GuestLink,
+    pub expired: bool,
+    /// 完整的上传页面地址（用局域网 IP 拼接，便于手机扫码）
+    pub url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateGuestLinkResponse {
+    #[serde(flatten)]
+    pub item: GuestLinkItem,
+    /// 终端可直接打印的二维码
+    pub qr: String,
+}
+
+fn guest_link_item(state: &AppState, link: GuestLink, secret: &str) -> Result<GuestLinkItem, StatusCode> {
+    let token = create_guest_token(secret, &link).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let host = crate::qr::get_best_host(&state.config.server.host);
+    Ok(GuestLinkItem {
+        expired: link.is_expired(),
+        url: format!("http://{}:{}/g/{}", host, state.config.server.port, token),
+        link,
+    })
+}
+
+/// POST /api/admin/guest-links - 创建访客上传链接
+pub async fn create_guest_link(
+    State(state): State<AppState>,
+    Json(req): Json<CreateGuestLinkRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let album = req.album.trim();
+    let invalid_limit = [req.expires_in_hours, req.max_files, req.max_bytes]
+        .into_iter()
+        .flatten()
+        .any(|v| v <= 0);
+    if !super::is_valid_album_name(album) || invalid_limit {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let now = Utc::now();
+    let link = GuestLink {
+        id: uuid::Uuid::new_v4().to_string(),
+        album: album.to_string(),
+        max_files: req.max_files,
+        max_bytes: req.max_bytes,
+        files_used: 0,
+        bytes_used: 0,
+        require_review: req.require_review,
+        expires_at: req.expires_in_hours.map(|h| now + Duration::hours(h)),
+        created_at: now,
+    };
+
+    let secret = {
+        let db = state.db.lock().await;
+        db.insert_guest_link(&link).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        link_secret(&db)?
+    };
+    let item = guest_link_item(&state, link, &secret)?;
+    let qr = crate::qr::generate_qr_string(&item.url).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    info!(album = %item.link.album, "Guest upload link created");
+
+    Ok((StatusCode::CREATED, Json(CreateGuestLinkResponse { item, qr })))
+}
+
+/// GET /api/admin/guest-links - 列出访客上传链接
+pub async fn list_guest_links(State(state): State<AppState>) -> Result<impl IntoResponse, StatusCode> {
+    let (links, secret) = {
+        let db = state.db.lock().await;
+        let links = db.list_guest_links().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        (links, link_secret(&db)?)
+    };
+    let items = links
+        .into_iter()
+        .map(|link| guest_link_item(&state, link, &secret))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Json(items))
+}
+
+/// DELETE /api/admin/guest-links/:id - 撤销访客上传链接
+pub async fn revoke_guest_link(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let db = state.db.lock().await;
+    if db.delete_guest_link(&id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+/// 解析访客 token：无效、过期或已撤销均返回 404
+async fn authorize(state: &AppState, token: &str) -> Result<GuestLink, StatusCode> {
+    let db = state.db.lock().await;
+    let secret = link_secret(&db)?;
+    let claims = verify_share_token(token, &secret, "guest-upload").map_err(|_| StatusCode::NOT_FOUND)?;
+    db.get_guest_link(&claims.sid)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .filter(|link| !link.is_expired())
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// 上传必须是通过同一链接发起的；返回预留的字节数
+async fn reserved_size(state: &AppState, link: &GuestLink, upload_id: &str) -> Result<i64, StatusCode> {
+    let db = state.db.lock().await;
+    match db.get_guest_upload(upload_id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+        Some((link_id, size)) if link_id == link.id => Ok(size),
+        _ => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+/// 已收到的分片总大小
+async fn received_bytes(state: &AppState, upload_id: &str) -> Result<i64, StatusCode> {
+    let session = state
+        .db
+        .lock()
+        .await
+        .get_upload_session(upload_id)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let mut total = 0;
+    let mut entries = match tokio::fs::read_dir(&session.temp_path).await {
+        Ok(entries) => entries,
+        Err(_) => return Ok(0),
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if let Ok(meta) = entry.metadata().await {
+            total += meta.len() as i64;
+        }
+    }
+    Ok(total)
+}
+
+/// 放弃上传：删除临时文件并归还额度
+async fn abort_upload(state: &AppState, upload_id: &str) {
+    let db = state.db.lock().await;
+    if let Ok(Some(session)) = db.get_upload_session(upload_id) {
+        let _ = std::fs::remove_dir_all(&session.temp_path);
+    }
+    let _ = db.delete_upload_session(upload_id);
+    if let Err(e) = db.finish_guest_upload(upload_id, true) {
+        warn!(upload_id = %upload_id, error = %e, "Failed to release guest upload quota");
+    }
+    drop(db);
+    state.active_uploads.lock().await.remove(upload_id);
+}
+
+/// GET /g/:token - 访客上传页面
+pub async fn guest_page() -> impl IntoResponse {
+    Html(include_str!("static/guest.html"))
+}
+
+#[derive(Debug, Serialize)]
+pub struct GuestInfo {
+    pub album: String,
+    pub expires_at: Option<String>,
+    /// 剩余可上传的文件数/字节数，不限制时为 null
+    pub remaining_files: Option<i64>,
+    pub remaining_bytes: Option<i64>,
+    /// 上传后需审核才会出现在相册中
+    pub require_review: bool,
+    pub chunk_size: usize,
+}
+
+/// GET /g/:token/info - 目标相册与剩余额度
+pub async fn guest_info(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let link = authorize(&state, &token).await?;
+    Ok(Json(GuestInfo {
+        album: link.album,
+        expires_at: link.expires_at.map(|t| t.to_rfc3339()),
+        remaining_files: link.max_files.map(|max| (max - link.files_used).max(0)),
+        remaining_bytes: link.max_bytes.map(|max| (max - link.bytes_used).max(0)),
+        require_review: link.require_review,
+        chunk_size: state.config.server.chunk_size,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GuestInitRequest {
+    pub filename: String,
+    pub total_size: i64,
+    pub total_chunks: i32,
+}
+
+/// 相册目录中已有该文件（或会被它的 XMP sidecar、JPEG 变体覆盖的文件）
+fn name_taken(dir: &std::path::Path, name: &str) -> bool {
+    let path = dir.join(name);
+    let variant_taken = (crate::converter::is_heif(&path) || crate::converter::raw::is_raw(&path))
+        && path.with_extension("jpg").exists();
+    path.exists() || crate::xmp::sidecar_path(&path).exists() || variant_taken
+}
+
+/// 访客上传绝不覆盖已有文件：重名时改用 `IMG_0001 (1).jpg` 这样的文件名
+fn free_filename(dir: &std::path::Path, name: &str) -> String {
+    let mut candidate = name.to_string();
+    let mut n = 0;
+    while name_taken(dir, &candidate) {
+        n += 1;
+        candidate = super::photos::numbered_filename(name, n);
+    }
+    candidate
+}
+
+/// POST /g/:token/upload/init - 相册固定为链接指定的相册，并预留额度
+pub async fn guest_init_upload(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    Json(req): Json<GuestInitRequest>,
+) -> Result<Response, StatusCode> {
+    let link = authorize(&state, &token).await?;
+    // 只保留文件名部分，防止写到相册目录之外
+    let filename = std::path::Path::new(req.filename.trim())
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .filter(|name| !name.starts_with('.'))
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    if req.total_size <= 0 || req.total_chunks <= 0 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let filename = free_filename(&state.config.storage.base_path.join(&link.album), &filename);
+
+    let Json(response) = init_upload(
+        State(state.clone()),
+        Json(InitUploadRequest {
+            filename,
+            album: link.album.clone(),
+            total_size: req.total_size,
+            total_chunks: req.total_chunks,
+        }),
+    )
+    .await?;
+
+    let reserved = state
+        .db
+        .lock()
+        .await
+        .reserve_guest_upload(&link.id, &response.upload_id, req.total_size)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !reserved {
+        abort_upload(&state, &response.upload_id).await;
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+    Ok(Json(response).into_response())
+}
+
+/// POST /g/:token/upload/chunk - 同 `/api/upload/chunked/chunk`，超出预留大小时终止上传
+pub async fn guest_upload_chunk(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    Query(query): Query<UploadChunkQuery>,
+    multipart: Multipart,
+) -> Result<Response, StatusCode> {
+    let link = authorize(&state, &token).await?;
+    let reserved = reserved_size(&state, &link, &query.upload_id).await?;
+    let upload_id = query.upload_id.clone();
+
+    let response = upload_chunk(State(state.clone()), Query(query), multipart)
+        .await?
+        .into_response();
+    if received_bytes(&state, &upload_id).await? > reserved {
+        warn!(upload_id = %upload_id, "Guest upload exceeded its declared size");
+        abort_upload(&state, &upload_id).await;
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+    Ok(response)
+}
+
+/// POST /g/:token/upload/complete/:upload_id
+pub async fn guest_complete_upload(
+    State(state): State<AppState>,
+    Path((token, upload_id)): Path<(String, String)>,
+) -> Result<Response, StatusCode> {
+    let link = authorize(&state, &token).await?;
+    let reserved = reserved_size(&state, &link, &upload_id).await?;
+    if received_bytes(&state, &upload_id).await? > reserved {
+        abort_upload(&state, &upload_id).await;
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    let response = complete_upload(State(state.clone()), Path(upload_id.clone()))
+        .await
+        .into_response();
+    let succeeded = response.status().is_success();
+    if let Err(e) = state.db.lock().await.finish_guest_upload(&upload_id, !succeeded) {
+        warn!(upload_id = %upload_id, error = %e, "Failed to update guest upload quota");
+    }
+    if succeeded {
+        info!(upload_id = %upload_id, album = %link.album, "Guest upload completed");
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_free_filename_never_reuses_existing_names() {
+        let dir = std::env::temp_dir().join(format!("skynas-guest-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("IMG_0001.JPG"), b"existing").unwrap();
+        std::fs::write(dir.join("IMG_0001 (1).JPG"), b"existing").unwrap();
+        std::fs::write(dir.join("IMG_0002.jpg"), b"camera jpeg").unwrap();
+
+        assert_eq!(free_filename(&dir, "IMG_0001.JPG"), "IMG_0001 (2).JPG");
+        assert_eq!(free_filename(&dir, "IMG_0002.HEIC"), "IMG_0002 (1).HEIC");
+        assert_eq!(free_filename(&dir, "IMG_0003.jpg"), "IMG_0003.jpg");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,12 +1,13 @@
 use crate::config::Config;
 use crate::db::Database;
+use crate::image_cache::ImageCache;
 use crate::websocket::{EventSender, WsEvent, create_event_channel, ws_handler};
 use axum::{
     Router,
     extract::{DefaultBodyLimit, Multipart, State},
     http::StatusCode,
     response::{Html, IntoResponse, Json},
-    routing::{delete, get, post},
+    routing::{delete, get, patch, post},
 };
 use sha2::Digest;
 use std::collections::HashMap;
@@ -29,10 +30,42 @@ use uploads::{
 };
 
 mod photos;
-use photos::{delete_photo, get_image, get_photo, get_thumbnail, list_albums, list_photos};
+mod serve;
+use photos::{
+    delete_photo, get_image, get_photo, get_thumbnail, list_near_duplicates, list_photos,
+    list_tags, update_photo_metadata,
+};
+
+mod albums;
+use albums::{create_album, delete_album, list_albums, merge_album, update_album};
+
+mod download;
+use download::{download_album, download_photos, download_photos_post};
+
+mod share;
+use share::{
+    create_share, list_shares, revoke_share, share_download, share_image, share_info, share_page,
+    share_thumbnail, unlock_share,
+};
+
+mod guest;
+use guest::{
+    create_guest_link, guest_complete_upload, guest_info, guest_init_upload, guest_page,
+    guest_upload_chunk, list_guest_links, revoke_guest_link,
+};
+
+mod review;
+use review::{approve_pending, list_pending, reject_pending};
+
+mod bulk;
+use bulk::bulk_photos;
 
 mod admin;
-use admin::{admin_login, get_admin_stats, get_config, update_config, validate_storage_path};
+use admin::{
+    admin_login, collect_thumbnail_garbage, get_admin_stats, get_config,
+    get_thumbnail_rebuild_status, list_duplicates, merge_duplicates, rebuild_thumbnails,
+    update_config, validate_storage_path,
+};
 use crate::auth::require_admin_auth;
 use axum::middleware;
 
@@ -42,18 +75,40 @@ pub struct AppState {
     pub db: Arc<Mutex<Database>>,
     pub event_sender: EventSender,
     pub active_uploads: Arc<Mutex<HashMap<String, CancellationToken>>>,
+    pub image_cache: Arc<ImageCache>,
 }
 
 pub async fn run_server(config: Config, db: Database) -> anyhow::Result<()> {
     let (event_sender, _) = create_event_channel();
 
+    if let Err(e) = albums::register_album_dirs(&db, &config.storage.base_path) {
+        warn!(error = %e, "Failed to register album directories");
+    }
+
+    let image_cache = ImageCache::open(
+        crate::thumbnail::ThumbnailGenerator::thumbnail_dir(&config).join("cache"),
+        config.thumbnails.cache_max_bytes,
+    )?;
+
     let state = AppState {
         config: config.clone(),
         db: Arc::new(Mutex::new(db)),
         event_sender,
         active_uploads: Arc::new(Mutex::new(HashMap::new())),
+        image_cache: Arc::new(image_cache),
     };
 
+    // Resume a thumbnail rebuild that was interrupted by a restart
+    let interrupted = state
+        .db
+        .lock()
+        .await
+        .get_job(crate::backfill::JOB_NAME)?
+        .is_some_and(|job| job.status == crate::models::JobStatus::Running);
+    if interrupted && let Some(guard) = crate::backfill::try_start() {
+        admin::spawn_thumbnail_rebuild(&state, guard, crate::backfill::RebuildOptions::default());
+    }
+
     let app = Router::new()
         .route("/", get(index_handler))
         .route("/ws", get(ws_handler))
@@ -70,7 +125,11 @@ pub async fn run_server(config: Config, db: Database) -> anyhow::Result<()> {
         )
         .route("/api/health", get(health_handler))
         .route("/api/photos", get(list_photos))
+        .route("/api/photos/near-duplicates", get(list_near_duplicates))
+        .route("/api/photos/download", get(download_photos).post(download_photos_post))
         .route("/api/albums", get(list_albums))
+        .route("/api/tags", get(list_tags))
+        .route("/api/albums/:id/download", get(download_album))
         .route("/api/photos/:id", get(get_photo))
         .route("/api/photos/:id/thumbnail", get(get_thumbnail))
         .route("/api/photos/:id/image", get(get_image))
@@ -78,6 +137,19 @@ pub async fn run_server(config: Config, db: Database) -> anyhow::Result<()> {
         .route("/api/uploads/:id/cancel", post(cancel_upload))
         .route("/api/uploads/cancel-all", post(cancel_all_uploads))
         .route("/api/uploads/cleanup-incomplete", delete(cleanup_incomplete_uploads))
+        // Public share links
+        .route("/s/:token", get(share_page))
+        .route("/s/:token/info", get(share_info))
+        .route("/s/:token/unlock", post(unlock_share))
+        .route("/s/:token/download", get(share_download))
+        .route("/s/:token/photos/:id/thumbnail", get(share_thumbnail))
+        .route("/s/:token/photos/:id/image", get(share_image))
+        // Guest upload links
+        .route("/g/:token", get(guest_page))
+        .route("/g/:token/info", get(guest_info))
+        .route("/g/:token/upload/init", post(guest_init_upload))
+        .route("/g/:token/upload/chunk", post(guest_upload_chunk))
+        .route("/g/:token/upload/complete/:upload_id", post(guest_complete_upload))
         // Admin routes
         .route("/api/admin/login", post(admin_login))
         .route(
@@ -101,9 +173,113 @@ pub async fn run_server(config: Config, db: Database) -> anyhow::Result<()> {
                 require_admin_auth,
             )),
         )
+        .route(
+            "/api/admin/duplicates",
+            get(list_duplicates).layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_admin_auth,
+            )),
+        )
+        .route(
+            "/api/admin/duplicates/merge",
+            post(merge_duplicates).layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_admin_auth,
+            )),
+        )
+        .route(
+            "/api/admin/thumbnails/rebuild",
+            get(get_thumbnail_rebuild_status).post(rebuild_thumbnails).layer(
+                middleware::from_fn_with_state(state.clone(), require_admin_auth),
+            ),
+        )
+        .route(
+            "/api/admin/thumbnails/gc",
+            post(collect_thumbnail_garbage).layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_admin_auth,
+            )),
+        )
+        .route(
+            "/api/admin/shares",
+            get(list_shares).post(create_share).layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_admin_auth,
+            )),
+        )
+        .route(
+            "/api/admin/shares/:id",
+            delete(revoke_share).layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_admin_auth,
+            )),
+        )
+        .route(
+            "/api/admin/guest-links",
+            get(list_guest_links).post(create_guest_link).layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_admin_auth,
+            )),
+        )
+        .route(
+            "/api/admin/guest-links/:id",
+            delete(revoke_guest_link).layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_admin_auth,
+            )),
+        )
+        .route(
+            "/api/admin/review",
+            get(list_pending).layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_admin_auth,
+            )),
+        )
+        .route(
+            "/api/admin/review/approve",
+            post(approve_pending).layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_admin_auth,
+            )),
+        )
+        .route(
+            "/api/admin/review/reject",
+            post(reject_pending).layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_admin_auth,
+            )),
+        )
+        .route(
+            "/api/albums",
+            post(create_album).layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_admin_auth,
+            )),
+        )
+        .route(
+            "/api/albums/:id",
+            patch(update_album).delete(delete_album).layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_admin_auth,
+            )),
+        )
+        .route(
+            "/api/albums/:id/merge",
+            post(merge_album).layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_admin_auth,
+            )),
+        )
+        .route(
+            "/api/photos/bulk",
+            post(bulk_photos).layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_admin_auth,
+            )),
+        )
         .route(
             "/api/photos/:id",
-            delete(delete_photo).layer(middleware::from_fn_with_state(
+            delete(delete_photo).patch(update_photo_metadata).layer(middleware::from_fn_with_state(
                 state.clone(),
                 require_admin_auth,
             )),
@@ -123,6 +299,20 @@ pub async fn run_server(config: Config, db: Database) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// 相册名对应存储目录下的目录，嵌套相册用 `/` 分隔（如 `Family/2025/Summer`）；
+/// 每一级都不能为空、包含 `\` 或以 `.` 开头，保证不会跳出存储目录
+pub(crate) fn is_valid_album_name(album: &str) -> bool {
+    album
+        .split('/')
+        .all(|segment| !segment.is_empty() && !segment.contains('\\') && !segment.starts_with('.'))
+}
+
+/// 签发分享链接、分享访问凭证和访客链接所用的 secret（每个安装随机生成）
+pub(crate) fn link_secret(db: &Database) -> Result<String, StatusCode> {
+    db.get_or_create_link_secret("change-me-in-production")
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
 async fn index_handler() -> impl IntoResponse {
     let html = include_str!("static/index.html");
     Html(html)
@@ -236,6 +426,13 @@ async fn upload_handler(
         return Err(StatusCode::REQUEST_TIMEOUT);
     }
 
+    if !is_valid_album_name(&album) {
+        let _ = tokio::fs::remove_dir_all(&temp_dir).await;
+        state.active_uploads.lock().await.remove(&upload_id);
+        warn!(upload_id = %upload_id, album = %album, "Invalid album name");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
     if let Some((filename, temp_path, size)) = file_info {
         let size_i64 = size as i64;
 
@@ -354,19 +551,34 @@ async fn upload_handler(
 
         debug!(upload_id = %upload_id, hash = %file_hash, "File hash calculated");
 
+        // Import an existing XMP sidecar (e.g. synced from a desktop editor)
+        let sidecar = import_sidecar(&file_path).await;
+
+        // Read duration, resolution, codec etc. from video containers
+        let video = probe_video(&file_path).await.unwrap_or_default();
+
         let photo = crate::models::Photo {
             id: 0,
             filename: filename.clone(),
             album: album.clone(),
-            file_hash: Some(file_hash),
+            file_hash: Some(file_hash.clone()),
             size_bytes: size_i64,
-            created_at: None,
+            created_at: video.creation_time,
             uploaded_at: chrono::Utc::now(),
             local_path: file_path.to_string_lossy().to_string(),
             has_jpeg_variant: false,
-            thumbnail_path: None,
-            width: None,
-            height: None,
+            is_raw: crate::converter::raw::is_raw(&file_path),
+            title: sidecar.title,
+            caption: sidecar.caption,
+            rating: sidecar.rating,
+            width: video.width,
+            height: video.height,
+            duration_ms: video.duration_ms,
+            video_codec: video.codec,
+            frame_rate: video.frame_rate,
+            latitude: video.latitude,
+            longitude: video.longitude,
+            ..Default::default()
         };
 
         debug!(upload_id = %upload_id, "Saving photo to database");
@@ -390,47 +602,10 @@ async fn upload_handler(
             }
         };
 
-        // Generate thumbnail asynchronously (only for image files)
-        let is_image = filename.to_lowercase().ends_with(".jpg")
-            || filename.to_lowercase().ends_with(".jpeg")
-            || filename.to_lowercase().ends_with(".png")
-            || filename.to_lowercase().ends_with(".heic")
-            || filename.to_lowercase().ends_with(".webp");
-
-        if let (true, Some(photo_id)) = (is_image, photo_id_res) {
-            let file_path_clone = file_path.clone();
-            let config_clone = state.config.clone();
-            let db_arc = state.db.clone();
-
-            tokio::spawn(async move {
-                match crate::thumbnail::ThumbnailGenerator::generate(
-                    &file_path_clone,
-                    &config_clone,
-                    300, // thumbnail max edge size
-                ).await {
-                    Ok((thumb_path, width, height)) => {
-                        // Update photo record in database
-                        let db = db_arc.lock().await;
-                        if let Err(e) = db.conn.execute(
-                            "UPDATE photos SET thumbnail_path = ?1, width = ?2, height = ?3 WHERE id = ?4",
-                            rusqlite::params![
-                                thumb_path.to_string_lossy().to_string(),
-                                width,
-                                height,
-                                photo_id
-                            ],
-                        ) {
-                            tracing::error!("Failed to update photo thumbnail info: {}", e);
-                        } else {
-                            tracing::info!("Generated thumbnail for photo {}: {}x{}", photo_id, width, height);
-                        }
-                    }
-                    Err(e) => {
-                        tracing::error!("Failed to generate thumbnail: {}", e);
-                        // Thumbnail generation failure should not affect main flow
-                    }
-                }
-            });
+        // Generate thumbnail and JPEG variant asynchronously
+        if let Some(photo_id) = photo_id_res {
+            spawn_thumbnail_task(&state, photo_id, &file_hash, &file_path);
+            spawn_conversion_task(&state, photo_id, &upload_id, &filename, &file_path);
         }
 
         // Send progress event - 100%
@@ -487,3 +662,159 @@ async fn upload_handler(
         Err(StatusCode::BAD_REQUEST)
     }
 }
+
+/// Parse container metadata if the file is an MP4/MOV video
+///
+/// Parsing failures are logged and treated as "no metadata".
+pub(crate) async fn probe_video(
+    file_path: &std::path::Path,
+) -> Option<crate::video::VideoMetadata> {
+    let is_video = file_path
+        .file_name()
+        .map(|n| crate::video::is_video_file(&n.to_string_lossy()))
+        .unwrap_or(false);
+    if !is_video {
+        return None;
+    }
+
+    let path = file_path.to_path_buf();
+    match tokio::task::spawn_blocking(move || crate::video::probe(&path)).await {
+        Ok(Ok(meta)) => {
+            debug!(
+                path = %file_path.display(),
+                duration_ms = ?meta.duration_ms,
+                codec = ?meta.codec,
+                "Video metadata parsed"
+            );
+            Some(meta)
+        }
+        Ok(Err(e)) => {
+            warn!(path = %file_path.display(), error = %e, "Failed to parse video metadata");
+            None
+        }
+        Err(e) => {
+            error!(error = %e, "Video metadata task failed");
+            None
+        }
+    }
+}
+
+/// Read an existing XMP sidecar of a freshly ingested file
+///
+/// Missing or unreadable sidecars are logged and treated as empty metadata.
+pub(crate) async fn import_sidecar(file_path: &std::path::Path) -> crate::xmp::XmpMetadata {
+    let path = file_path.to_path_buf();
+    match tokio::task::spawn_blocking(move || crate::xmp::read_sidecar(&path)).await {
+        Ok(Ok(meta)) => meta.unwrap_or_default(),
+        Ok(Err(e)) => {
+            warn!(path = %file_path.display(), error = %e, "Failed to import XMP sidecar");
+            Default::default()
+        }
+        Err(e) => {
+            error!(error = %e, "XMP sidecar task failed");
+            Default::default()
+        }
+    }
+}
+
+/// Convert a freshly ingested HEIC or RAW photo to its JPEG variant in the
+/// background
+///
+/// Conversions share a bounded pool (`heic_converter.max_concurrent`) and
+/// report queue position and outcome over WebSocket. A failure only means
+/// the photo has no variant; the upload itself has already succeeded.
+pub(crate) fn spawn_conversion_task(
+    state: &AppState,
+    photo_id: i64,
+    upload_id: &str,
+    filename: &str,
+    file_path: &std::path::Path,
+) {
+    let converter = crate::converter::HeicConverter::new(state.config.heic_converter.clone());
+    if !converter.applies_to(file_path) {
+        return;
+    }
+
+    let file_path = file_path.to_path_buf();
+    let upload_id = upload_id.to_string();
+    let filename = filename.to_string();
+    let sender = state.event_sender.clone();
+    let db_arc = state.db.clone();
+
+    tokio::spawn(async move {
+        let result = converter
+            .convert_queued(
+                file_path,
+                |position| {
+                    tracing::debug!(upload_id = %upload_id, position, "JPEG conversion queued");
+                    let _ = sender.send(WsEvent::HeicQueued {
+                        upload_id: upload_id.clone(),
+                        filename: filename.clone(),
+                        position,
+                    });
+                },
+                || {
+                    let _ = sender.send(WsEvent::HeicConverting {
+                        upload_id: upload_id.clone(),
+                        filename: filename.clone(),
+                    });
+                },
+            )
+            .await;
+
+        let (converted, error) = match result {
+            Ok(Some(jpeg_path)) => {
+                if let Err(e) = db_arc.lock().await.set_jpeg_variant(photo_id, true) {
+                    tracing::error!("Failed to record JPEG variant for photo {}: {}", photo_id, e);
+                }
+                tracing::info!(upload_id = %upload_id, converted = %jpeg_path.display(), "Converted to JPEG");
+                (jpeg_path.to_string_lossy().to_string(), None)
+            }
+            Ok(None) => (String::new(), None),
+            Err(e) => {
+                tracing::warn!(upload_id = %upload_id, filename = %filename, error = %format!("{:#}", e), "JPEG conversion failed");
+                (String::new(), Some(format!("{:#}", e)))
+            }
+        };
+
+        let _ = sender.send(WsEvent::HeicConverted {
+            upload_id,
+            original: filename,
+            success: !converted.is_empty(),
+            converted,
+            error,
+        });
+    });
+}
+
+/// Generate the thumbnail (and perceptual hash) for a freshly ingested photo
+/// in the background; failures are logged and do not affect the upload
+pub(crate) fn spawn_thumbnail_task(
+    state: &AppState,
+    photo_id: i64,
+    file_hash: &str,
+    file_path: &std::path::Path,
+) {
+    let file_path = file_path.to_path_buf();
+    let file_hash = file_hash.to_string();
+    let config = state.config.clone();
+    let db_arc = state.db.clone();
+
+    tokio::spawn(async move {
+        match crate::thumbnail::ThumbnailGenerator::generate(&file_path, &file_hash, &config).await {
+            Ok(thumb) => {
+                // Update photo record in database
+                let db = db_arc.lock().await;
+                if let Err(e) = db.update_thumbnail_info(photo_id, &thumb, None) {
+                    tracing::error!("Failed to update photo thumbnail info: {}", e);
+                } else {
+                    tracing::info!("Generated thumbnail for photo {}: {}x{}", photo_id, thumb.width, thumb.height);
+                }
+            }
+            Err(e) => {
+                tracing::error!("Failed to generate thumbnail: {}", e);
+                // Thumbnail generation failure should not affect main flow
+            }
+        }
+    });
+}
@@ -5,8 +5,8 @@ use axum::{
     Json,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 
+use crate::models::PhotoOrder;
 use crate::server::AppState;
 
 #[derive(Debug, Deserialize)]
@@ -14,6 +14,12 @@ pub struct ListPhotosQuery {
     pub page: Option<i32>,
     pub limit: Option<i32>,
     pub album: Option<String>,
+    /// 同时列出子相册中的照片
+    #[serde(default)]
+    pub include_sub_albums: bool,
+    /// uploaded（默认）或 taken
+    #[serde(default)]
+    pub sort: PhotoOrder,
 }
 
 #[derive(Debug, Serialize)]
@@ -34,11 +40,32 @@ pub struct PhotoItem {
     pub height: Option<i32>,
     pub uploaded_at: String,
     pub thumbnail_url: Option<String>,
+    pub title: Option<String>,
+    pub caption: Option<String>,
+    pub rating: Option<i32>,
+    /// 拍摄时间
+    pub taken_at: Option<String>,
+    pub duration_ms: Option<i64>,
+    pub video_codec: Option<String>,
+    pub frame_rate: Option<f64>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// 缩略图加载前显示的 BlurHash 占位图
+    pub blurhash: Option<String>,
+    /// 宽高比（宽/高），用于提前布局
+    pub aspect_ratio: Option<f64>,
+    /// RAW 文件（显示的是内嵌预览图）
+    pub is_raw: bool,
+    pub favorite: bool,
 }
 
 impl From<crate::models::Photo> for PhotoItem {
     fn from(photo: crate::models::Photo) -> Self {
         let thumbnail_url = photo.thumbnail_path.as_ref().map(|_| format!("/api/photos/{}/thumbnail", photo.id));
+        let aspect_ratio = match (photo.width, photo.height) {
+            (Some(w), Some(h)) if w > 0 && h > 0 => Some((w as f64 / h as f64 * 10000.0).round() / 10000.0),
+            _ => None,
+        };
         Self {
             id: photo.id,
             filename: photo.filename,
@@ -48,6 +75,19 @@ impl From<crate::models::Photo> for PhotoItem {
             height: photo.height,
             uploaded_at: photo.uploaded_at.to_rfc3339(),
             thumbnail_url,
+            title: photo.title,
+            caption: photo.caption,
+            rating: photo.rating,
+            taken_at: photo.created_at.map(|t| t.to_rfc3339()),
+            duration_ms: photo.duration_ms,
+            video_codec: photo.video_codec,
+            frame_rate: photo.frame_rate,
+            latitude: photo.latitude,
+            longitude: photo.longitude,
+            blurhash: photo.blurhash,
+            is_raw: photo.is_raw,
+            favorite: photo.favorite,
+            aspect_ratio,
         }
     }
 }
@@ -64,7 +104,7 @@ pub async fn list_photos(
     let db = state.db.lock().await;
 
     let album_ref = query.album.as_deref();
-    let (photos, total) = db.list_photos(album_ref, limit, offset)
+    let (photos, total) = db.list_photos(album_ref, query.include_sub_albums, query.sort, limit, offset)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let photo_items: Vec<PhotoItem> = photos.into_iter().map(PhotoItem::from).collect();
@@ -85,160 +125,571 @@ pub async fn get_photo(
     let db = state.db.lock().await;
 
     let photo = db.get_photo(id)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let tags = db.list_photo_tags(id)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    match photo {
-        Some(p) => Ok(Json(PhotoItem::from(p))),
-        None => Err(StatusCode::NOT_FOUND),
-    }
+    Ok(Json(PhotoDetail { photo: PhotoItem::from(photo), tags }))
 }
 
-/// GET /api/photos/:id/thumbnail - 获取缩略图
-pub async fn get_thumbnail(
+#[derive(Debug, Serialize)]
+pub struct PhotoDetail {
+    #[serde(flatten)]
+    pub photo: PhotoItem,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagItem {
+    pub name: String,
+    pub count: i64,
+}
+
+/// GET /api/tags - 所有标签及使用次数
+pub async fn list_tags(State(state): State<AppState>) -> Result<impl IntoResponse, StatusCode> {
+    let db = state.db.lock().await;
+    let tags = db.list_tags()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(tags.into_iter().map(|(name, count)| TagItem { name, count }).collect::<Vec<_>>()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdatePhotoMetadataRequest {
+    /// 标题，空字符串表示清除
+    pub title: Option<String>,
+    /// 说明，空字符串表示清除
+    pub caption: Option<String>,
+    /// 星级 1-5，0 表示清除
+    pub rating: Option<i32>,
+}
+
+/// PATCH /api/photos/:id - 编辑标题、说明和星级，并写入 XMP sidecar
+pub async fn update_photo_metadata(
     State(state): State<AppState>,
     Path(id): Path<i64>,
+    Json(req): Json<UpdatePhotoMetadataRequest>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    use axum::response::Response;
-    use axum::body::Body;
-    use axum::http::header;
+    if let Some(rating) = req.rating
+        && !(0..=5).contains(&rating)
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
 
     let db = state.db.lock().await;
 
-    let thumbnail_path = db.get_thumbnail_path(id)
+    let photo = db.get_photo(id)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    // 未提供的字段保持不变
+    let title = match req.title {
+        Some(t) => Some(t.trim().to_string()).filter(|t| !t.is_empty()),
+        None => photo.title.clone(),
+    };
+    let caption = match req.caption {
+        Some(c) => Some(c.trim().to_string()).filter(|c| !c.is_empty()),
+        None => photo.caption.clone(),
+    };
+    let rating = match req.rating {
+        Some(r) => Some(r).filter(|r| *r > 0),
+        None => photo.rating,
+    };
+
+    db.update_photo_metadata(id, title.as_deref(), caption.as_deref(), rating)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    drop(db);
+
+    // 写入 sidecar，使元数据随文件一起同步；读取-合并-写入在阻塞线程中完成。
+    // 数据库已更新，写入失败只记录警告，不影响本次编辑的结果
+    let meta = crate::xmp::XmpMetadata {
+        title: title.clone(),
+        caption: caption.clone(),
+        rating,
+    };
+    let media_path = std::path::PathBuf::from(&photo.local_path);
+    match tokio::task::spawn_blocking(move || crate::xmp::write_sidecar(&media_path, &meta)).await {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => tracing::warn!("Failed to write XMP sidecar for photo {}: {}", id, e),
+        Err(e) => tracing::warn!("XMP sidecar task for photo {} failed: {}", id, e),
+    }
+
+    Ok(Json(PhotoItem::from(crate::models::Photo {
+        title,
+        caption,
+        rating,
+        ..photo
+    })))
+}
 
-    if let Some(path) = thumbnail_path {
-        if let Ok(bytes) = tokio::fs::read(&path).await {
-            return Ok(Response::builder()
-                .header(header::CONTENT_TYPE, "image/jpeg")
-                .body(Body::from(bytes))
-                .unwrap());
+#[derive(Debug, Deserialize)]
+pub struct ThumbnailQuery {
+    /// 期望的最长边（像素），返回不小于该值的最小版本
+    pub size: Option<u32>,
+}
+
+/// GET /api/photos/:id/thumbnail - 获取缩略图
+///
+/// 根据 `?size=` 和 `Accept` 请求头选择合适的尺寸与格式。
+pub async fn get_thumbnail(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(query): Query<ThumbnailQuery>,
+    headers: axum::http::HeaderMap,
+) -> Result<impl IntoResponse, StatusCode> {
+    use axum::http::{HeaderValue, header};
+    use sha2::Digest;
+
+    let (renditions, photo) = {
+        let db = state.db.lock().await;
+        let renditions = db.list_renditions(id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let photo = db.get_photo(id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::NOT_FOUND)?;
+        (renditions, photo)
+    };
+
+    let accept = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok());
+    let (path, content_type, rendition) = match crate::thumbnail::select_rendition(&renditions, query.size, accept) {
+        Some(r) => {
+            let mime = crate::config::ThumbnailFormat::parse(&r.format)
+                .map(|f| f.mime_type())
+                .unwrap_or("image/jpeg");
+            (r.path.clone(), mime, format!("{}.{}", r.max_size, r.format))
         }
-    }
+        // 旧照片只有单个 JPEG 缩略图
+        None => (photo.thumbnail_path.ok_or(StatusCode::NOT_FOUND)?, "image/jpeg", "thumbnail".to_string()),
+    };
 
-    Err(StatusCode::NOT_FOUND)
+    // 配置变化后重新生成的缩略图路径不变，ETag 需包含生成配置
+    let etag = photo.file_hash.as_deref().map(|hash| {
+        let spec = photo.thumbnail_spec.as_deref().unwrap_or_default();
+        let spec_hash = format!("{:x}", sha2::Sha256::digest(spec.as_bytes()));
+        super::serve::etag(hash, &format!("{}_{}", rendition, &spec_hash[..8]))
+    });
+
+    let mut response = super::serve::serve_file(std::path::Path::new(&path), content_type, &headers, etag).await?;
+    let response_headers = response.headers_mut();
+    response_headers.insert(header::VARY, HeaderValue::from_static("Accept"));
+    response_headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(super::serve::CACHE_CONTROL));
+    Ok(response)
 }
 
-/// GET /api/albums - 获取相册列表
-pub async fn list_albums(
+#[derive(Debug, Deserialize)]
+pub struct NearDuplicatesQuery {
+    /// 最大汉明距离（0-64），默认取配置值
+    pub distance: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NearDuplicateCluster {
+    pub photos: Vec<PhotoItem>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NearDuplicatesResponse {
+    pub distance: u32,
+    pub clusters: Vec<NearDuplicateCluster>,
+}
+
+/// GET /api/photos/near-duplicates - 按感知哈希聚类视觉上近似重复的照片
+pub async fn list_near_duplicates(
     State(state): State<AppState>,
+    Query(query): Query<NearDuplicatesQuery>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let db = state.db.lock().await;
+    let distance = query
+        .distance
+        .unwrap_or(state.config.dedup.phash_max_distance)
+        .min(64);
+
+    let hashes = {
+        let db = state.db.lock().await;
+        db.list_perceptual_hashes()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    };
 
-    let albums = db.list_albums()
+    // 两两比较为 O(n²)，放到阻塞线程中执行
+    let clusters = tokio::task::spawn_blocking(move || crate::phash::cluster(&hashes, distance))
+        .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let result: Vec<HashMap<String, String>> = albums
-        .into_iter()
-        .map(|(name, count)| {
-            HashMap::from([
-                ("name".to_string(), name),
-                ("count".to_string(), count.to_string()),
-            ])
-        })
-        .collect();
+    let db = state.db.lock().await;
+    let mut result = Vec::with_capacity(clusters.len());
+    for ids in clusters {
+        let mut photos = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(photo) = db.get_photo(id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+                photos.push(PhotoItem::from(photo));
+            }
+        }
+        if photos.len() > 1 {
+            result.push(NearDuplicateCluster { photos });
+        }
+    }
 
-    Ok(Json(result))
+    Ok(Json(NearDuplicatesResponse {
+        distance,
+        clusters: result,
+    }))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ImageQuery {
+    /// 目标宽度（像素）
+    pub w: Option<u32>,
+    /// 目标高度（像素）
+    pub h: Option<u32>,
+    /// contain（默认）或 cover
+    #[serde(default)]
+    pub fit: crate::thumbnail::Fit,
+    /// jpeg / webp / avif
+    pub format: Option<String>,
+    /// original / jpeg / best（默认）
+    #[serde(default)]
+    pub variant: ImageVariant,
+    /// 以附件形式下载，文件名取原始文件名
+    #[serde(default)]
+    pub download: bool,
+}
+
+/// 返回原图还是 JPEG 变体（HEIC、RAW 转换结果）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageVariant {
+    /// 始终返回原图
+    Original,
+    /// JPEG 变体；没有变体时仅当原图本身是 JPEG 才返回原图
+    Jpeg,
+    /// `Accept` 中明确列出原图类型（如 `image/heic`）时返回原图，否则优先返回 JPEG 变体
+    #[default]
+    Best,
+}
+
+/// 根据扩展名推断 Content-Type
+fn content_type_for(path: &std::path::Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "webp" => "image/webp",
+        "heic" => "image/heic",
+        "heif" => "image/heif",
+        "mp4" | "mov" | "m4v" => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
+
+/// 替换文件名的扩展名（下载 JPEG 变体或转码结果时使用）
+fn with_extension(filename: &str, extension: &str) -> String {
+    let stem = std::path::Path::new(filename)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| filename.to_string());
+    format!("{}.{}", stem, extension)
+}
+
+impl ImageQuery {
+    /// 是否需要按需缩放/转码（否则直接返回原图）
+    fn is_transform(&self) -> bool {
+        self.w.is_some() || self.h.is_some() || self.format.is_some()
+    }
 }
 
 /// GET /api/photos/:id/image - 获取原图
+///
+/// 指定 `w` / `h` / `fit` / `format` 时返回按需生成的版本，结果缓存在
+/// `.thumbnails/cache` 中。原图与变体从磁盘流式返回，支持 `Range`
+/// （Safari 播放视频需要 206 响应）。`variant` 选择原图或 JPEG 变体，
+/// `download=true` 时附带 `Content-Disposition`。
 pub async fn get_image(
     State(state): State<AppState>,
     Path(id): Path<i64>,
+    Query(query): Query<ImageQuery>,
+    headers: axum::http::HeaderMap,
 ) -> Result<impl IntoResponse, StatusCode> {
-    use axum::response::Response;
-    use axum::body::Body;
-    use axum::http::header;
+    use axum::http::{HeaderValue, header};
 
-    let db = state.db.lock().await;
+    let photo = {
+        let db = state.db.lock().await;
+        db.get_photo(id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::NOT_FOUND)?
+    };
 
-    // 查询照片路径
-    let (local_path, has_jpeg): (String, bool) = db.conn.query_row(
-        "SELECT local_path, has_jpeg_variant FROM photos WHERE id = ?1",
-        [id],
-        |row| Ok((row.get(0)?, row.get(1)?))
-    ).map_err(|_| StatusCode::NOT_FOUND)?;
-
-    // 如果存在 JPEG 变体，返回 JPEG
-    let file_path = if has_jpeg {
-        let jpeg_path = std::path::PathBuf::from(&local_path)
-            .with_extension("jpg");
-        if jpeg_path.exists() {
-            jpeg_path
-        } else {
-            std::path::PathBuf::from(&local_path)
+    let original = std::path::PathBuf::from(&photo.local_path);
+    let jpeg = photo
+        .has_jpeg_variant
+        .then(|| original.with_extension("jpg"))
+        .filter(|path| path.exists());
+    let original_type = content_type_for(&original);
+
+    let (file_path, rendition) = match (query.variant, jpeg) {
+        (ImageVariant::Original, _) => (original, "original"),
+        (ImageVariant::Jpeg, Some(jpeg)) => (jpeg, "jpeg"),
+        (ImageVariant::Jpeg, None) if original_type == "image/jpeg" => (original, "original"),
+        (ImageVariant::Jpeg, None) => return Err(StatusCode::NOT_FOUND),
+        (ImageVariant::Best, Some(jpeg)) => {
+            let accept = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()).unwrap_or("");
+            if accept.contains(original_type) {
+                (original, "original")
+            } else {
+                (jpeg, "jpeg")
+            }
         }
+        (ImageVariant::Best, None) => (original, "original"),
+    };
+
+    let (mut response, download_name) = if query.is_transform() {
+        let (rendition, format) = rendition_key(&state, &query)?;
+        let etag = photo.file_hash.as_deref().map(|hash| super::serve::etag(hash, &rendition));
+        // 缓存仍有效时不必生成
+        let validators = super::serve::Validators { etag: etag.clone(), last_modified: None };
+        let response = if validators.is_not_modified(&headers) {
+            validators.not_modified()
+        } else {
+            let cache_id = photo.file_hash.clone().unwrap_or_else(|| format!("photo{}", id));
+            let key = format!("{}_{}", cache_id, rendition);
+            let bytes = render_cached(&state, &file_path, &key, &query, format).await?;
+            super::serve::serve_bytes(bytes, format.mime_type(), &headers, etag)
+        };
+        (response, with_extension(&photo.filename, format.extension()))
     } else {
-        std::path::PathBuf::from(&local_path)
+        let etag = photo.file_hash.as_deref().map(|hash| super::serve::etag(hash, rendition));
+        let response = super::serve::serve_file(&file_path, content_type_for(&file_path), &headers, etag).await?;
+        let name = if rendition == "jpeg" { with_extension(&photo.filename, "jpg") } else { photo.filename.clone() };
+        (response, name)
     };
 
-    // 读取文件
-    match tokio::fs::read(&file_path).await {
-        Ok(bytes) => {
-            // 根据扩展名设置 Content-Type
-            let content_type = match file_path.extension().and_then(|e| e.to_str()) {
-                Some("jpg") | Some("jpeg") => "image/jpeg",
-                Some("png") => "image/png",
-                Some("webp") => "image/webp",
-                Some("heic") => "image/heic",
-                Some("mp4") | Some("mov") => "video/mp4",
-                _ => "application/octet-stream",
-            };
-
-            Ok(Response::builder()
-                .header(header::CONTENT_TYPE, content_type)
-                .header(header::CACHE_CONTROL, "public, max-age=31536000")
-                .body(Body::from(bytes))
-                .unwrap())
-        }
-        Err(e) => {
-            tracing::error!("Failed to read image file {}: {}", file_path.display(), e);
-            Err(StatusCode::NOT_FOUND)
-        }
+    let response_headers = response.headers_mut();
+    if query.download
+        && let Ok(value) = HeaderValue::from_str(&super::serve::attachment(&download_name))
+    {
+        response_headers.insert(header::CONTENT_DISPOSITION, value);
     }
+    // 默认返回哪个版本取决于 Accept
+    if query.variant == ImageVariant::Best && !query.is_transform() {
+        response_headers.insert(header::VARY, HeaderValue::from_static("Accept"));
+    }
+    response_headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(super::serve::CACHE_CONTROL));
+    Ok(response)
 }
 
-/// DELETE /api/photos/:id - 删除照片
-pub async fn delete_photo(
-    State(state): State<AppState>,
-    Path(id): Path<i64>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let db = state.db.lock().await;
+/// 校验按需生成的参数，返回版本描述（用于缓存键和 ETag）与输出格式
+fn rendition_key(
+    state: &AppState,
+    query: &ImageQuery,
+) -> Result<(String, crate::config::ThumbnailFormat), StatusCode> {
+    use crate::config::ThumbnailFormat;
+
+    let thumbnails = &state.config.thumbnails;
+    let limit = thumbnails.max_on_demand_size;
+    if [query.w, query.h].into_iter().flatten().any(|v| v == 0 || v > limit) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
 
-    // 获取照片信息（路径和缩略图路径）
-    let (local_path, thumbnail_path): (String, Option<String>) = db.conn.query_row(
-        "SELECT local_path, thumbnail_path FROM photos WHERE id = ?1",
-        [id],
-        |row| Ok((row.get(0)?, row.get(1)?))
-    ).map_err(|_| StatusCode::NOT_FOUND)?;
+    let format = match query.format.as_deref() {
+        Some(f) => ThumbnailFormat::parse(f).ok_or(StatusCode::BAD_REQUEST)?,
+        None => ThumbnailFormat::Jpeg,
+    };
+    // 当前构建不支持的格式回退为 JPEG
+    let format = format.or_fallback();
+
+    let dim = |v: Option<u32>| v.map(|v| v.to_string()).unwrap_or_else(|| "auto".to_string());
+    let rendition = format!(
+        "{}x{}_{}_q{}.{}",
+        dim(query.w),
+        dim(query.h),
+        query.fit.as_str(),
+        thumbnails.on_demand_quality,
+        format.extension()
+    );
+    Ok((rendition, format))
+}
 
-    // 删除数据库记录
-    db.conn.execute(
-        "DELETE FROM photos WHERE id = ?1",
-        [id],
-    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+/// 从缓存读取按需生成的图像，未命中时生成并写入缓存
+async fn render_cached(
+    state: &AppState,
+    source: &std::path::Path,
+    key: &str,
+    query: &ImageQuery,
+    format: crate::config::ThumbnailFormat,
+) -> Result<Vec<u8>, StatusCode> {
+    if let Some(path) = state.image_cache.get(key)
+        && let Ok(bytes) = tokio::fs::read(&path).await
+    {
+        return Ok(bytes);
+    }
+
+    let cache = state.image_cache.clone();
+    let source = source.to_path_buf();
+    let cache_key = key.to_string();
+    let quality = state.config.thumbnails.on_demand_quality;
+    let (w, h, fit) = (query.w, query.h, query.fit);
+    tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<u8>> {
+        let bytes = crate::thumbnail::ThumbnailGenerator::render(&source, w, h, fit, format, quality)?;
+        if let Err(e) = cache.insert(&cache_key, &bytes) {
+            tracing::warn!("Failed to cache rendition {}: {}", cache_key, e);
+        }
+        Ok(bytes)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|e| {
+        tracing::warn!("Failed to render {}: {:#}", key, e);
+        StatusCode::UNSUPPORTED_MEDIA_TYPE
+    })
+}
+
+/// 删除照片的数据库记录及磁盘上的原图、JPEG 变体、XMP sidecar 和缩略图
+pub(super) async fn remove_photo(state: &AppState, id: i64) -> Result<(), StatusCode> {
+    let (photo, renditions) = {
+        let db = state.db.lock().await;
+        let photo = db.get_photo(id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::NOT_FOUND)?;
+        let renditions = db.list_renditions(id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        db.delete_photo(id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        (photo, renditions)
+    };
+    remove_photo_files(&photo, renditions).await;
+    Ok(())
+}
 
+/// 删除照片在磁盘上的文件（数据库记录已删除）
+pub(super) async fn remove_photo_files(photo: &crate::models::Photo, renditions: Vec<crate::models::PhotoRendition>) {
     // 删除主文件
-    let main_path = std::path::PathBuf::from(&local_path);
+    let main_path = std::path::PathBuf::from(&photo.local_path);
     if let Err(e) = tokio::fs::remove_file(&main_path).await {
         tracing::warn!("Failed to delete main file {}: {}", main_path.display(), e);
     }
 
     // 删除 JPEG 变体（如果存在）
     let jpeg_path = main_path.with_extension("jpg");
-    if jpeg_path.exists() {
-        if let Err(e) = tokio::fs::remove_file(&jpeg_path).await {
-            tracing::warn!("Failed to delete JPEG variant {}: {}", jpeg_path.display(), e);
-        }
+    if photo.has_jpeg_variant
+        && jpeg_path.exists()
+        && let Err(e) = tokio::fs::remove_file(&jpeg_path).await
+    {
+        tracing::warn!("Failed to delete JPEG variant {}: {}", jpeg_path.display(), e);
+    }
+
+    // 删除 XMP sidecar（如果存在）
+    let sidecar_path = crate::xmp::sidecar_path(&main_path);
+    if sidecar_path.exists()
+        && let Err(e) = tokio::fs::remove_file(&sidecar_path).await
+    {
+        tracing::warn!("Failed to delete XMP sidecar {}: {}", sidecar_path.display(), e);
     }
 
-    // 删除缩略图
-    if let Some(thumb) = thumbnail_path {
+    // 删除缩略图（默认缩略图通常也是其中一个版本）
+    let mut thumb_paths: Vec<String> = renditions.into_iter().map(|r| r.path).collect();
+    if let Some(thumb) = &photo.thumbnail_path
+        && !thumb_paths.contains(thumb)
+    {
+        thumb_paths.push(thumb.clone());
+    }
+    for thumb in thumb_paths {
         let thumb_path = std::path::PathBuf::from(&thumb);
         if let Err(e) = tokio::fs::remove_file(&thumb_path).await {
             tracing::warn!("Failed to delete thumbnail {}: {}", thumb_path.display(), e);
         }
     }
+}
+
+/// 照片在磁盘上的文件（原图、JPEG 变体、XMP sidecar）及其以 `original` 为原图时的位置
+fn photo_file_moves(photo: &crate::models::Photo, original: &std::path::Path) -> Vec<(std::path::PathBuf, std::path::PathBuf)> {
+    let current = std::path::PathBuf::from(&photo.local_path);
+    let mut files = vec![(current.clone(), original.to_path_buf())];
+    let jpeg = current.with_extension("jpg");
+    if photo.has_jpeg_variant && jpeg != current && jpeg.exists() {
+        files.push((jpeg, original.with_extension("jpg")));
+    }
+    let sidecar = crate::xmp::sidecar_path(&current);
+    if sidecar.exists() {
+        files.push((sidecar, crate::xmp::sidecar_path(original)));
+    }
+    files
+}
+
+/// `IMG_0001.jpg` 的第 n 个备选名 `IMG_0001 (n).jpg`
+pub(super) fn numbered_filename(filename: &str, n: u32) -> String {
+    match filename.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{} ({}).{}", stem, n, ext),
+        _ => format!("{} ({})", filename, n),
+    }
+}
+
+/// 撤销已完成的重命名（逆序）
+pub(super) async fn undo_renames(renames: &[(std::path::PathBuf, std::path::PathBuf)]) {
+    for (from, to) in renames.iter().rev() {
+        if let Err(e) = tokio::fs::rename(to, from).await {
+            tracing::warn!("Failed to move {} back to {}: {}", to.display(), from.display(), e);
+        }
+    }
+}
+
+/// 把照片的所有文件移动到相册目录 `dir`，返回对应的数据库更新和已完成的重命名
+///
+/// 一张照片的文件要么全部移动，要么全部留在原处。目标目录已有同名文件时，
+/// `keep_both` 为 true 则改用 `IMG_0001 (1).jpg` 这样的文件名，否则返回错误。
+pub(super) async fn move_photo_files(
+    photo: &crate::models::Photo,
+    album: &str,
+    dir: &std::path::Path,
+    keep_both: bool,
+) -> Result<(crate::db::PhotoMove, Vec<(std::path::PathBuf, std::path::PathBuf)>), String> {
+    let name = std::path::Path::new(&photo.local_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| photo.filename.clone());
+
+    let mut candidate = name.clone();
+    let mut n = 0;
+    let files = loop {
+        let files = photo_file_moves(photo, &dir.join(&candidate));
+        match files.iter().find(|(_, to)| to.exists()) {
+            None => break files,
+            Some((_, existing)) if !keep_both => {
+                let existing = existing.file_name().unwrap_or_default().to_string_lossy();
+                return Err(format!("{} already exists in {}", existing, album));
+            }
+            Some(_) => {
+                n += 1;
+                candidate = numbered_filename(&name, n);
+            }
+        }
+    };
+
+    let mut moved = Vec::with_capacity(files.len());
+    for (from, to) in files {
+        if let Err(e) = tokio::fs::rename(&from, &to).await {
+            undo_renames(&moved).await;
+            return Err(format!("failed to move {}: {}", from.display(), e));
+        }
+        moved.push((from, to));
+    }
+
+    let update = crate::db::PhotoMove {
+        id: photo.id,
+        album: album.to_string(),
+        // 只有因重名改名时才改变显示的文件名
+        filename: if candidate == name { photo.filename.clone() } else { candidate },
+        local_path: moved[0].1.to_string_lossy().to_string(),
+    };
+    Ok((update, moved))
+}
+
+/// DELETE /api/photos/:id - 删除照片
+pub async fn delete_photo(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, StatusCode> {
+    remove_photo(&state, id).await?;
 
     Ok(Json(serde_json::json!({
         "success": true,
@@ -0,0 +1,90 @@
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use super::AppState;
+use super::photos::{PhotoItem, remove_photo};
+use crate::websocket::WsEvent;
+
+/// GET /api/admin/review - 等待审核的照片（最早上传的在前）
+pub async fn list_pending(State(state): State<AppState>) -> Result<impl IntoResponse, StatusCode> {
+    let db = state.db.lock().await;
+    let photos = db.list_pending_photos()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(photos.into_iter().map(PhotoItem::from).collect::<Vec<_>>()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewRequest {
+    pub ids: Vec<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReviewResponse {
+    /// 实际处理的照片；不存在或已审核过的 ID 会被忽略
+    pub ids: Vec<i64>,
+    /// 剩余待审核数量
+    pub pending: i64,
+}
+
+fn resolved(state: &AppState, approved: Vec<i64>, rejected: Vec<i64>, pending: i64) {
+    let _ = state.event_sender.send(WsEvent::ReviewResolved { approved, rejected, pending });
+}
+
+/// POST /api/admin/review/approve - 批量通过，照片随即出现在相册中
+pub async fn approve_pending(
+    State(state): State<AppState>,
+    Json(req): Json<ReviewRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if req.ids.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let (approved, pending) = {
+        let db = state.db.lock().await;
+        let approved = db.approve_photos(&req.ids)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let pending = db.count_pending_photos()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        (approved, pending)
+    };
+    info!(count = approved.len(), "Approved pending photos");
+
+    resolved(&state, approved.clone(), Vec::new(), pending);
+    Ok(Json(ReviewResponse { ids: approved, pending }))
+}
+
+/// POST /api/admin/review/reject - 批量拒绝，删除照片及其文件
+pub async fn reject_pending(
+    State(state): State<AppState>,
+    Json(req): Json<ReviewRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if req.ids.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    // 只删除仍在待审核状态的照片，避免误删相册中的照片
+    let pending_ids: Vec<i64> = {
+        let db = state.db.lock().await;
+        db.list_photos_by_ids(&req.ids, true)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .into_iter()
+            .filter(|photo| photo.pending_review)
+            .map(|photo| photo.id)
+            .collect()
+    };
+
+    let mut rejected = Vec::with_capacity(pending_ids.len());
+    for id in pending_ids {
+        match remove_photo(&state, id).await {
+            Ok(()) => rejected.push(id),
+            // 并发请求已删除
+            Err(StatusCode::NOT_FOUND) => {}
+            Err(status) => return Err(status),
+        }
+    }
+    let pending = state.db.lock().await.count_pending_photos()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    info!(count = rejected.len(), "Rejected pending photos");
+
+    resolved(&state, Vec::new(), rejected.clone(), pending);
+    Ok(Json(ReviewResponse { ids: rejected, pending }))
+}
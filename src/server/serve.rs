@@ -0,0 +1,360 @@
+//! 媒体响应：从磁盘流式读取，支持条件请求（`If-None-Match` /
+//! `If-Modified-Since` → 304）与 `Range` / `If-Range`
+//!
+//! 只支持单个字节区间；多区间请求按规范忽略，返回完整内容。
+
+use axum::body::Body;
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::response::Response;
+use chrono::{DateTime, Utc};
+use std::path::Path;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+/// 同一 URL 的内容可能因删除后重新上传而改变，因此每次都用
+/// ETag / Last-Modified 重新验证（未变化时只返回 304）
+pub const CACHE_CONTROL: &str = "no-cache";
+
+/// 强 ETag：内容哈希 + 版本描述（如 `jpeg`、`256.webp`）
+pub fn etag(file_hash: &str, rendition: &str) -> String {
+    format!("\"{}_{}\"", file_hash, rendition)
+}
+
+/// 响应的缓存校验值
+#[derive(Debug, Default)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+impl Validators {
+    /// 客户端缓存是否仍然有效（可返回 304）
+    ///
+    /// 有 `If-None-Match` 时忽略 `If-Modified-Since`（RFC 9110 13.2.2）。
+    pub fn is_not_modified(&self, headers: &HeaderMap) -> bool {
+        if let Some(value) = headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+            let Some(etag) = &self.etag else {
+                return false;
+            };
+            // 弱比较：忽略 W/ 前缀
+            return value
+                .split(',')
+                .map(|tag| tag.trim())
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag);
+        }
+
+        let since = headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| DateTime::parse_from_rfc2822(v).ok());
+        match (since, self.last_modified) {
+            (Some(since), Some(modified)) => modified.timestamp() <= since.timestamp(),
+            _ => false,
+        }
+    }
+
+    /// `If-Range` 是否仍然有效（不满足时忽略 Range，返回完整内容）
+    ///
+    /// ETag 用强比较；日期只有与 `Last-Modified` 完全一致才算有效。
+    fn if_range_matches(&self, headers: &HeaderMap) -> bool {
+        let Some(value) = headers.get(header::IF_RANGE).and_then(|v| v.to_str().ok()) else {
+            return true;
+        };
+        if value.starts_with('"') {
+            return self.etag.as_deref() == Some(value);
+        }
+        match (DateTime::parse_from_rfc2822(value), self.last_modified) {
+            (Ok(date), Some(modified)) => date.timestamp() == modified.timestamp(),
+            _ => false,
+        }
+    }
+
+    fn apply(&self, mut builder: axum::http::response::Builder) -> axum::http::response::Builder {
+        if let Some(etag) = &self.etag {
+            builder = builder.header(header::ETAG, etag);
+        }
+        if let Some(modified) = self.last_modified {
+            builder = builder.header(header::LAST_MODIFIED, http_date(modified));
+        }
+        builder
+    }
+
+    /// 304 响应
+    pub fn not_modified(&self) -> Response {
+        self.apply(Response::builder().status(StatusCode::NOT_MODIFIED))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    /// 按请求头决定返回的区间
+    fn requested_range(&self, headers: &HeaderMap, len: u64) -> ByteRange {
+        match headers.get(header::RANGE).and_then(|v| v.to_str().ok()) {
+            Some(range) if self.if_range_matches(headers) => parse_range(range, len),
+            _ => ByteRange::Full,
+        }
+    }
+}
+
+/// `Range` 请求头的解析结果
+#[derive(Debug, PartialEq)]
+pub enum ByteRange {
+    /// 无（或忽略）Range，返回完整内容
+    Full,
+    /// 闭区间 [start, end]
+    Partial { start: u64, end: u64 },
+    /// 区间超出文件长度，返回 416
+    Unsatisfiable,
+}
+
+/// 解析 `Range: bytes=...`（`a-b`、`a-`、`-n`）
+pub fn parse_range(value: &str, len: u64) -> ByteRange {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+
+    let (start, end) = match (start.parse::<u64>(), end.parse::<u64>()) {
+        // 后缀区间：最后 n 个字节
+        (Err(_), Ok(suffix)) if start.is_empty() => {
+            if suffix == 0 || len == 0 {
+                return ByteRange::Unsatisfiable;
+            }
+            (len.saturating_sub(suffix), len - 1)
+        }
+        (Ok(start), Err(_)) if end.is_empty() => (start, len.saturating_sub(1)),
+        (Ok(start), Ok(end)) if start <= end => (start, end.min(len.saturating_sub(1))),
+        _ => return ByteRange::Full,
+    };
+
+    if start >= len {
+        ByteRange::Unsatisfiable
+    } else {
+        ByteRange::Partial { start, end }
+    }
+}
+
+/// `Content-Disposition: attachment`，非 ASCII 文件名用 RFC 5987 编码，
+/// 旧客户端看到的是用 `_` 替换后的名称
+pub fn attachment(filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| if c.is_ascii() && !c.is_ascii_control() && c != '"' && c != '\\' { c } else { '_' })
+        .collect();
+    let encoded: String = filename
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+    format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", fallback, encoded)
+}
+
+/// HTTP 日期格式（IMF-fixdate）
+pub fn http_date(time: DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+fn unsatisfiable(len: u64) -> Response {
+    Response::builder()
+        .status(StatusCode::RANGE_NOT_SATISFIABLE)
+        .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+        .header(header::ACCEPT_RANGES, "bytes")
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn partial_headers(response: &mut Response, start: u64, end: u64, len: u64) {
+    *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_RANGE,
+        HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end, len)).unwrap(),
+    );
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(end - start + 1));
+}
+
+/// 流式返回磁盘文件，不把整个文件读入内存
+///
+/// `Last-Modified` 取文件修改时间。
+pub async fn serve_file(
+    path: &Path,
+    content_type: &str,
+    headers: &HeaderMap,
+    etag: Option<String>,
+) -> Result<Response, StatusCode> {
+    let mut file = tokio::fs::File::open(path).await.map_err(|e| {
+        tracing::error!("Failed to open file {}: {}", path.display(), e);
+        StatusCode::NOT_FOUND
+    })?;
+    let metadata = file.metadata().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let len = metadata.len();
+    let validators = Validators {
+        etag,
+        last_modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+    };
+    if validators.is_not_modified(headers) {
+        return Ok(validators.not_modified());
+    }
+
+    let range = validators.requested_range(headers, len);
+    if range == ByteRange::Unsatisfiable {
+        return Ok(unsatisfiable(len));
+    }
+
+    let builder = validators.apply(
+        Response::builder()
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::ACCEPT_RANGES, "bytes"),
+    );
+
+    match range {
+        ByteRange::Partial { start, end } => {
+            file.seek(std::io::SeekFrom::Start(start))
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            let body = Body::from_stream(ReaderStream::new(file.take(end - start + 1)));
+            let mut response = builder.body(body).unwrap();
+            partial_headers(&mut response, start, end, len);
+            Ok(response)
+        }
+        _ => Ok(builder
+            .header(header::CONTENT_LENGTH, len)
+            .body(Body::from_stream(ReaderStream::new(file)))
+            .unwrap()),
+    }
+}
+
+/// 返回内存中的内容（按需生成的版本），同样支持条件请求与 Range
+pub fn serve_bytes(bytes: Vec<u8>, content_type: &str, headers: &HeaderMap, etag: Option<String>) -> Response {
+    let validators = Validators { etag, last_modified: None };
+    if validators.is_not_modified(headers) {
+        return validators.not_modified();
+    }
+
+    let len = bytes.len() as u64;
+    let range = validators.requested_range(headers, len);
+    let builder = validators.apply(
+        Response::builder()
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::ACCEPT_RANGES, "bytes"),
+    );
+
+    match range {
+        ByteRange::Unsatisfiable => unsatisfiable(len),
+        ByteRange::Partial { start, end } => {
+            let body = Body::from(bytes[start as usize..=end as usize].to_vec());
+            let mut response = builder.body(body).unwrap();
+            partial_headers(&mut response, start, end, len);
+            response
+        }
+        ByteRange::Full => builder.body(Body::from(bytes)).unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-99", 1000), ByteRange::Partial { start: 0, end: 99 });
+        assert_eq!(parse_range("bytes=900-", 1000), ByteRange::Partial { start: 900, end: 999 });
+        assert_eq!(parse_range("bytes=-100", 1000), ByteRange::Partial { start: 900, end: 999 });
+        assert_eq!(parse_range("bytes=-5000", 1000), ByteRange::Partial { start: 0, end: 999 });
+        assert_eq!(parse_range("bytes=500-5000", 1000), ByteRange::Partial { start: 500, end: 999 });
+        assert_eq!(parse_range("bytes=1000-", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-0", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-1,5-9", 1000), ByteRange::Full);
+        assert_eq!(parse_range("bytes=9-1", 1000), ByteRange::Full);
+        assert_eq!(parse_range("items=0-1", 1000), ByteRange::Full);
+    }
+
+    #[test]
+    fn test_attachment() {
+        assert_eq!(
+            attachment("夏天 2025.zip"),
+            "attachment; filename=\"__ 2025.zip\"; filename*=UTF-8''%E5%A4%8F%E5%A4%A9%202025.zip"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_serve_file_range() {
+        let path = std::env::temp_dir().join(format!("skynas_range_{}", uuid::Uuid::new_v4()));
+        let content: Vec<u8> = (0..=255u8).cycle().take(10_000).collect();
+        tokio::fs::write(&path, &content).await.unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=100-199"));
+        let response = serve_file(&path, "video/mp4", &headers, None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 100-199/10000");
+        let last_modified = response.headers()[header::LAST_MODIFIED].clone();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], &content[100..200]);
+
+        // If-Range with the current date keeps the range, a stale one does not
+        headers.insert(header::IF_RANGE, last_modified);
+        let response = serve_file(&path, "video/mp4", &headers, None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        headers.insert(header::IF_RANGE, HeaderValue::from_static("Mon, 01 Jan 2001 00:00:00 GMT"));
+        let response = serve_file(&path, "video/mp4", &headers, None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ACCEPT_RANGES], "bytes");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body.len(), content.len());
+
+        headers.remove(header::IF_RANGE);
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=20000-"));
+        let response = serve_file(&path, "video/mp4", &headers, None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */10000");
+
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_conditional_requests() {
+        let path = std::env::temp_dir().join(format!("skynas_etag_{}", uuid::Uuid::new_v4()));
+        tokio::fs::write(&path, b"thumbnail bytes").await.unwrap();
+        let tag = etag("abc123", "256.webp");
+        assert_eq!(tag, "\"abc123_256.webp\"");
+
+        let response = serve_file(&path, "image/webp", &HeaderMap::new(), Some(tag.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ETAG], tag.as_str());
+        let last_modified = response.headers()[header::LAST_MODIFIED].clone();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(&format!("\"other\", W/{}", tag)).unwrap());
+        let response = serve_file(&path, "image/webp", &headers, Some(tag.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], tag.as_str());
+
+        // A different ETag wins over a matching date
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("\"other\""));
+        headers.insert(header::IF_MODIFIED_SINCE, last_modified.clone());
+        let response = serve_file(&path, "image/webp", &headers, Some(tag.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        headers.remove(header::IF_NONE_MATCH);
+        let response = serve_file(&path, "image/webp", &headers, Some(tag.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        // If-Range with the current ETag keeps the range
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=0-8"));
+        headers.insert(header::IF_RANGE, HeaderValue::from_str(&tag).unwrap());
+        let response = serve_bytes(b"thumbnail bytes".to_vec(), "image/webp", &headers, Some(tag.clone()));
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"thumbnail");
+
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+}
@@ -0,0 +1,366 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{Html, IntoResponse, Response},
+};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{AppState, link_secret};
+use super::download::{DownloadQuery, plan, stream_zip};
+use super::photos::{ImageQuery, ImageVariant, PhotoItem, ThumbnailQuery, get_image, get_thumbnail};
+use crate::auth::{create_share_access_token, create_share_token, verify_share_token};
+use crate::models::{Photo, Share};
+
+/// 访问凭证 Cookie 名称，每个分享单独一个
+fn access_cookie(share_id: &str) -> String {
+    format!("skynas_share_{}", share_id.replace('-', ""))
+}
+
+fn cookie_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateShareRequest {
+    /// 与 `album` 二选一
+    pub photo_id: Option<i64>,
+    pub album: Option<String>,
+    pub password: Option<String>,
+    /// 有效期（小时），不填则不过期
+    pub expires_in_hours: Option<i64>,
+    /// 分享页面不提供原图和打包下载，只能在线浏览
+    ///
+    /// 只约束 `/s/:token/...` 路由，不是访问控制：局域网内未鉴权的
+    /// `/api/photos/:id/image` 仍可取到原图，照片 id 也会出现在分享信息中。
+    #[serde(default)]
+    pub disable_download: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShareItem {
+    #[serde(flatten)]
+    pub share: Share,
+    pub requires_password: bool,
+    pub expired: bool,
+    /// 分享页面地址（相对路径）
+    pub url: String,
+}
+
+fn share_item(share: Share, secret: &str) -> Result<ShareItem, StatusCode> {
+    let token = create_share_token(secret, &share).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(ShareItem {
+        requires_password: share.password_hash.is_some(),
+        expired: share.is_expired(),
+        url: format!("/s/{}", token),
+        share,
+    })
+}
+
+/// POST /api/admin/shares - 创建分享链接
+pub async fn create_share(
+    State(state): State<AppState>,
+    Json(req): Json<CreateShareRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let album = req.album.filter(|a| !a.trim().is_empty());
+    if req.photo_id.is_some() == album.is_some() || req.expires_in_hours.is_some_and(|h| h <= 0) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let password_hash = match req.password.as_deref().filter(|p| !p.is_empty()) {
+        Some(password) => Some(
+            bcrypt::hash(password, bcrypt::DEFAULT_COST).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        ),
+        None => None,
+    };
+
+    let db = state.db.lock().await;
+    let exists = match (req.photo_id, &album) {
+        (Some(id), _) => db.get_photo(id).map(|p| p.is_some()),
+        (None, Some(album)) => db.list_photos_by_album(album).map(|p| !p.is_empty()),
+        (None, None) => unreachable!(),
+    }
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !exists {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let now = Utc::now();
+    let share = Share {
+        id: uuid::Uuid::new_v4().to_string(),
+        photo_id: req.photo_id,
+        album,
+        password_hash,
+        disable_download: req.disable_download,
+        expires_at: req.expires_in_hours.map(|h| now + Duration::hours(h)),
+        created_at: now,
+    };
+    db.insert_share(&share).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let secret = link_secret(&db)?;
+
+    Ok((StatusCode::CREATED, Json(share_item(share, &secret)?)))
+}
+
+/// GET /api/admin/shares - 列出所有分享（含已过期）
+pub async fn list_shares(State(state): State<AppState>) -> Result<impl IntoResponse, StatusCode> {
+    let db = state.db.lock().await;
+    let secret = link_secret(&db)?;
+    let shares = db.list_shares().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let items = shares
+        .into_iter()
+        .map(|share| share_item(share, &secret))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Json(items))
+}
+
+/// DELETE /api/admin/shares/:id - 撤销分享
+pub async fn revoke_share(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let db = state.db.lock().await;
+    if db.delete_share(&id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+/// 解析分享 token：无效、过期或已撤销返回 404，需要密码但未解锁返回 401
+async fn authorize(state: &AppState, token: &str, headers: &HeaderMap) -> Result<Share, StatusCode> {
+    let db = state.db.lock().await;
+    let secret = link_secret(&db)?;
+    let claims = verify_share_token(token, &secret, "share").map_err(|_| StatusCode::NOT_FOUND)?;
+    let share = db
+        .get_share(&claims.sid)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .filter(|share| !share.is_expired())
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    if share.password_hash.is_some() {
+        let unlocked = cookie_value(headers, &access_cookie(&share.id))
+            .and_then(|cookie| verify_share_token(cookie, &secret, "share-access").ok())
+            .is_some_and(|access| access.sid == share.id);
+        if !unlocked {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+    }
+    Ok(share)
+}
+
+/// 分享中的照片；不在分享范围内的照片按不存在处理
+async fn shared_photos(state: &AppState, share: &Share) -> Result<Vec<Photo>, StatusCode> {
+    let db = state.db.lock().await;
+    let photos = match (share.photo_id, &share.album) {
+        (Some(id), _) => db.get_photo(id).map(|p| p.into_iter().collect()),
+        (None, Some(album)) => db.list_photos_by_album(album),
+        (None, None) => Ok(Vec::new()),
+    };
+    photos.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn ensure_in_share(state: &AppState, share: &Share, photo_id: i64) -> Result<(), StatusCode> {
+    let included = match (share.photo_id, &share.album) {
+        (Some(id), _) => id == photo_id,
+        (None, Some(album)) => state
+            .db
+            .lock()
+            .await
+            .photo_in_album(photo_id, album)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        (None, None) => false,
+    };
+    if included { Ok(()) } else { Err(StatusCode::NOT_FOUND) }
+}
+
+/// GET /s/:token - 只读相册页面
+pub async fn share_page() -> impl IntoResponse {
+    Html(include_str!("static/share.html"))
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShareInfo {
+    pub album: Option<String>,
+    pub disable_download: bool,
+    pub expires_at: Option<String>,
+    pub photos: Vec<PhotoItem>,
+}
+
+/// GET /s/:token/info - 分享内容
+pub async fn share_info(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, StatusCode> {
+    let share = authorize(&state, &token, &headers).await?;
+    let photos = shared_photos(&state, &share)
+        .await?
+        .into_iter()
+        .map(|photo| {
+            let mut item = PhotoItem::from(photo);
+            item.thumbnail_url = item
+                .thumbnail_url
+                .map(|_| format!("/s/{}/photos/{}/thumbnail", token, item.id));
+            // 不对外公开拍摄地点
+            item.latitude = None;
+            item.longitude = None;
+            item
+        })
+        .collect();
+
+    Ok(Json(ShareInfo {
+        album: share.album,
+        disable_download: share.disable_download,
+        expires_at: share.expires_at.map(|t| t.to_rfc3339()),
+        photos,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnlockRequest {
+    pub password: String,
+}
+
+/// POST /s/:token/unlock - 验证密码，通过后写入访问凭证 Cookie
+pub async fn unlock_share(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    Json(req): Json<UnlockRequest>,
+) -> Result<Response, StatusCode> {
+    let (share, secret) = {
+        let db = state.db.lock().await;
+        let secret = link_secret(&db)?;
+        let claims = verify_share_token(&token, &secret, "share").map_err(|_| StatusCode::NOT_FOUND)?;
+        let share = db
+            .get_share(&claims.sid)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .filter(|share| !share.is_expired())
+            .ok_or(StatusCode::NOT_FOUND)?;
+        (share, secret)
+    };
+    let Some(hash) = share.password_hash.as_deref() else {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    };
+    let valid = bcrypt::verify(&req.password, hash).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !valid {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let (access, max_age) =
+        create_share_access_token(&secret, &share.id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let cookie = format!(
+        "{}={}; Path=/s/; Max-Age={}; HttpOnly; SameSite=Lax",
+        access_cookie(&share.id),
+        access,
+        max_age
+    );
+    let mut response = StatusCode::NO_CONTENT.into_response();
+    response.headers_mut().insert(
+        header::SET_COOKIE,
+        HeaderValue::from_str(&cookie).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+    );
+    Ok(response)
+}
+
+/// GET /s/:token/photos/:id/thumbnail
+pub async fn share_thumbnail(
+    State(state): State<AppState>,
+    Path((token, id)): Path<(String, i64)>,
+    query: Query<ThumbnailQuery>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let share = authorize(&state, &token, &headers).await?;
+    ensure_in_share(&state, &share, id).await?;
+    get_thumbnail(State(state), Path(id), query, headers)
+        .await
+        .map(IntoResponse::into_response)
+}
+
+/// GET /s/:token/photos/:id/image - 参数同 `/api/photos/:id/image`
+///
+/// 禁止下载时不能带 `download` 或 `variant=original`，图片只返回不超过
+/// 最大缩略图尺寸的展示版本。这只限制分享路由本身；同一局域网内的
+/// `/api/photos/:id/image` 不经过分享检查，因此并不能保证访客拿不到原图。
+pub async fn share_image(
+    State(state): State<AppState>,
+    Path((token, id)): Path<(String, i64)>,
+    Query(mut query): Query<ImageQuery>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let share = authorize(&state, &token, &headers).await?;
+    ensure_in_share(&state, &share, id).await?;
+    if share.disable_download {
+        if query.download || query.variant == ImageVariant::Original {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        let photo = state
+            .db
+            .lock()
+            .await
+            .get_photo(id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::NOT_FOUND)?;
+        // 视频没有展示版本，只能按原文件播放
+        if !crate::video::is_video_file(&photo.filename) {
+            let thumbnails = &state.config.thumbnails;
+            let cap = thumbnails
+                .renditions
+                .iter()
+                .map(|r| r.max_size)
+                .max()
+                .unwrap_or(thumbnails.max_on_demand_size)
+                .min(thumbnails.max_on_demand_size);
+            // 只给了一边时原本就是按比例缩放，补上另一边后仍保持 contain
+            if query.w.is_none() || query.h.is_none() {
+                query.fit = crate::thumbnail::Fit::Contain;
+            }
+            query.w = Some(query.w.unwrap_or(cap).min(cap));
+            query.h = Some(query.h.unwrap_or(cap).min(cap));
+        }
+    }
+    get_image(State(state), Path(id), Query(query), headers)
+        .await
+        .map(IntoResponse::into_response)
+}
+
+/// GET /s/:token/download - 打包下载分享的全部照片
+pub async fn share_download(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    Query(query): Query<DownloadQuery>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let share = authorize(&state, &token, &headers).await?;
+    if share.disable_download {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let photos = shared_photos(&state, &share).await?;
+    if photos.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let name = share.album.as_deref().unwrap_or("photos");
+    let items = plan(photos, query.variant, false);
+    // manifest 中包含原始元数据，不提供给分享访客
+    Ok(stream_zip(items, false, &format!("{}.zip", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cookie_value() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, HeaderValue::from_static("a=1; skynas_share_abc=tok.en=; b=2"));
+        assert_eq!(cookie_value(&headers, "skynas_share_abc"), Some("tok.en="));
+        assert_eq!(cookie_value(&headers, "skynas_share_ab"), None);
+    }
+}
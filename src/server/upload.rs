@@ -45,7 +45,12 @@ pub struct UploadStatus {
 pub async fn init_upload(
     State(state): State<AppState>,
     Json(req): Json<InitUploadRequest>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<Json<InitUploadResponse>, StatusCode> {
+    if !super::is_valid_album_name(&req.album) {
+        warn!(album = %req.album, "Invalid album name");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
     let start = Instant::now();
     let upload_id = Uuid::new_v4().to_string();
 
@@ -364,13 +369,20 @@ pub async fn complete_upload(
     }
 
     // Get upload session
-    let session = {
+    let (session, is_guest) = {
         let db = state.db.lock().await;
-        db.get_upload_session(&upload_id)
+        let session = db.get_upload_session(&upload_id)
             .map_err(|e| {
                 error!(upload_id = %upload_id, error = %e, "Database error getting upload session");
                 StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+        let is_guest = db.get_guest_upload(&upload_id)
+            .map_err(|e| {
+                error!(upload_id = %upload_id, error = %e, "Database error getting guest upload");
+                StatusCode::INTERNAL_SERVER_ERROR
             })?
+            .is_some();
+        (session, is_guest)
     };
 
     let session = session.ok_or_else(|| {
@@ -418,8 +430,33 @@ pub async fn complete_upload(
 
     let final_path = album_path.join(&session.filename);
     debug!(upload_id = %upload_id, final_path = %final_path.display(), "Creating final file");
-    let mut final_file = tokio::fs::File::create(&final_path)
-        .await
+    // Guest uploads must never replace an existing file; the name was made
+    // unique at init, so a clash here means another upload took it since
+    let final_file = if is_guest {
+        tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&final_path)
+            .await
+    } else {
+        tokio::fs::File::create(&final_path).await
+    };
+    if let Err(e) = &final_file
+        && e.kind() == std::io::ErrorKind::AlreadyExists
+    {
+        warn!(upload_id = %upload_id, final_path = %final_path.display(), "Guest upload would replace an existing file");
+        let _ = tokio::fs::remove_dir_all(&session.temp_path).await;
+        let _ = state.db.lock().await.delete_upload_session(&upload_id);
+        state.active_uploads.lock().await.remove(&upload_id);
+        let _ = state.event_sender.send(WsEvent::UploadError {
+            upload_id: upload_id.clone(),
+            filename: session.filename.clone(),
+            error: "A file with this name already exists".to_string(),
+            stage: "merge".to_string(),
+        });
+        return Err(StatusCode::CONFLICT);
+    }
+    let mut final_file = final_file
         .map_err(|e| {
             error!(upload_id = %upload_id, final_path = %final_path.display(), error = %e, "Failed to create final file");
             let _ = state.event_sender.send(WsEvent::UploadError {
@@ -498,79 +535,105 @@ pub async fn complete_upload(
     let file_hash = format!("{:x}", sha2::Sha256::digest(&file_data));
     debug!(upload_id = %upload_id, hash = %file_hash, "File hash calculated");
 
-    // Try HEIC conversion
-    let converter = crate::converter::HeicConverter::new(state.config.heic_converter.clone());
-
-    // Send event - starting conversion
-    let _ = state.event_sender.send(WsEvent::HeicConverting {
-        upload_id: upload_id.clone(),
-        filename: session.filename.clone(),
-    });
-
-    let has_jpeg = if let Ok(Some(jpeg_path)) = converter.convert(&final_path) {
-        info!(
-            upload_id = %upload_id,
-            original = %session.filename,
-            converted = %jpeg_path.display(),
-            "HEIC converted to JPEG"
-        );
-        // Send conversion success event
-        let _ = state.event_sender.send(WsEvent::HeicConverted {
-            upload_id: upload_id.clone(),
-            original: session.filename.clone(),
-            converted: jpeg_path.to_string_lossy().to_string(),
-            success: true,
-        });
-        true
-    } else {
-        debug!(upload_id = %upload_id, filename = %session.filename, "No HEIC conversion needed or conversion skipped");
-        // Send conversion skip event (not an error, just not applicable)
-        let _ = state.event_sender.send(WsEvent::HeicConverted {
-            upload_id: upload_id.clone(),
-            original: session.filename.clone(),
-            converted: String::new(),
-            success: false,
-        });
-        false
-    };
-
     // Send event - saving to database
     let _ = state.event_sender.send(WsEvent::DatabaseSaving {
         upload_id: upload_id.clone(),
         filename: session.filename.clone(),
     });
 
+    // Import an existing XMP sidecar (e.g. synced from a desktop editor)
+    let sidecar = super::import_sidecar(&final_path).await;
+
+    // Read duration, resolution, codec etc. from video containers
+    let video = super::probe_video(&final_path).await.unwrap_or_default();
+
     // Save to database
     debug!(upload_id = %upload_id, "Saving to database");
-    {
+    let (photo_id, pending_review, duplicate) = {
         let db = state.db.lock().await;
-        let photo = crate::models::Photo {
-            id: 0,
-            filename: session.filename.clone(),
-            album: session.album.clone(),
-            file_hash: Some(file_hash),
-            size_bytes: session.total_size,
-            created_at: None,
-            uploaded_at: chrono::Utc::now(),
-            local_path: final_path.to_string_lossy().to_string(),
-            has_jpeg_variant: has_jpeg,
-            thumbnail_path: None,
-            width: None,
-            height: None,
+        // `insert_photo` upserts on the content hash, which would hand a guest
+        // the existing (possibly approved) photo; such a copy is dropped
+        // instead. The guest's quota stays charged for the transfer.
+        let existing = if is_guest {
+            db.find_photo_by_hash(&file_hash)
+                .map_err(|e| {
+                    error!(upload_id = %upload_id, error = %e, "Failed to look up photo by hash");
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?
+                .map(|photo| photo.id)
+        } else {
+            None
         };
-        db.insert_photo(&photo)
-            .map_err(|e| {
-                error!(upload_id = %upload_id, filename = %session.filename, error = %e, "Failed to save photo to database");
-                let _ = state.event_sender.send(WsEvent::UploadError {
-                    upload_id: upload_id.clone(),
-                    filename: session.filename.clone(),
-                    error: format!("Database error: {}", e),
-                    stage: "database".to_string(),
-                });
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
+        if let Some(existing_id) = existing {
+            (existing_id, false, true)
+        } else {
+            // 需要审核的访客上传先不出现在相册中
+            let pending_review = db.guest_upload_requires_review(&upload_id).unwrap_or_else(|e| {
+                warn!(upload_id = %upload_id, error = %e, "Failed to check review requirement");
+                true
+            });
+            let photo = crate::models::Photo {
+                id: 0,
+                filename: session.filename.clone(),
+                album: session.album.clone(),
+                file_hash: Some(file_hash.clone()),
+                size_bytes: session.total_size,
+                created_at: video.creation_time,
+                uploaded_at: chrono::Utc::now(),
+                local_path: final_path.to_string_lossy().to_string(),
+                has_jpeg_variant: false,
+                is_raw: crate::converter::raw::is_raw(&final_path),
+                title: sidecar.title,
+                caption: sidecar.caption,
+                rating: sidecar.rating,
+                width: video.width,
+                height: video.height,
+                duration_ms: video.duration_ms,
+                video_codec: video.codec,
+                frame_rate: video.frame_rate,
+                latitude: video.latitude,
+                longitude: video.longitude,
+                pending_review,
+                ..Default::default()
+            };
+            let photo_id = db.insert_photo(&photo)
+                .map_err(|e| {
+                    error!(upload_id = %upload_id, filename = %session.filename, error = %e, "Failed to save photo to database");
+                    let _ = state.event_sender.send(WsEvent::UploadError {
+                        upload_id: upload_id.clone(),
+                        filename: session.filename.clone(),
+                        error: format!("Database error: {}", e),
+                        stage: "database".to_string(),
+                    });
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?;
+            (photo_id, pending_review, false)
+        }
+    };
+
+    if duplicate {
+        info!(upload_id = %upload_id, photo_id = photo_id, "Guest upload duplicates an existing photo, discarding it");
+        if let Err(e) = tokio::fs::remove_file(&final_path).await {
+            warn!(upload_id = %upload_id, final_path = %final_path.display(), error = %e, "Failed to delete duplicate guest upload");
+        }
+    } else {
+        info!(upload_id = %upload_id, photo_id = photo_id, pending_review, "Photo saved to database");
+
+        if pending_review {
+            let pending = state.db.lock().await.count_pending_photos().unwrap_or_default();
+            let _ = state.event_sender.send(WsEvent::ReviewPending {
+                photo_id,
+                filename: session.filename.clone(),
+                album: session.album.clone(),
+                pending,
+            });
+        }
+
+        // Generate thumbnail and JPEG variant asynchronously; a HEIC burst
+        // queues on the converter pool instead of holding up uploads
+        super::spawn_thumbnail_task(&state, photo_id, &file_hash, &final_path);
+        super::spawn_conversion_task(&state, photo_id, &upload_id, &session.filename, &final_path);
     }
-    info!(upload_id = %upload_id, "Photo saved to database");
 
     // Show notification
     crate::notify::show_upload_complete(1, &session.album);
@@ -622,7 +685,6 @@ pub async fn complete_upload(
         album = %session.album,
         size_bytes = session.total_size,
         total_chunks = session.total_chunks,
-        has_jpeg_variant = has_jpeg,
         total_elapsed_ms = total_elapsed,
         "Upload completed successfully"
     );
@@ -632,7 +694,8 @@ pub async fn complete_upload(
         "upload_id": upload_id,
         "filename": session.filename,
         "album": session.album,
-        "size": session.total_size
+        "size": session.total_size,
+        "duplicate": duplicate
     })))
 }
 
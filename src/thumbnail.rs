@@ -1,79 +1,415 @@
-use crate::config::Config;
+use crate::config::{Config, ThumbnailFormat};
+use crate::models::PhotoRendition;
+use image::codecs::jpeg::JpegEncoder;
 use image::imageops::FilterType;
-use image::GenericImageView;
+use image::{ColorType, DynamicImage, GenericImageView, ImageEncoder};
+use serde::Deserialize;
 use std::path::{Path, PathBuf};
 use anyhow::{Result, Context};
 
 pub struct ThumbnailGenerator;
 
+/// 缩略图生成结果
+#[derive(Debug, Clone)]
+pub struct GeneratedThumbnail {
+    /// 默认缩略图路径（最小的 JPEG 版本，兼容旧客户端）
+    pub path: PathBuf,
+    /// 原图宽度
+    pub width: i32,
+    /// 原图高度
+    pub height: i32,
+    /// 基于缩略图计算的感知哈希（dHash）
+    pub perceptual_hash: u64,
+    /// BlurHash 占位图，用于缩略图加载前的渐进显示
+    pub blurhash: Option<String>,
+    /// 按配置生成的各尺寸/格式版本
+    pub renditions: Vec<PhotoRendition>,
+    /// 生成时的缩略图配置签名，用于判断缩略图是否过期
+    pub spec: String,
+}
+
 impl ThumbnailGenerator {
     /// 获取缩略图存储目录
     pub fn thumbnail_dir(config: &Config) -> PathBuf {
         config.storage.base_path.join(".thumbnails")
     }
 
+    /// 缩略图路径：由内容哈希和版本参数决定，按哈希前缀分两级目录
+    ///
+    /// 例如 `.thumbnails/ab/cd/abcd…_256.webp`。相同内容总是对应相同路径，
+    /// 重新生成时直接覆盖，不会留下孤立文件。
+    pub fn rendition_path(config: &Config, content_hash: &str, max_size: u32, format: ThumbnailFormat) -> PathBuf {
+        let shard = |range: std::ops::Range<usize>| content_hash.get(range).unwrap_or("00").to_string();
+        Self::thumbnail_dir(config)
+            .join(shard(0..2))
+            .join(shard(2..4))
+            .join(format!("{}_{}.{}", content_hash, max_size, format.extension()))
+    }
+
     /// 生成缩略图
     ///
+    /// 按 `config.thumbnails.renditions` 生成各尺寸、各格式的版本。
+    /// 当前构建不支持的格式（未启用 `webp` / `avif` feature）回退为 JPEG。
+    ///
     /// # Arguments
-    /// * `img_path` - 原图路径
+    /// * `img_path` - 原图（或视频）路径
+    /// * `content_hash` - 原文件的 SHA-256，用于确定缩略图路径
     /// * `config` - 服务器配置
-    /// * `max_size` - 缩略图最大边长（默认 300）
     ///
     /// # Returns
-    /// * (缩略图路径, 原图宽度, 原图高度)
+    /// * 缩略图路径、原图尺寸、感知哈希及所有版本
     pub async fn generate(
         img_path: &Path,
+        content_hash: &str,
         config: &Config,
-        max_size: u32,
-    ) -> Result<(PathBuf, i32, i32)> {
-        // 创建缩略图目录
-        let thumbnail_dir = Self::thumbnail_dir(config);
-        tokio::fs::create_dir_all(&thumbnail_dir).await?;
+    ) -> Result<GeneratedThumbnail> {
 
-        // 生成缩略图文件名（使用 UUID 避免冲突）
-        let thumbnail_filename = format!("thumb_{}.jpg", uuid::Uuid::new_v4());
-        let thumbnail_path = thumbnail_dir.join(&thumbnail_filename);
+        // 视频或 image 无法解码的格式，交给外部缩略图工具取帧
+        let is_video = crate::video::is_video_file(&img_path.to_string_lossy());
+        let decoded = if is_video {
+            Err(anyhow::anyhow!("{:?} is a video", img_path))
+        } else {
+            let path = img_path.to_path_buf();
+            tokio::task::spawn_blocking(move || {
+                crate::converter::decode_image(&path).with_context(|| format!("Failed to open image: {:?}", path))
+            })
+            .await?
+        };
+        let img = match decoded {
+            Ok(img) => img,
+            Err(e) => match crate::external_thumbnailer::extract_frame(img_path, config).await {
+                Ok(frame) => frame,
+                Err(external) if is_video => return Err(external),
+                Err(external) => {
+                    tracing::debug!("External thumbnailers failed: {:#}", external);
+                    return Err(e);
+                }
+            },
+        };
 
         // 在阻塞线程中执行图像处理
         let img_path = img_path.to_path_buf();
-        let thumbnail_path_clone = thumbnail_path.clone();
-
-        let (width, height) = tokio::task::spawn_blocking(move || -> Result<(i32, i32)> {
-            // 打开原图
-            let img = image::open(&img_path)
-                .with_context(|| format!("Failed to open image: {:?}", img_path))?;
+        let specs = config.thumbnails.renditions.clone();
+        let spec = config.thumbnails.signature();
+        let config = config.clone();
+        let content_hash = content_hash.to_string();
 
+        tokio::task::spawn_blocking(move || -> Result<GeneratedThumbnail> {
             // 获取原图尺寸
             let (orig_width, orig_height) = img.dimensions();
 
-            // 计算缩放比例
-            let ratio = if orig_width > orig_height {
-                max_size as f32 / orig_width as f32
-            } else {
-                max_size as f32 / orig_height as f32
-            };
+            // 从大到小依次缩放，每次基于上一档结果，避免重复处理原图
+            let mut sizes: Vec<u32> = specs.iter().map(|r| r.max_size).collect();
+            sizes.sort_unstable_by(|a, b| b.cmp(a));
+            sizes.dedup();
+
+            let mut renditions = Vec::new();
+            let mut current = img;
+            for size in sizes {
+                let (w, h) = current.dimensions();
+                if w.max(h) > size {
+                    // 使用 Lanczos3 算法保持质量
+                    current = current.resize(size, size, FilterType::Lanczos3);
+                }
+
+                for spec in specs.iter().filter(|r| r.max_size == size) {
+                    let format = spec.format.or_fallback();
+                    if format != spec.format {
+                        tracing::debug!("{} thumbnails not supported by this build, using JPEG", spec.format.as_str());
+                    }
+                    if renditions.iter().any(|r: &PhotoRendition| r.max_size == size && r.format == format.as_str()) {
+                        continue;
+                    }
+
+                    let path = Self::rendition_path(&config, &content_hash, size, format);
+                    Self::write_atomic(&path, |f| Self::encode(&current, format, spec.quality, std::io::BufWriter::new(f)))
+                        .with_context(|| format!("Failed to save thumbnail: {:?}", path))?;
+                    let size_bytes = std::fs::metadata(&path).map(|m| m.len() as i64).unwrap_or(0);
+
+                    renditions.push(PhotoRendition {
+                        max_size: size,
+                        format: format.as_str().to_string(),
+                        path: path.to_string_lossy().to_string(),
+                        width: current.width() as i32,
+                        height: current.height() as i32,
+                        size_bytes,
+                    });
+                }
+            }
+
+            // 基于最小的版本计算感知哈希，用于近似重复检测
+            let perceptual_hash = crate::phash::dhash(&current);
+
+            // 计算 BlurHash 占位图（失败不影响缩略图）
+            let blurhash = Self::blurhash(&current)
+                .map_err(|e| tracing::warn!("Failed to compute BlurHash for {:?}: {}", img_path, e))
+                .ok();
 
-            let new_width = (orig_width as f32 * ratio) as u32;
-            let new_height = (orig_height as f32 * ratio) as u32;
+            // 默认缩略图：最小的 JPEG 版本
+            let path = renditions
+                .iter()
+                .filter(|r| r.format == ThumbnailFormat::Jpeg.as_str())
+                .min_by_key(|r| r.max_size)
+                .or(renditions.first())
+                .map(|r| PathBuf::from(&r.path))
+                .context("No thumbnail renditions configured")?;
 
-            // 生成缩略图（使用 Lanczos3 算法保持质量）
-            let thumbnail = img.resize(new_width, new_height, FilterType::Lanczos3);
+            Ok(GeneratedThumbnail {
+                path,
+                width: orig_width as i32,
+                height: orig_height as i32,
+                perceptual_hash,
+                blurhash,
+                renditions,
+                spec,
+            })
+        }).await?
+    }
+
+    /// 先写入同目录下的临时文件再重命名，覆盖时读取方不会看到写了一半的文件
+    fn write_atomic(path: &Path, write: impl FnOnce(std::fs::File) -> Result<()>) -> Result<()> {
+        let dir = path.parent().context("Thumbnail path has no parent directory")?;
+        std::fs::create_dir_all(dir)?;
+        let tmp = dir.join(format!(".tmp_{}", uuid::Uuid::new_v4()));
+        let result = std::fs::File::create(&tmp)
+            .map_err(anyhow::Error::from)
+            .and_then(write)
+            .and_then(|_| std::fs::rename(&tmp, path).map_err(anyhow::Error::from));
+        if result.is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+        result
+    }
+
+    /// 按需生成指定尺寸和格式的图像
+    ///
+    /// `width` / `height` 为空表示该方向不限制；不会放大原图。
+    /// `Fit::Cover` 需要同时指定宽高，否则按 `Fit::Contain` 处理。
+    pub fn render(
+        img_path: &Path,
+        width: Option<u32>,
+        height: Option<u32>,
+        fit: Fit,
+        format: ThumbnailFormat,
+        quality: u8,
+    ) -> Result<Vec<u8>> {
+        let img = crate::converter::decode_image(img_path)
+            .with_context(|| format!("Failed to open image: {:?}", img_path))?;
+        let (orig_width, orig_height) = img.dimensions();
+
+        let resized = match (fit, width, height) {
+            (Fit::Cover, Some(w), Some(h)) => {
+                // 不放大：目标框超出原图时按比例缩小目标框
+                let scale = (orig_width as f64 / w as f64).min(orig_height as f64 / h as f64).min(1.0);
+                let (w, h) = (((w as f64 * scale) as u32).max(1), ((h as f64 * scale) as u32).max(1));
+                img.resize_to_fill(w, h, FilterType::Lanczos3)
+            }
+            _ => {
+                let w = width.unwrap_or(orig_width).min(orig_width);
+                let h = height.unwrap_or(orig_height).min(orig_height);
+                if w < orig_width || h < orig_height {
+                    img.resize(w, h, FilterType::Lanczos3)
+                } else {
+                    img
+                }
+            }
+        };
 
-            // 保存缩略图
-            thumbnail.save(&thumbnail_path_clone)
-                .with_context(|| format!("Failed to save thumbnail: {:?}", thumbnail_path_clone))?;
+        let mut buf = Vec::new();
+        Self::encode(&resized, format, quality, std::io::Cursor::new(&mut buf))?;
+        Ok(buf)
+    }
+
+    /// 以指定格式和质量编码
+    fn encode<W: std::io::Write>(img: &DynamicImage, format: ThumbnailFormat, quality: u8, writer: W) -> Result<()> {
+        let rgb = img.to_rgb8();
+        let (w, h) = rgb.dimensions();
+        let quality = quality.clamp(1, 100);
+
+        match format {
+            ThumbnailFormat::Jpeg => {
+                JpegEncoder::new_with_quality(writer, quality).write_image(&rgb, w, h, ColorType::Rgb8)?
+            }
+            #[cfg(feature = "webp")]
+            ThumbnailFormat::Webp => {
+                use image::codecs::webp::{WebPEncoder, WebPQuality};
+                WebPEncoder::new_with_quality(writer, WebPQuality::lossy(quality))
+                    .write_image(&rgb, w, h, ColorType::Rgb8)?
+            }
+            #[cfg(feature = "avif")]
+            ThumbnailFormat::Avif => {
+                use image::codecs::avif::AvifEncoder;
+                AvifEncoder::new_with_speed_quality(writer, 8, quality)
+                    .write_image(&rgb, w, h, ColorType::Rgb8)?
+            }
+            #[allow(unreachable_patterns)]
+            other => anyhow::bail!("{} encoding is not enabled in this build", other.as_str()),
+        }
+        Ok(())
+    }
+
+    /// 计算 BlurHash
+    ///
+    /// 先缩小到 32px 以内再编码，横图使用 4x3 个分量，竖图使用 3x4 个分量。
+    pub fn blurhash(img: &image::DynamicImage) -> Result<String> {
+        let small = img.thumbnail(32, 32).to_rgba8();
+        let (w, h) = small.dimensions();
+        let (cx, cy) = if w >= h { (4, 3) } else { (3, 4) };
+        blurhash::encode(cx, cy, w, h, small.as_raw())
+            .map_err(|e| anyhow::anyhow!("BlurHash encoding failed: {:?}", e))
+    }
+}
 
-            Ok((orig_width as i32, orig_height as i32))
-        }).await??;
+/// 按需缩放时的适配方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fit {
+    /// 完整显示在目标框内（默认）
+    #[default]
+    Contain,
+    /// 填满目标框，超出部分居中裁剪
+    Cover,
+}
 
-        Ok((thumbnail_path, width, height))
+impl Fit {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Fit::Contain => "contain",
+            Fit::Cover => "cover",
+        }
     }
 }
 
+/// 为请求挑选缩略图版本
+///
+/// 先排除客户端 `Accept` 不支持的格式（JPEG 始终可用），再选取最长边不小于
+/// `size` 的最小尺寸（未指定时取最小尺寸，均不足时取最大尺寸），同尺寸下
+/// 优先 AVIF、其次 WebP、最后 JPEG。
+pub fn select_rendition<'a>(
+    renditions: &'a [PhotoRendition],
+    size: Option<u32>,
+    accept: Option<&str>,
+) -> Option<&'a PhotoRendition> {
+    let accept = accept.unwrap_or("");
+    let preference = |format: &str| match ThumbnailFormat::parse(format) {
+        Some(ThumbnailFormat::Avif) if accept.contains("image/avif") => Some(0),
+        Some(ThumbnailFormat::Webp) if accept.contains("image/webp") => Some(1),
+        Some(ThumbnailFormat::Jpeg) => Some(2),
+        _ => None,
+    };
+
+    let candidates: Vec<&PhotoRendition> = renditions
+        .iter()
+        .filter(|r| preference(&r.format).is_some())
+        .collect();
+
+    let target = match size {
+        Some(size) => candidates
+            .iter()
+            .map(|r| r.max_size)
+            .filter(|s| *s >= size)
+            .min()
+            .or_else(|| candidates.iter().map(|r| r.max_size).max()),
+        None => candidates.iter().map(|r| r.max_size).min(),
+    }?;
+
+    candidates
+        .into_iter()
+        .filter(|r| r.max_size == target)
+        .min_by_key(|r| preference(&r.format))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::PathBuf;
+
+    #[test]
+    fn test_blurhash() {
+        let img = image::DynamicImage::ImageRgb8(image::ImageBuffer::from_fn(64, 48, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 5) as u8, 128])
+        }));
+        let hash = ThumbnailGenerator::blurhash(&img).unwrap();
+        // 1 (size) + 1 (max AC) + 4 (DC) + 2 * 11 (AC) characters for 4x3
+        assert_eq!(hash.len(), 28);
+    }
+
+    #[tokio::test]
+    async fn test_generate_renditions() {
+        use crate::config::RenditionConfig;
+
+        let dir = std::env::temp_dir().join(format!("skynas_thumb_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let src = dir.join("src.png");
+        image::DynamicImage::ImageRgb8(image::ImageBuffer::from_fn(400, 200, |x, _| {
+            image::Rgb([(x % 256) as u8, 80, 160])
+        }))
+        .save(&src)
+        .unwrap();
+
+        let mut config = Config::default();
+        config.storage.base_path = dir.clone();
+        config.thumbnails.renditions = vec![
+            RenditionConfig { max_size: 100, format: ThumbnailFormat::Jpeg, quality: 80 },
+            RenditionConfig { max_size: 300, format: ThumbnailFormat::Jpeg, quality: 80 },
+            // Larger than the source: kept at the original size
+            RenditionConfig { max_size: 1000, format: ThumbnailFormat::Jpeg, quality: 80 },
+        ];
+
+        let thumb = ThumbnailGenerator::generate(&src, "abcdef", &config).await.unwrap();
+        assert_eq!((thumb.width, thumb.height), (400, 200));
+        let dims: Vec<(u32, i32, i32)> = thumb.renditions.iter().map(|r| (r.max_size, r.width, r.height)).collect();
+        assert_eq!(dims, vec![(1000, 400, 200), (300, 300, 150), (100, 100, 50)]);
+        assert_eq!(thumb.path, dir.join(".thumbnails").join("ab").join("cd").join("abcdef_100.jpg"));
+        for r in &thumb.renditions {
+            assert!(std::path::Path::new(&r.path).exists());
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_select_rendition() {
+        let r = |max_size, format: &str| PhotoRendition {
+            max_size,
+            format: format.to_string(),
+            path: format!("{}.{}", max_size, format),
+            width: max_size as i32,
+            height: max_size as i32,
+            size_bytes: 0,
+        };
+        let all = vec![r(256, "jpeg"), r(256, "webp"), r(768, "jpeg"), r(768, "webp"), r(1600, "jpeg")];
+        let pick = |size, accept| select_rendition(&all, size, accept).map(|r| r.path.as_str());
+
+        assert_eq!(pick(None, None), Some("256.jpeg"));
+        assert_eq!(pick(None, Some("image/avif,image/webp,*/*")), Some("256.webp"));
+        assert_eq!(pick(Some(300), Some("image/webp")), Some("768.webp"));
+        assert_eq!(pick(Some(1000), Some("image/webp")), Some("1600.jpeg"));
+        assert_eq!(pick(Some(4000), None), Some("1600.jpeg"));
+        assert_eq!(select_rendition(&[], None, None).map(|r| r.max_size), None);
+    }
+
+    #[test]
+    fn test_render_fit() {
+        let dir = std::env::temp_dir().join(format!("skynas_render_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let src = dir.join("src.png");
+        image::DynamicImage::ImageRgb8(image::ImageBuffer::from_pixel(400, 200, image::Rgb([10, 20, 30])))
+            .save(&src)
+            .unwrap();
+
+        let dims = |w, h, fit| {
+            let bytes = ThumbnailGenerator::render(&src, w, h, fit, ThumbnailFormat::Jpeg, 80).unwrap();
+            image::load_from_memory(&bytes).unwrap().dimensions()
+        };
+        assert_eq!(dims(Some(100), Some(100), Fit::Contain), (100, 50));
+        assert_eq!(dims(Some(100), Some(100), Fit::Cover), (100, 100));
+        assert_eq!(dims(None, Some(50), Fit::Cover), (100, 50));
+        // Never upscaled
+        assert_eq!(dims(Some(1000), None, Fit::Contain), (400, 200));
+        assert_eq!(dims(Some(800), Some(800), Fit::Cover), (200, 200));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 
     #[test]
     fn test_thumbnail_dir() {
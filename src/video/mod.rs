@@ -0,0 +1,517 @@
+//! Video metadata extraction for MP4/MOV uploads
+//!
+//! Walks the ISO-BMFF / QuickTime atom tree of the `moov` box to pull out
+//! duration, display resolution, codec, frame rate, creation time and GPS
+//! position without decoding any media data.
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, TimeZone, Utc};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Metadata of a video file; every field is optional because writers differ
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VideoMetadata {
+    pub duration_ms: Option<i64>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub codec: Option<String>,
+    pub frame_rate: Option<f64>,
+    pub creation_time: Option<DateTime<Utc>>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+/// Whether a file name looks like an ISO-BMFF/QuickTime video
+pub fn is_video_file(filename: &str) -> bool {
+    let lower = filename.to_lowercase();
+    [".mp4", ".mov", ".m4v", ".3gp"]
+        .iter()
+        .any(|ext| lower.ends_with(ext))
+}
+
+/// Parse the metadata of a video file on disk
+pub fn probe(path: &Path) -> Result<VideoMetadata> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open video: {:?}", path))?;
+    parse(std::io::BufReader::new(file))
+}
+
+/// Parse metadata from any seekable ISO-BMFF stream
+pub fn parse<R: Read + Seek>(mut reader: R) -> Result<VideoMetadata> {
+    let moov = read_top_level_box(&mut reader, b"moov")?.context("No moov box found")?;
+    Ok(parse_moov(&moov))
+}
+
+/// Seconds between 1904-01-01 (QuickTime epoch) and 1970-01-01
+const QUICKTIME_EPOCH_OFFSET: i64 = 2_082_844_800;
+
+/// Refuse to load absurdly large `moov` boxes into memory
+const MAX_MOOV_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Find a top-level box by seeking over its siblings and return its payload
+fn read_top_level_box<R: Read + Seek>(reader: &mut R, kind: &[u8; 4]) -> Result<Option<Vec<u8>>> {
+    let end = reader.seek(SeekFrom::End(0))?;
+    let mut pos = reader.seek(SeekFrom::Start(0))?;
+
+    while pos + 8 <= end {
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        let mut size = u32::from_be_bytes(header[0..4].try_into().unwrap()) as u64;
+        let mut header_len = 8u64;
+        if size == 1 {
+            let mut large = [0u8; 8];
+            reader.read_exact(&mut large)?;
+            size = u64::from_be_bytes(large);
+            header_len = 16;
+        } else if size == 0 {
+            size = end - pos;
+        }
+        // `largesize` is untrusted and may overflow
+        let box_end = match pos.checked_add(size) {
+            Some(box_end) if size >= header_len && box_end <= end => box_end,
+            _ => bail!("Malformed box at offset {}", pos),
+        };
+
+        if &header[4..8] == kind {
+            let payload_len = size - header_len;
+            if payload_len > MAX_MOOV_SIZE {
+                bail!("{} box too large ({} bytes)", String::from_utf8_lossy(kind), payload_len);
+            }
+            let mut payload = vec![0u8; payload_len as usize];
+            reader.read_exact(&mut payload)?;
+            return Ok(Some(payload));
+        }
+
+        pos = reader.seek(SeekFrom::Start(box_end))?;
+    }
+
+    Ok(None)
+}
+
+/// Iterate over the child boxes in a payload as (type, payload) pairs
+fn children(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    let mut pos = 0usize;
+    std::iter::from_fn(move || {
+        if pos + 8 > data.len() {
+            return None;
+        }
+        let mut size = be_u32(data, pos)? as usize;
+        let kind = &data[pos + 4..pos + 8];
+        let mut header_len = 8;
+        if size == 1 {
+            size = be_u64(data, pos + 8)? as usize;
+            header_len = 16;
+        } else if size == 0 {
+            size = data.len() - pos;
+        }
+        let end = pos.checked_add(size).filter(|end| *end <= data.len())?;
+        if size < header_len {
+            return None;
+        }
+        let payload = &data[pos + header_len..end];
+        pos = end;
+        Some((kind, payload))
+    })
+}
+
+fn child<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    children(data).find(|(k, _)| k == kind).map(|(_, p)| p)
+}
+
+fn be_u16(data: &[u8], pos: usize) -> Option<u16> {
+    data.get(pos..pos + 2).map(|b| u16::from_be_bytes(b.try_into().unwrap()))
+}
+
+fn be_u32(data: &[u8], pos: usize) -> Option<u32> {
+    data.get(pos..pos + 4).map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+}
+
+fn be_i32(data: &[u8], pos: usize) -> Option<i32> {
+    be_u32(data, pos).map(|v| v as i32)
+}
+
+fn be_u64(data: &[u8], pos: usize) -> Option<u64> {
+    data.get(pos..pos + 8).map(|b| u64::from_be_bytes(b.try_into().unwrap()))
+}
+
+fn parse_moov(moov: &[u8]) -> VideoMetadata {
+    let mut meta = VideoMetadata::default();
+
+    if let Some(mvhd) = child(moov, b"mvhd")
+        && let Some((creation, timescale, duration)) = parse_time_header(mvhd)
+    {
+        meta.creation_time = quicktime_time(creation);
+        if timescale > 0 && duration > 0 && duration != u64::MAX && duration != u32::MAX as u64 {
+            meta.duration_ms = Some((duration as u128 * 1000 / timescale as u128) as i64);
+        }
+    }
+
+    for (_, trak) in children(moov).filter(|(k, _)| *k == b"trak") {
+        if let Some(track) = parse_video_track(trak) {
+            meta.width = track.width;
+            meta.height = track.height;
+            meta.codec = track.codec;
+            meta.frame_rate = track.frame_rate;
+            if meta.duration_ms.is_none() {
+                meta.duration_ms = track.duration_ms;
+            }
+            break;
+        }
+    }
+
+    // QuickTime user data: ©xyz holds an ISO 6709 location string
+    if let Some(udta) = child(moov, b"udta")
+        && let Some(xyz) = child(udta, b"\xa9xyz")
+        && let Some(len) = be_u16(xyz, 0)
+        && let Some(text) = xyz.get(4..4 + len as usize)
+        && let Some((lat, lon)) = parse_iso6709(&String::from_utf8_lossy(text))
+    {
+        meta.latitude = Some(lat);
+        meta.longitude = Some(lon);
+    }
+
+    // Apple metadata keys (iPhone recordings): more precise location and a
+    // creation date that carries the local time zone
+    if let Some(meta_box) = child(moov, b"meta") {
+        for (key, value) in apple_metadata(meta_box) {
+            match key.as_str() {
+                "com.apple.quicktime.location.ISO6709" => {
+                    if let Some((lat, lon)) = parse_iso6709(&value) {
+                        meta.latitude = Some(lat);
+                        meta.longitude = Some(lon);
+                    }
+                }
+                "com.apple.quicktime.creationdate" => {
+                    if let Some(time) = parse_apple_date(&value) {
+                        meta.creation_time = Some(time);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    meta
+}
+
+/// (creation time, timescale, duration) from an `mvhd` or `mdhd` payload
+fn parse_time_header(data: &[u8]) -> Option<(u64, u32, u64)> {
+    let version = *data.first()?;
+    if version == 1 {
+        Some((be_u64(data, 4)?, be_u32(data, 20)?, be_u64(data, 24)?))
+    } else {
+        Some((be_u32(data, 4)? as u64, be_u32(data, 12)?, be_u32(data, 16)? as u64))
+    }
+}
+
+fn quicktime_time(seconds: u64) -> Option<DateTime<Utc>> {
+    if seconds == 0 {
+        return None;
+    }
+    let unix = i64::try_from(seconds).ok()?.checked_sub(QUICKTIME_EPOCH_OFFSET)?;
+    Utc.timestamp_opt(unix, 0).single()
+}
+
+struct VideoTrack {
+    width: Option<i32>,
+    height: Option<i32>,
+    codec: Option<String>,
+    frame_rate: Option<f64>,
+    duration_ms: Option<i64>,
+}
+
+fn parse_video_track(trak: &[u8]) -> Option<VideoTrack> {
+    let mdia = child(trak, b"mdia")?;
+    let hdlr = child(mdia, b"hdlr")?;
+    if hdlr.get(8..12)? != b"vide" {
+        return None;
+    }
+
+    let mut track = VideoTrack {
+        width: None,
+        height: None,
+        codec: None,
+        frame_rate: None,
+        duration_ms: None,
+    };
+
+    if let Some(tkhd) = child(trak, b"tkhd") {
+        // Matrix and dimensions follow the version-dependent time fields
+        let base = if tkhd.first() == Some(&1) { 4 + 32 } else { 4 + 20 };
+        let matrix = base + 16;
+        let dims = matrix + 36;
+        if let (Some(w), Some(h)) = (be_u32(tkhd, dims), be_u32(tkhd, dims + 4)) {
+            let (mut w, mut h) = ((w >> 16) as i32, (h >> 16) as i32);
+            // A matrix with a == d == 0 rotates by 90/270 degrees (portrait
+            // iPhone recordings); report the displayed orientation
+            if let (Some(a), Some(d)) = (be_i32(tkhd, matrix), be_i32(tkhd, matrix + 16))
+                && a == 0
+                && d == 0
+            {
+                std::mem::swap(&mut w, &mut h);
+            }
+            if w > 0 && h > 0 {
+                track.width = Some(w);
+                track.height = Some(h);
+            }
+        }
+    }
+
+    let mut timescale = 0u32;
+    if let Some(mdhd) = child(mdia, b"mdhd")
+        && let Some((_, ts, duration)) = parse_time_header(mdhd)
+    {
+        timescale = ts;
+        if ts > 0 && duration > 0 {
+            track.duration_ms = Some((duration as u128 * 1000 / ts as u128) as i64);
+        }
+    }
+
+    if let Some(stbl) = child(mdia, b"minf").and_then(|minf| child(minf, b"stbl")) {
+        if let Some(stsd) = child(stbl, b"stsd")
+            && let Some(format) = stsd.get(12..16)
+        {
+            track.codec = Some(String::from_utf8_lossy(format).trim().to_string());
+        }
+
+        if let Some(stts) = child(stbl, b"stts") {
+            track.frame_rate = stts_frame_rate(stts, timescale);
+        }
+    }
+
+    Some(track)
+}
+
+/// Average frame rate from a time-to-sample table
+///
+/// Entry counts and deltas come from the file, so totals that overflow
+/// yield no frame rate rather than a wrapped one.
+fn stts_frame_rate(stts: &[u8], timescale: u32) -> Option<f64> {
+    let count = be_u32(stts, 4)?;
+    let mut samples = 0u64;
+    let mut ticks = 0u64;
+    for i in 0..count as usize {
+        let (Some(n), Some(delta)) = (be_u32(stts, 8 + i * 8), be_u32(stts, 12 + i * 8)) else {
+            break;
+        };
+        samples = samples.checked_add(n as u64)?;
+        ticks = ticks.checked_add((n as u64).checked_mul(delta as u64)?)?;
+    }
+    if samples == 0 || ticks == 0 || timescale == 0 {
+        return None;
+    }
+    let fps = samples as f64 * timescale as f64 / ticks as f64;
+    Some((fps * 1000.0).round() / 1000.0)
+}
+
+/// Key/value string pairs from a QuickTime `meta` box (`keys` + `ilst`)
+fn apple_metadata(meta: &[u8]) -> Vec<(String, String)> {
+    // ISO-style meta boxes carry version/flags before the children,
+    // QuickTime-style ones start with the `hdlr` box directly
+    let meta = if meta.get(4..8) == Some(b"hdlr") {
+        meta
+    } else {
+        meta.get(4..).unwrap_or_default()
+    };
+
+    let Some(keys) = child(meta, b"keys") else {
+        return Vec::new();
+    };
+    let mut names = Vec::new();
+    let mut pos = 8; // version/flags + entry count
+    while let Some(size) = be_u32(keys, pos) {
+        let size = size as usize;
+        let Some(end) = pos.checked_add(size).filter(|end| size >= 8 && *end <= keys.len()) else {
+            break;
+        };
+        names.push(String::from_utf8_lossy(&keys[pos + 8..end]).to_string());
+        pos = end;
+    }
+
+    let Some(ilst) = child(meta, b"ilst") else {
+        return Vec::new();
+    };
+    let mut values = Vec::new();
+    for (index, item) in children(ilst) {
+        let index = u32::from_be_bytes(index.try_into().unwrap()) as usize;
+        let Some(name) = index.checked_sub(1).and_then(|i| names.get(i)) else {
+            continue;
+        };
+        // data box: type indicator (4) + locale (4) + value; type 1 is UTF-8
+        if let Some(data) = child(item, b"data")
+            && be_u32(data, 0) == Some(1)
+            && let Some(value) = data.get(8..)
+        {
+            values.push((name.clone(), String::from_utf8_lossy(value).to_string()));
+        }
+    }
+    values
+}
+
+/// Parse an ISO 6709 string such as `+37.3349-122.0090+010.000/`
+fn parse_iso6709(text: &str) -> Option<(f64, f64)> {
+    let text = text.trim().trim_end_matches('/');
+    let bytes = text.as_bytes();
+    let mut starts: Vec<usize> = (0..bytes.len())
+        .filter(|&i| bytes[i] == b'+' || bytes[i] == b'-')
+        .collect();
+    if starts.first() != Some(&0) || starts.len() < 2 {
+        return None;
+    }
+    starts.push(text.len());
+    let lat: f64 = text[starts[0]..starts[1]].parse().ok()?;
+    let lon: f64 = text[starts[1]..starts[2]].parse().ok()?;
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+        return None;
+    }
+    Some((lat, lon))
+}
+
+/// Parse Apple's `2026-05-01T10:11:12+0800` style timestamps
+fn parse_apple_date(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    DateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%z")
+        .or_else(|_| DateTime::parse_from_rfc3339(text))
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn bx(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut out = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(payload);
+        out
+    }
+
+    fn concat(parts: &[Vec<u8>]) -> Vec<u8> {
+        parts.concat()
+    }
+
+    fn sample_movie() -> Vec<u8> {
+        // mvhd v0: 2026-01-01T00:00:00Z, timescale 600, 10.5s
+        let created = (1_767_225_600i64 + QUICKTIME_EPOCH_OFFSET) as u32;
+        let mut mvhd = vec![0u8; 4];
+        mvhd.extend(created.to_be_bytes());
+        mvhd.extend(created.to_be_bytes());
+        mvhd.extend(600u32.to_be_bytes());
+        mvhd.extend(6300u32.to_be_bytes());
+        mvhd.extend(vec![0u8; 80]);
+
+        // tkhd v0 with a 90 degree rotation matrix and 1920x1080 frames
+        let mut tkhd = vec![0u8; 4 + 20 + 16];
+        for v in [0i32, 0x10000, 0, -0x10000, 0, 0, 0, 0, 0x4000_0000] {
+            tkhd.extend(v.to_be_bytes());
+        }
+        tkhd.extend((1920u32 << 16).to_be_bytes());
+        tkhd.extend((1080u32 << 16).to_be_bytes());
+
+        let mut mdhd = vec![0u8; 12];
+        mdhd.extend(30000u32.to_be_bytes());
+        mdhd.extend(315000u32.to_be_bytes());
+        mdhd.extend([0u8; 4]);
+
+        let mut hdlr = vec![0u8; 8];
+        hdlr.extend(b"vide");
+        hdlr.extend([0u8; 13]);
+
+        let mut stsd = vec![0u8; 4];
+        stsd.extend(1u32.to_be_bytes());
+        stsd.extend(bx(b"hvc1", &[0u8; 78]));
+
+        // 315 frames of 1001 ticks at 30000 -> 29.97 fps
+        let mut stts = vec![0u8; 4];
+        stts.extend(1u32.to_be_bytes());
+        stts.extend(315u32.to_be_bytes());
+        stts.extend(1001u32.to_be_bytes());
+
+        let stbl = bx(b"stbl", &concat(&[bx(b"stsd", &stsd), bx(b"stts", &stts)]));
+        let minf = bx(b"minf", &stbl);
+        let mdia = bx(b"mdia", &concat(&[bx(b"mdhd", &mdhd), bx(b"hdlr", &hdlr), minf]));
+        let trak = bx(b"trak", &concat(&[bx(b"tkhd", &tkhd), mdia]));
+
+        let location = b"+48.8584+002.2945+035.000/";
+        let mut xyz = (location.len() as u16).to_be_bytes().to_vec();
+        xyz.extend([0x15, 0xc7]);
+        xyz.extend(location);
+        let udta = bx(b"udta", &bx(b"\xa9xyz", &xyz));
+
+        let moov = bx(b"moov", &concat(&[bx(b"mvhd", &mvhd), trak, udta]));
+        concat(&[bx(b"ftyp", b"qt  \0\0\0\0qt  "), bx(b"mdat", &[0u8; 64]), moov])
+    }
+
+    #[test]
+    fn test_parse_movie() {
+        let meta = parse(Cursor::new(sample_movie())).unwrap();
+        assert_eq!(meta.duration_ms, Some(10_500));
+        assert_eq!(meta.width, Some(1080));
+        assert_eq!(meta.height, Some(1920));
+        assert_eq!(meta.codec.as_deref(), Some("hvc1"));
+        assert_eq!(meta.frame_rate, Some(29.97));
+        assert_eq!(
+            meta.creation_time.map(|t| t.to_rfc3339()),
+            Some("2026-01-01T00:00:00+00:00".to_string())
+        );
+        assert_eq!(meta.latitude, Some(48.8584));
+        assert_eq!(meta.longitude, Some(2.2945));
+    }
+
+    #[test]
+    fn test_missing_moov() {
+        let data = bx(b"ftyp", b"isom\0\0\0\0");
+        assert!(parse(Cursor::new(data)).is_err());
+    }
+
+    #[test]
+    fn test_overflowing_largesize() {
+        // size 1 + a 64-bit largesize that wraps when added to the offset
+        let mut huge = 1u32.to_be_bytes().to_vec();
+        huge.extend(b"free");
+        huge.extend((u64::MAX - 4).to_be_bytes());
+        let data = concat(&[bx(b"ftyp", b"isom\0\0\0\0"), huge.clone()]);
+        assert!(parse(Cursor::new(data)).is_err());
+
+        let moov = concat(&[bx(b"mvhd", &[0u8; 100]), huge]);
+        assert_eq!(children(&moov).count(), 1);
+    }
+
+    #[test]
+    fn test_stts_frame_rate_overflow() {
+        let stts = |entries: &[(u32, u32)]| {
+            let mut data = vec![0u8; 4];
+            data.extend((entries.len() as u32).to_be_bytes());
+            for (n, delta) in entries {
+                data.extend(n.to_be_bytes());
+                data.extend(delta.to_be_bytes());
+            }
+            data
+        };
+        assert_eq!(stts_frame_rate(&stts(&[(300, 20)]), 600), Some(30.0));
+        // Each entry adds ~2^64 ticks, so the second one overflows the total
+        let huge = stts(&[(u32::MAX, u32::MAX), (u32::MAX, u32::MAX), (u32::MAX, u32::MAX)]);
+        assert_eq!(stts_frame_rate(&huge, 600), None);
+    }
+
+    #[test]
+    fn test_quicktime_time_out_of_range() {
+        assert_eq!(quicktime_time(QUICKTIME_EPOCH_OFFSET as u64).map(|t| t.timestamp()), Some(0));
+        assert!(quicktime_time(0).is_none());
+        assert!(quicktime_time(u64::MAX).is_none());
+        assert!(quicktime_time(i64::MAX as u64).is_none());
+    }
+
+    #[test]
+    fn test_parse_iso6709_and_apple_date() {
+        assert_eq!(parse_iso6709("+37.3349-122.0090/"), Some((37.3349, -122.009)));
+        assert_eq!(parse_iso6709("garbage"), None);
+        assert_eq!(
+            parse_apple_date("2026-05-01T10:11:12+0800").map(|t| t.to_rfc3339()),
+            Some("2026-05-01T02:11:12+00:00".to_string())
+        );
+    }
+}
@@ -44,6 +44,13 @@ pub enum WsEvent {
         path: String,
         size: i64,
     },
+    /// HEIC conversion waiting for a free converter slot
+    HeicQueued {
+        upload_id: String,
+        filename: String,
+        /// 1-based position in the conversion queue
+        position: usize,
+    },
     /// HEIC conversion in progress
     HeicConverting {
         upload_id: String,
@@ -55,6 +62,8 @@ pub enum WsEvent {
         original: String,
         converted: String,
         success: bool,
+        /// Converter error (including its stderr) when conversion failed
+        error: Option<String>,
     },
     /// Saving to database
     DatabaseSaving {
@@ -84,6 +93,34 @@ pub enum WsEvent {
     CloudSyncStarted,
     /// Cloud sync completed
     CloudSyncComplete { success: bool },
+    /// Thumbnail rebuild progress (also sent once when the job finishes)
+    ThumbnailRebuildProgress {
+        processed: i64,
+        total: i64,
+        regenerated: i64,
+        failed: i64,
+        done: bool,
+    },
+    /// A guest upload is waiting for moderation
+    ReviewPending {
+        photo_id: i64,
+        filename: String,
+        album: String,
+        /// Total number of photos awaiting review
+        pending: i64,
+    },
+    /// Pending photos were approved or rejected
+    ReviewResolved {
+        approved: Vec<i64>,
+        rejected: Vec<i64>,
+        pending: i64,
+    },
+    /// Result of a bulk photo operation
+    BulkCompleted {
+        action: String,
+        succeeded: Vec<i64>,
+        failed: Vec<i64>,
+    },
 }
 
 pub type EventSender = broadcast::Sender<WsEvent>;
@@ -160,6 +197,14 @@ fn serialize_event(event: WsEvent) -> serde_json::Value {
                 "size": size
             })
         }
+        WsEvent::HeicQueued { upload_id, filename, position } => {
+            serde_json::json!({
+                "type": "heic_queued",
+                "upload_id": upload_id,
+                "filename": filename,
+                "position": position
+            })
+        }
         WsEvent::HeicConverting { upload_id, filename } => {
             serde_json::json!({
                 "type": "heic_converting",
@@ -167,13 +212,14 @@ fn serialize_event(event: WsEvent) -> serde_json::Value {
                 "filename": filename
             })
         }
-        WsEvent::HeicConverted { upload_id, original, converted, success } => {
+        WsEvent::HeicConverted { upload_id, original, converted, success, error } => {
             serde_json::json!({
                 "type": "heic_converted",
                 "upload_id": upload_id,
                 "original": original,
                 "converted": converted,
-                "success": success
+                "success": success,
+                "error": error
             })
         }
         WsEvent::DatabaseSaving { upload_id, filename } => {
@@ -217,6 +263,41 @@ fn serialize_event(event: WsEvent) -> serde_json::Value {
                 "success": success
             })
         }
+        WsEvent::ThumbnailRebuildProgress { processed, total, regenerated, failed, done } => {
+            serde_json::json!({
+                "type": "thumbnail_rebuild_progress",
+                "processed": processed,
+                "total": total,
+                "regenerated": regenerated,
+                "failed": failed,
+                "done": done
+            })
+        }
+        WsEvent::ReviewPending { photo_id, filename, album, pending } => {
+            serde_json::json!({
+                "type": "review_pending",
+                "photo_id": photo_id,
+                "filename": filename,
+                "album": album,
+                "pending": pending
+            })
+        }
+        WsEvent::ReviewResolved { approved, rejected, pending } => {
+            serde_json::json!({
+                "type": "review_resolved",
+                "approved": approved,
+                "rejected": rejected,
+                "pending": pending
+            })
+        }
+        WsEvent::BulkCompleted { action, succeeded, failed } => {
+            serde_json::json!({
+                "type": "bulk_completed",
+                "action": action,
+                "succeeded": succeeded,
+                "failed": failed
+            })
+        }
     }
 }
 
@@ -289,6 +370,29 @@ mod tests {
         assert_eq!(json["error"], "Disk full");
         assert_eq!(json["stage"], "merge");
 
+        // Test HeicQueued
+        let event = WsEvent::HeicQueued {
+            upload_id: "test-123".to_string(),
+            filename: "IMG_1.heic".to_string(),
+            position: 3,
+        };
+        let json = serialize_event(event);
+        assert_eq!(json["type"], "heic_queued");
+        assert_eq!(json["position"], 3);
+
+        // Test HeicConverted failure
+        let event = WsEvent::HeicConverted {
+            upload_id: "test-123".to_string(),
+            original: "IMG_1.heic".to_string(),
+            converted: String::new(),
+            success: false,
+            error: Some("heif-convert exited with 1: unsupported file".to_string()),
+        };
+        let json = serialize_event(event);
+        assert_eq!(json["type"], "heic_converted");
+        assert_eq!(json["success"], false);
+        assert_eq!(json["error"], "heif-convert exited with 1: unsupported file");
+
         // Test CloudSync events
         let event = WsEvent::CloudSyncStarted;
         let json = serialize_event(event);
@@ -298,6 +402,48 @@ mod tests {
         let json = serialize_event(event);
         assert_eq!(json["type"], "cloud_sync_complete");
         assert_eq!(json["success"], true);
+
+        // Test ThumbnailRebuildProgress
+        let event = WsEvent::ThumbnailRebuildProgress {
+            processed: 10,
+            total: 40,
+            regenerated: 7,
+            failed: 1,
+            done: false,
+        };
+        let json = serialize_event(event);
+        assert_eq!(json["type"], "thumbnail_rebuild_progress");
+        assert_eq!(json["processed"], 10);
+        assert_eq!(json["done"], false);
+
+        // Test review events
+        let event = WsEvent::ReviewPending {
+            photo_id: 42,
+            filename: "IMG_1.jpg".to_string(),
+            album: "Wedding 2026".to_string(),
+            pending: 3,
+        };
+        let json = serialize_event(event);
+        assert_eq!(json["type"], "review_pending");
+        assert_eq!(json["photo_id"], 42);
+        assert_eq!(json["pending"], 3);
+
+        let event = WsEvent::ReviewResolved { approved: vec![1, 2], rejected: vec![3], pending: 0 };
+        let json = serialize_event(event);
+        assert_eq!(json["type"], "review_resolved");
+        assert_eq!(json["approved"], serde_json::json!([1, 2]));
+        assert_eq!(json["rejected"], serde_json::json!([3]));
+
+        // Test BulkCompleted
+        let event = WsEvent::BulkCompleted {
+            action: "move".to_string(),
+            succeeded: vec![1, 2],
+            failed: vec![3],
+        };
+        let json = serialize_event(event);
+        assert_eq!(json["type"], "bulk_completed");
+        assert_eq!(json["action"], "move");
+        assert_eq!(json["failed"], serde_json::json!([3]));
     }
 
     /// Test event channel creation and basic send/receive
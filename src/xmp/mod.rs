@@ -0,0 +1,369 @@
+//! XMP sidecar support
+//!
+//! Titles, captions and star ratings are written to `<name>.xmp` next to the
+//! original so that desktop editors (Lightroom, darktable, digiKam, ...) pick
+//! them up, and existing sidecars are imported when a file is ingested.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// Descriptive metadata carried in an XMP sidecar
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XmpMetadata {
+    pub title: Option<String>,
+    pub caption: Option<String>,
+    pub rating: Option<i32>,
+}
+
+impl XmpMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.caption.is_none() && self.rating.is_none()
+    }
+}
+
+const XMP_TEMPLATE_START: &str = r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/" x:xmptk="SkyNAS">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/">"#;
+
+const XMP_TEMPLATE_END: &str = r#"
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>
+"#;
+
+const DC_NS: &str = "http://purl.org/dc/elements/1.1/";
+const XMP_NS: &str = "http://ns.adobe.com/xap/1.0/";
+
+/// Sidecar path written for a media file (`IMG_0001.HEIC` -> `IMG_0001.xmp`)
+///
+/// This is the Lightroom/Bridge convention; it is shared by a HEIC original
+/// and its JPEG variant.
+pub fn sidecar_path(media_path: &Path) -> PathBuf {
+    media_path.with_extension("xmp")
+}
+
+/// Locate an existing sidecar, also accepting upper-case extensions and the
+/// darktable-style `IMG_0001.HEIC.xmp` naming
+pub fn find_sidecar(media_path: &Path) -> Option<PathBuf> {
+    let mut candidates = vec![sidecar_path(media_path), media_path.with_extension("XMP")];
+    if let Some(name) = media_path.file_name() {
+        let mut full = name.to_os_string();
+        full.push(".xmp");
+        candidates.push(media_path.with_file_name(full));
+    }
+    candidates.into_iter().find(|p| p.is_file())
+}
+
+/// Read and parse the sidecar of a media file, if there is one
+pub fn read_sidecar(media_path: &Path) -> Result<Option<XmpMetadata>> {
+    let Some(path) = find_sidecar(media_path) else {
+        return Ok(None);
+    };
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read XMP sidecar: {:?}", path))?;
+    let meta = parse(&content);
+    Ok((!meta.is_empty()).then_some(meta))
+}
+
+/// Write metadata to the sidecar of a media file
+///
+/// Properties other than title, description and rating in an existing
+/// sidecar (develop settings, keywords, ...) are preserved.
+pub fn write_sidecar(media_path: &Path, meta: &XmpMetadata) -> Result<PathBuf> {
+    let path = find_sidecar(media_path).unwrap_or_else(|| sidecar_path(media_path));
+    let content = match std::fs::read_to_string(&path) {
+        Ok(existing) => merge(&existing, meta).unwrap_or_else(|| render(meta)),
+        Err(_) => render(meta),
+    };
+    std::fs::write(&path, content)
+        .with_context(|| format!("Failed to write XMP sidecar: {:?}", path))?;
+    Ok(path)
+}
+
+/// Render a fresh XMP packet
+pub fn render(meta: &XmpMetadata) -> String {
+    let mut out = String::from(XMP_TEMPLATE_START);
+    out.push_str(&render_properties(meta));
+    out.push_str(XMP_TEMPLATE_END);
+    out
+}
+
+fn render_properties(meta: &XmpMetadata) -> String {
+    let mut out = String::new();
+    if let Some(title) = &meta.title {
+        out.push_str(&render_alt("dc:title", title));
+    }
+    if let Some(caption) = &meta.caption {
+        out.push_str(&render_alt("dc:description", caption));
+    }
+    if let Some(rating) = meta.rating {
+        out.push_str(&format!("\n   <xmp:Rating>{}</xmp:Rating>", rating));
+    }
+    out
+}
+
+fn render_alt(tag: &str, value: &str) -> String {
+    format!(
+        "\n   <{tag}>\n    <rdf:Alt>\n     <rdf:li xml:lang=\"x-default\">{}</rdf:li>\n    </rdf:Alt>\n   </{tag}>",
+        escape(value)
+    )
+}
+
+/// Replace our properties inside an existing packet
+///
+/// Returns `None` if the packet has no `rdf:Description` to update.
+fn merge(existing: &str, meta: &XmpMetadata) -> Option<String> {
+    let mut doc = existing.to_string();
+    for tag in ["dc:title", "dc:description", "xmp:Rating"] {
+        while let Some((start, end)) = find_element(&doc, tag) {
+            doc.replace_range(start..end, "");
+        }
+    }
+
+    let desc_start = doc.find("<rdf:Description")?;
+    let tag_end = desc_start + doc[desc_start..].find('>')?;
+    let mut start_tag = doc[desc_start..tag_end].to_string();
+    let self_closing = start_tag.ends_with('/');
+    if self_closing {
+        start_tag.pop();
+    }
+    start_tag = remove_attribute(&start_tag, "xmp:Rating");
+    start_tag = remove_attribute(&start_tag, "dc:title");
+    start_tag = remove_attribute(&start_tag, "dc:description");
+    if !start_tag.contains("xmlns:dc=") {
+        start_tag.push_str(&format!("\n    xmlns:dc=\"{}\"", DC_NS));
+    }
+    if !start_tag.contains("xmlns:xmp=") {
+        start_tag.push_str(&format!("\n    xmlns:xmp=\"{}\"", XMP_NS));
+    }
+
+    let mut replacement = start_tag;
+    replacement.push('>');
+    replacement.push_str(&render_properties(meta));
+    if self_closing {
+        replacement.push_str("\n  </rdf:Description>");
+    }
+    doc.replace_range(desc_start..=tag_end, &replacement);
+    Some(doc)
+}
+
+/// Parse title, description and rating out of an XMP packet
+///
+/// Both the attribute form (`xmp:Rating="3"`) and the element form are
+/// accepted. Ratings outside 1-5 (Lightroom uses -1 for rejected and 0 for
+/// unrated) are ignored.
+pub fn parse(xml: &str) -> XmpMetadata {
+    let rating = element_text(xml, "xmp:Rating")
+        .or_else(|| attribute_value(xml, "xmp:Rating"))
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .map(|v| v.round() as i32)
+        .filter(|v| (1..=5).contains(v));
+
+    XmpMetadata {
+        title: alt_text(xml, "dc:title"),
+        caption: alt_text(xml, "dc:description"),
+        rating,
+    }
+}
+
+/// Text of a language alternative, preferring the `x-default` entry
+fn alt_text(xml: &str, tag: &str) -> Option<String> {
+    let (start, end) = find_element(xml, tag)?;
+    let element = &xml[start..end];
+
+    let mut first = None;
+    let mut rest = element;
+    while let Some(li_start) = rest.find("<rdf:li") {
+        let after = &rest[li_start..];
+        let open_end = after.find('>')?;
+        let open_tag = &after[..open_end];
+        if open_tag.ends_with('/') {
+            rest = &after[open_end + 1..];
+            continue;
+        }
+        let close = after.find("</rdf:li>")?;
+        let text = unescape(&after[open_end + 1..close]);
+        if open_tag.contains("x-default") {
+            return non_empty(text);
+        }
+        if first.is_none() {
+            first = Some(text);
+        }
+        rest = &after[close..];
+    }
+
+    match first {
+        Some(text) => non_empty(text),
+        // Simple (non-Alt) values written by some tools
+        None => element_text(xml, tag).and_then(non_empty),
+    }
+}
+
+fn non_empty(text: String) -> Option<String> {
+    let trimmed = text.trim();
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
+}
+
+/// Byte range of `<tag ...>...</tag>` (or a self-closing `<tag/>`)
+fn find_element(xml: &str, tag: &str) -> Option<(usize, usize)> {
+    let open = format!("<{}", tag);
+    let mut search_from = 0;
+    loop {
+        let start = search_from + xml[search_from..].find(&open)?;
+        let after = &xml[start + open.len()..];
+        // Make sure we matched the whole tag name, not a prefix of it
+        match after.chars().next() {
+            Some('>') | Some(' ') | Some('/') | Some('\n') | Some('\r') | Some('\t') => {}
+            _ => {
+                search_from = start + open.len();
+                continue;
+            }
+        }
+        let open_end = start + xml[start..].find('>')?;
+        if xml[..open_end].ends_with('/') {
+            return Some((start, open_end + 1));
+        }
+        let close = format!("</{}>", tag);
+        let close_start = open_end + xml[open_end..].find(&close)?;
+        return Some((start, close_start + close.len()));
+    }
+}
+
+fn element_text(xml: &str, tag: &str) -> Option<String> {
+    let (start, end) = find_element(xml, tag)?;
+    let element = &xml[start..end];
+    let open_end = element.find('>')?;
+    let close = element.rfind("</")?;
+    if close <= open_end {
+        return None;
+    }
+    Some(unescape(&element[open_end + 1..close]))
+}
+
+fn attribute_value(xml: &str, name: &str) -> Option<String> {
+    let needle = format!("{}=", name);
+    let pos = xml.find(&needle)? + needle.len();
+    let quote = xml[pos..].chars().next()?;
+    if quote != '"' && quote != '\'' {
+        return None;
+    }
+    let value_start = pos + 1;
+    let value_end = value_start + xml[value_start..].find(quote)?;
+    Some(unescape(&xml[value_start..value_end]))
+}
+
+fn remove_attribute(tag: &str, name: &str) -> String {
+    let needle = format!("{}=", name);
+    let Some(pos) = tag.find(&needle) else {
+        return tag.to_string();
+    };
+    let value_start = pos + needle.len();
+    let Some(quote) = tag[value_start..].chars().next() else {
+        return tag.to_string();
+    };
+    let Some(len) = tag[value_start + 1..].find(quote) else {
+        return tag.to_string();
+    };
+    let end = value_start + 1 + len + 1;
+    let start = tag[..pos].trim_end().len();
+    format!("{}{}", &tag[..start], &tag[end..])
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        let after = &rest[amp..];
+        let Some(semi) = after.find(';') else {
+            out.push_str(after);
+            return out;
+        };
+        let entity = &after[1..semi];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if entity.starts_with("#x") || entity.starts_with("#X") => {
+                u32::from_str_radix(&entity[2..], 16).ok().and_then(char::from_u32)
+            }
+            _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(char::from_u32),
+            _ => None,
+        };
+        match decoded {
+            Some(c) => out.push(c),
+            None => out.push_str(&after[..=semi]),
+        }
+        rest = &after[semi + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_parse_round_trip() {
+        let meta = XmpMetadata {
+            title: Some("Beach & Sunset".to_string()),
+            caption: Some("Grandma's <first> trip".to_string()),
+            rating: Some(4),
+        };
+        let xml = render(&meta);
+        assert_eq!(parse(&xml), meta);
+    }
+
+    #[test]
+    fn test_parse_attribute_rating_and_rejected() {
+        let xml = r#"<rdf:Description rdf:about="" xmp:Rating="5"/>"#;
+        assert_eq!(parse(xml).rating, Some(5));
+
+        let xml = r#"<rdf:Description rdf:about="" xmp:Rating="-1"/>"#;
+        assert_eq!(parse(xml).rating, None);
+    }
+
+    #[test]
+    fn test_parse_prefers_x_default() {
+        let xml = r#"<dc:title><rdf:Alt>
+            <rdf:li xml:lang="de-DE">Strand</rdf:li>
+            <rdf:li xml:lang="x-default">Beach</rdf:li>
+        </rdf:Alt></dc:title>"#;
+        assert_eq!(parse(xml).title.as_deref(), Some("Beach"));
+    }
+
+    #[test]
+    fn test_merge_preserves_foreign_properties() {
+        let existing = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmlns:crs="http://ns.adobe.com/camera-raw-settings/1.0/"
+    xmp:Rating="2" crs:Exposure2012="+0.50"/>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+        let meta = XmpMetadata {
+            title: None,
+            caption: Some("Edited".to_string()),
+            rating: Some(3),
+        };
+        let merged = merge(existing, &meta).unwrap();
+        assert!(merged.contains(r#"crs:Exposure2012="+0.50""#));
+        assert!(!merged.contains(r#"xmp:Rating="2""#));
+        assert_eq!(parse(&merged), meta);
+    }
+}